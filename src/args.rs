@@ -1,54 +1,536 @@
 use std::collections::HashMap;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+
+use yake::{
+    CiFolding, ColorChoice, ExportFormat, InitTemplate, OutputFormat, Verbosity, YAKE_VERSION,
+};
 
 #[derive(Debug, PartialEq)]
 pub struct YakeArgs {
-    pub target: String,
+    /// Targets to execute in order, the default target of the Yakefile if none are given
+    pub targets: Vec<String>,
     pub params: HashMap<String, String>,
+    /// Env variables overriding the ones of the Yakefile and it's targets
+    pub env: HashMap<String, String>,
+    pub subcommand: Option<YakeSubcommand>,
+    /// Show the output of silent targets
+    pub no_silent: bool,
+    /// Wrap the output of targets in collapsible CI log sections
+    pub ci_folding: Option<CiFolding>,
+    /// Run only the dependencies of the target
+    pub deps_only: bool,
+    /// Run only the target, not it's dependencies
+    pub skip_deps: bool,
+    /// Run targets even if their outputs are up to date or their inputs unchanged
+    pub force: bool,
+    /// Print the commands of the target and it's dependencies instead of running them
+    pub dry_run: bool,
+    /// Number of targets run concurrently, 0 if not given
+    pub jobs: usize,
+    /// Keep running the targets not depending on a failed one
+    pub keep_going: bool,
+    /// Arguments after `--`, passed on to the target
+    pub args: Vec<String>,
+    /// Path of the Yakefile, searched for if not given
+    pub file: Option<String>,
+    /// Profile of the Yakefile applied before anything runs
+    pub profile: Option<String>,
+    /// Run the target again whenever it's inputs change
+    pub watch: bool,
+    /// Clear the screen before each run in watch mode
+    pub clear: bool,
+    /// Format of the output of the targets
+    pub output: OutputFormat,
+    /// Print the durations of the targets and their commands at the end
+    pub timings: bool,
+    /// How much is reported besides the output of the commands
+    pub verbosity: Verbosity,
+    /// Whether the output is colored
+    pub color: ColorChoice,
+    /// Fail instead of warning if deprecated targets are run
+    pub strict: bool,
+    /// Fail for missing env variables instead of asking for them
+    pub no_input: bool,
+    /// Download remote includes again instead of using the cached ones
+    pub update_includes: bool,
+    /// List the targets instead of running the default target
+    pub list: bool,
+}
+
+/// Subcommands which inspect the Yakefile or manage services instead of executing a
+/// target.
+#[derive(Debug, PartialEq)]
+pub enum YakeSubcommand {
+    /// Show where a target is defined
+    Which {
+        target: String,
+        format: OutputFormat,
+    },
+    /// Print the dependency graph of all targets, in DOT format
+    Graph,
+    /// Report all problems of the Yakefile and it's sub yakes
+    Check,
+    /// Write a new Yakefile into the current directory
+    Init { template: InitTemplate },
+    /// Print a Makefile converted into a Yakefile
+    Import { makefile: String },
+    /// Print the Yakefile converted into a Makefile or justfile
+    Export { format: ExportFormat },
+    /// Start services in the background, along with their dependencies
+    Start { services: Vec<String> },
+    /// Stop running services, all of them if none are given
+    Stop { services: Vec<String> },
+    /// Show whether services are running, all of them if none are given
+    Status { services: Vec<String> },
+    /// Print the remote includes pinned to the digests of their content, downloaded
+    /// again with `update_hashes`
+    Includes { update_hashes: bool },
 }
 
 pub fn create_cli_app() -> YakeArgs {
-    let matches = App::new("Yake")
-        .version("0.1")
+    let matches = match create_app(true).get_matches_safe() {
+        // clap takes targets with names similar to a subcommand for typos of it
+        Err(ref e) if e.kind == ErrorKind::InvalidSubcommand => create_app(false).get_matches(),
+        Err(e) => e.exit(),
+        Ok(matches) => matches,
+    };
+
+    let services = |matches: &ArgMatches| -> Vec<String> {
+        matches
+            .values_of("SERVICES")
+            .into_iter()
+            .flatten()
+            .map(|service| service.trim().to_string())
+            .collect()
+    };
+    let subcommand = match matches.subcommand() {
+        ("which", Some(which)) => Some(YakeSubcommand::Which {
+            target: which.value_of("TARGET").unwrap().trim().to_string(),
+            format: parse_output_format(which.value_of("format")),
+        }),
+        ("graph", Some(_)) => Some(YakeSubcommand::Graph),
+        ("check", Some(_)) => Some(YakeSubcommand::Check),
+        ("init", Some(init)) => Some(YakeSubcommand::Init {
+            template: match init.value_of("template") {
+                Some("rust") => InitTemplate::Rust,
+                Some("node") => InitTemplate::Node,
+                Some("docker") => InitTemplate::Docker,
+                _ => InitTemplate::Generic,
+            },
+        }),
+        ("import", Some(import)) => Some(YakeSubcommand::Import {
+            makefile: import.value_of("MAKEFILE").unwrap().to_string(),
+        }),
+        ("export", Some(export)) => Some(YakeSubcommand::Export {
+            format: match export.value_of("format") {
+                Some("just") => ExportFormat::Just,
+                _ => ExportFormat::Make,
+            },
+        }),
+        ("start", Some(start)) => Some(YakeSubcommand::Start {
+            services: services(start),
+        }),
+        ("stop", Some(stop)) => Some(YakeSubcommand::Stop {
+            services: services(stop),
+        }),
+        ("status", Some(status)) => Some(YakeSubcommand::Status {
+            services: services(status),
+        }),
+        ("includes", Some(includes)) => Some(YakeSubcommand::Includes {
+            update_hashes: includes.is_present("update-hashes"),
+        }),
+        _ => None,
+    };
+
+    let mut args = YakeArgs {
+        targets: matches
+            .values_of("TARGETS")
+            .into_iter()
+            .flatten()
+            .filter(|target| !target.contains('='))
+            .map(|target| target.trim().to_string())
+            .collect(),
+        params: HashMap::new(),
+        env: HashMap::new(),
+        subcommand,
+        no_silent: matches.is_present("no-silent"),
+        ci_folding: match matches.value_of("ci-folding") {
+            Some("github") => Some(CiFolding::Github),
+            Some("gitlab") => Some(CiFolding::Gitlab),
+            Some("auto") => CiFolding::detect(),
+            _ => None,
+        },
+        deps_only: matches.is_present("deps-only"),
+        skip_deps: matches.is_present("skip-deps"),
+        force: matches.is_present("force"),
+        dry_run: matches.is_present("dry-run"),
+        jobs: matches
+            .value_of("jobs")
+            .map_or(0, |jobs| jobs.parse().unwrap()),
+        keep_going: matches.is_present("keep-going"),
+        args: matches
+            .values_of("ARGS")
+            .map(|args| args.map(|arg| arg.to_string()).collect())
+            .unwrap_or_default(),
+        file: matches.value_of("file").map(|file| file.to_string()),
+        profile: matches
+            .value_of("profile")
+            .map(|profile| profile.to_string()),
+        watch: matches.is_present("watch"),
+        clear: matches.is_present("clear"),
+        output: parse_output_format(matches.value_of("output")),
+        timings: matches.is_present("timings"),
+        verbosity: match matches.occurrences_of("verbose") {
+            _ if matches.is_present("quiet") => Verbosity::Quiet,
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        },
+        strict: matches.is_present("strict"),
+        no_input: matches.is_present("no-input"),
+        update_includes: matches.is_present("update-includes"),
+        list: matches.is_present("list"),
+        color: match matches.value_of("color") {
+            Some("always") => ColorChoice::Always,
+            Some("never") => ColorChoice::Never,
+            _ => ColorChoice::Auto,
+        },
+    };
+
+    let parameter_values = matches
+        .values_of("TARGETS")
+        .into_iter()
+        .flatten()
+        .filter(|param| param.contains('='))
+        .chain(matches.values_of("param").into_iter().flatten());
+    for param in parameter_values {
+        if let Some((name, value)) = param.trim().split_once('=') {
+            args.params.insert(name.to_string(), value.to_string());
+        }
+    }
+    for variable in matches.values_of("env").into_iter().flatten() {
+        if let Some((name, value)) = variable.split_once('=') {
+            args.env.insert(name.trim().to_string(), value.to_string());
+        }
+    }
+
+    args
+}
+
+/// Creates the command line app, subcommands are left out without `with_subcommands`.
+fn create_app(with_subcommands: bool) -> App<'static, 'static> {
+    let app = App::new("Yake")
+        .version(YAKE_VERSION)
         .author("Tim Eggert <tim@elbart.com>")
         .about("Make with yaml files")
+        .after_help(
+            "EXIT CODES:\n    \
+             0    All targets passed\n    \
+             1    Several targets failed, or a target failed whose command exited with 2, 124 or 130\n    \
+             2    The Yakefile, the targets or the command line are invalid\n    \
+             124  A command timed out\n    \
+             130  yake was interrupted\n\n\
+             If a single target failed, yake exits with the exit code of it's failed command, unless it is one of the codes above.",
+        )
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
-            Arg::with_name("TARGET")
-                .help("Target to invoke")
-                .required(true)
-                .index(1),
+            Arg::with_name("TARGETS")
+                .help("Targets to invoke in order, defaults to meta.default_target of the Yakefile, or one picked from a list in a terminal. Arguments given as name=value are parameters of the targets")
+                .multiple(true)
+                .index(1)
+                .validator(|target| {
+                    if target.contains('=') {
+                        validate_param(target)
+                    } else {
+                        Ok(())
+                    }
+                }),
         )
         .arg(
             Arg::with_name("param")
-                .help("Parameters for the yake processing")
+                .help("Parameters of the target, given as name=value")
                 .takes_value(true)
                 .short("p")
                 .long("parameter")
                 .multiple(true)
                 .required(false)
-                .requires("TARGET"),
+                .validator(validate_param),
         )
-        .get_matches();
-
-    let target = matches
-        .value_of("TARGET")
-        .expect("No target specified")
-        .trim();
-
-    let mut args = YakeArgs {
-        target: target.to_string(),
-        params: HashMap::new(),
-    };
+        .arg(
+            Arg::with_name("env")
+                .help("Env variables overriding the ones of the Yakefile and it's targets, given as name=value")
+                .takes_value(true)
+                .short("e")
+                .long("env")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(validate_param),
+        )
+        .arg(
+            Arg::with_name("file")
+                .help("Path of the Yakefile to use, defaults to the nearest one in the current or a parent directory")
+                .takes_value(true)
+                .short("f")
+                .long("file")
+                .env("YAKEFILE"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .help("Profile of the Yakefile overriding env variables and fields of targets, e.g. prod")
+                .takes_value(true)
+                .long("profile")
+                .env("YAKE_PROFILE"),
+        )
+        .arg(
+            Arg::with_name("no-silent")
+                .help("Shows the output of silent targets")
+                .long("no-silent"),
+        )
+        .arg(
+            Arg::with_name("ci-folding")
+                .help("Wraps the output of each target in a collapsible CI log section")
+                .takes_value(true)
+                .long("ci-folding")
+                .possible_values(&["github", "gitlab", "auto"]),
+        )
+        .arg(
+            Arg::with_name("deps-only")
+                .help("Runs the dependencies of the target, but not the target itself")
+                .long("deps-only"),
+        )
+        .arg(
+            Arg::with_name("skip-deps")
+                .help("Runs the target, but not it's dependencies")
+                .long("skip-deps")
+                .conflicts_with("deps-only"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .help("Runs targets even if their outputs are up to date or inputs unchanged")
+                .long("force"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .help("Prints the commands which would be executed, without running them")
+                .short("n")
+                .long("dry-run"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .help("Number of targets run concurrently, once their dependencies finished, one by default. Their output is tagged with their names. Also bounds the commands of parallel targets running at the same time")
+                .takes_value(true)
+                .short("j")
+                .long("jobs")
+                .validator(|jobs| match jobs.parse::<usize>() {
+                    Ok(jobs) if jobs > 0 => Ok(()),
+                    _ => Err("must be a positive number".to_string()),
+                }),
+        )
+        .arg(
+            Arg::with_name("keep-going")
+                .help("Keeps running the targets not depending on a failed target after a target failed")
+                .short("k")
+                .long("keep-going"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .help("Runs the target again whenever it's inputs, or any file of the project, change")
+                .short("w")
+                .long("watch"),
+        )
+        .arg(
+            Arg::with_name("clear")
+                .help("Clears the screen before each run in watch mode")
+                .long("clear")
+                .requires("watch"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Format of the output, defaults to text. json prints a single document with the result and output of each target once all of them finished")
+                .takes_value(true)
+                .long("output")
+                .possible_values(&["text", "json"])
+                .conflicts_with_all(&["dry-run", "watch"]),
+        )
+        .arg(
+            Arg::with_name("timings")
+                .help("Prints how long each target and command took at the end")
+                .long("timings"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .help("Shows dependencies, working directories and shell invocations, given twice the env of each command as well")
+                .short("v")
+                .long("verbose")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .help("Shows only the output of the commands and failures")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .help("Fails for unknown fields of the Yakefiles and instead of warning if deprecated targets would run")
+                .long("strict"),
+        )
+        .arg(
+            Arg::with_name("no-input")
+                .help("Fails for missing env variables required by targets instead of asking for them")
+                .long("no-input"),
+        )
+        .arg(
+            Arg::with_name("update-includes")
+                .help("Downloads remote includes again instead of using the cached ones")
+                .long("update-includes"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .help("Lists the available targets instead of running the default target")
+                .short("l")
+                .long("list")
+                .conflicts_with("TARGETS"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .help("Colors the output, auto colors it if stdout is a terminal and NO_COLOR is not set")
+                .takes_value(true)
+                .long("color")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("ARGS")
+                .help("Arguments passed on to the commands as {{ARGS}} and $YAKE_ARGS")
+                .multiple(true)
+                .last(true),
+        );
+    if !with_subcommands {
+        return app;
+    }
+    app.subcommand(
+        SubCommand::with_name("which")
+            .about("Shows the Yakefile and line defining a target")
+            .arg(
+                Arg::with_name("TARGET")
+                    .help("Target to look up")
+                    .required(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .help("Output format")
+                    .takes_value(true)
+                    .long("format")
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("graph")
+            .about("Prints the dependency graph of all targets")
+            .arg(
+                Arg::with_name("format")
+                    .help("Output format")
+                    .takes_value(true)
+                    .long("format")
+                    .possible_values(&["dot"])
+                    .default_value("dot"),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("check").about(
+            "Reports all problems of the Yakefile and it's sub yakes, without running anything",
+        ),
+    )
+    .subcommand(
+        SubCommand::with_name("init")
+            .about("Writes a new Yakefile with example targets into the current directory")
+            .arg(
+                Arg::with_name("template")
+                    .help("Kind of project the targets are for")
+                    .takes_value(true)
+                    .long("template")
+                    .possible_values(&["generic", "rust", "node", "docker"])
+                    .default_value("generic"),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("import")
+            .about("Converts a Makefile into a Yakefile, printed to stdout")
+            .arg(
+                Arg::with_name("MAKEFILE")
+                    .help("Path of the Makefile")
+                    .default_value("Makefile")
+                    .index(1),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("export")
+            .about("Converts the Yakefile into a Makefile or justfile, printed to stdout")
+            .arg(
+                Arg::with_name("format")
+                    .help("Format to export to")
+                    .takes_value(true)
+                    .long("format")
+                    .possible_values(&["make", "just"])
+                    .default_value("make"),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("start")
+            .about("Starts services in the background, along with their dependencies")
+            .arg(
+                Arg::with_name("SERVICES")
+                    .help("Services to start")
+                    .required(true)
+                    .multiple(true),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("stop")
+            .about("Stops running services")
+            .arg(
+                Arg::with_name("SERVICES")
+                    .help("Services to stop, all of them if none are given")
+                    .multiple(true),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("status")
+            .about("Shows whether services are running")
+            .arg(
+                Arg::with_name("SERVICES")
+                    .help("Services to show, all of them if none are given")
+                    .multiple(true),
+            ),
+    )
+    .subcommand(
+        SubCommand::with_name("includes")
+            .about("Prints the remote includes pinned to the digests of their content")
+            .arg(
+                Arg::with_name("update-hashes")
+                    .help("Downloads the remote includes again and pins them to their current content")
+                    .long("update-hashes"),
+            ),
+    )
+}
 
-    if let Some(parameter_values) = matches.values_of("param") {
-        for param in parameter_values {
-            match param.trim().split("=").collect::<Vec<&str>>().as_slice() {
-                [first, last] => args.params.insert(first.to_string(), last.to_string()),
-                _ => None,
-            };
-        }
+/// Output format given by name, text by default.
+fn parse_output_format(format: Option<&str>) -> OutputFormat {
+    match format {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
     }
+}
 
-    args
+/// Checks that a parameter is given as `name=value`, the value may contain `=`.
+fn validate_param(param: String) -> Result<(), String> {
+    match param.split_once('=') {
+        Some((name, _)) if !name.trim().is_empty() => Ok(()),
+        _ => Err(format!("expected name=value, got {}", param)),
+    }
 }
@@ -0,0 +1,222 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use interpolation::replace_placeholders;
+
+/// Gets the value of a built-in placeholder, `None` for other names. Built-in
+/// placeholders are available in commands and env values without being defined:
+/// `git.sha`, `git.short_sha`, `git.branch`, `timestamp`, `hostname` and `uuid`. Git
+/// values are the ones of the repository `dir` is in, the timestamp is the current UTC
+/// time in ISO 8601 format and the uuid a random one, different for each call. See
+/// `BuiltinValues` for values which stay the same during an execution.
+pub fn builtin_value(name: &str, dir: &Path) -> Option<Result<String, String>> {
+    let value = match name {
+        "git.sha" => git(dir, &["rev-parse", "HEAD"]),
+        "git.short_sha" => git(dir, &["rev-parse", "--short", "HEAD"]),
+        "git.branch" => git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        "timestamp" => Ok(format_timestamp(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        )),
+        "hostname" => hostname(),
+        "uuid" => Ok(random_uuid()),
+        _ => return None,
+    };
+    Some(value.map_err(|e| format!("Unable to get {{{{{}}}}}: {}", name, e)))
+}
+
+/// Values of the built-in placeholders of an execution. Each value is determined once
+/// and then reused, so that all env variables and commands get the same uuid and
+/// timestamp and git runs once per repository. Clones share the values.
+#[derive(Debug, Default, Clone)]
+pub struct BuiltinValues(Arc<Mutex<HashMap<(PathBuf, String), String>>>);
+
+impl BuiltinValues {
+    /// Value of a built-in placeholder, see `builtin_value`, determined on first use.
+    pub fn get(&self, name: &str, dir: &Path) -> Option<Result<String, String>> {
+        // git values depend on the repository, the others are the same everywhere
+        let key_dir = match name.starts_with("git.") {
+            true => dir.to_path_buf(),
+            false => PathBuf::new(),
+        };
+        let key = (key_dir, name.to_string());
+        // locked while the value is determined, concurrent targets get the same one
+        let mut values = self.0.lock().unwrap();
+        if let Some(value) = values.get(&key) {
+            return Some(Ok(value.clone()));
+        }
+        let value = builtin_value(name, dir)?;
+        if let Ok(ref value) = value {
+            values.insert(key, value.clone());
+        }
+        Some(value)
+    }
+
+    /// Forgets the values, new ones are determined for the next execution.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// Values are not part of what a yake is.
+impl PartialEq for BuiltinValues {
+    fn eq(&self, _: &BuiltinValues) -> bool {
+        true
+    }
+}
+
+/// Replaces the built-in placeholders of a text with their values, see
+/// `BuiltinValues`. Names which are `defined` otherwise and all other placeholders are
+/// kept.
+pub fn expand_builtins(
+    text: &str,
+    dir: &Path,
+    defined: &HashMap<String, String>,
+    values: &BuiltinValues,
+) -> Result<String, String> {
+    if !text.contains("{{") {
+        return Ok(text.to_string());
+    }
+    replace_placeholders(text, |name| {
+        if defined.contains_key(name) {
+            return Ok(None);
+        }
+        values.get(name, dir).transpose()
+    })
+}
+
+/// Runs git within a directory and returns it's output.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Name of the host yake runs on.
+fn hostname() -> Result<String, String> {
+    if let Some(name) = env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .filter(|name| !name.is_empty())
+    {
+        return Ok(name);
+    }
+    let output = Command::new("hostname")
+        .output()
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Formats seconds since the unix epoch as UTC date and time, e.g.
+/// `2024-03-01T12:30:00Z`.
+fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // civil date from days since the epoch, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Creates a random version 4 uuid. The randomly seeded hashers of the standard
+/// library are random enough for ids.
+fn random_uuid() -> String {
+    let random = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0),
+        );
+        hasher.finish()
+    };
+    let bits = (u128::from(random()) << 64 | u128::from(random())) & !(0xf000 << 64) | 0x4000 << 64;
+    let bits = bits & !(0xc000 << 48) | 0x8000 << 48;
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1709296200), "2024-03-01T12:30:00Z");
+    }
+
+    #[test]
+    fn test_random_uuid() {
+        let uuid = random_uuid();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!("89ab".contains(&uuid[19..20]));
+        assert_ne!(uuid, random_uuid());
+    }
+
+    #[test]
+    fn test_builtin_value() {
+        let sha = builtin_value("git.sha", Path::new(".")).unwrap().unwrap();
+        assert_eq!(sha.len(), 40);
+        assert!(builtin_value("git.sha", Path::new("/")).unwrap().is_err());
+        assert!(builtin_value("meta.version", Path::new(".")).is_none());
+    }
+
+    #[test]
+    fn test_builtin_values() {
+        let values = BuiltinValues::default();
+        let uuid = values.get("uuid", Path::new(".")).unwrap().unwrap();
+        assert_eq!(values.get("uuid", Path::new("/")), Some(Ok(uuid.clone())));
+        assert_eq!(
+            values.clone().get("uuid", Path::new(".")),
+            Some(Ok(uuid.clone()))
+        );
+        assert!(values.get("git.sha", Path::new("/")).unwrap().is_err());
+        assert!(values.get("meta.version", Path::new(".")).is_none());
+        values.clear();
+        assert_ne!(values.get("uuid", Path::new(".")), Some(Ok(uuid)));
+    }
+}
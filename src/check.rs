@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use error::YakeError;
+use yaml::{find_sub_yakefiles, find_unknown_fields, parse_yml_from_file};
+
+/// Loads a Yakefile along with it's sub yakes and finds all of their problems, see
+/// `Yake::check`, including fields yake does not know. Fails only if the Yakefile
+/// itself can not be parsed, sub yakes which can not be parsed are reported as
+/// problems.
+pub fn check_yakefile(path: &Path) -> Result<Vec<String>, YakeError> {
+    let mut yake = parse_yml_from_file(path).map_err(|message| YakeError::Load {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    let mut problems = Vec::new();
+    if let Err(e) = yake.validate_meta() {
+        problems.push(format!("{}: {}", path.display(), e));
+    }
+    problems.extend(find_unknown_fields(path).unwrap_or_default());
+    for sub_path in find_sub_yakefiles(&yake)? {
+        match parse_yml_from_file(&sub_path) {
+            Ok(sub_yake) => {
+                if let Err(e) = sub_yake.validate_meta() {
+                    problems.push(format!("{}: {}", sub_path.display(), e));
+                }
+                problems.extend(find_unknown_fields(&sub_path).unwrap_or_default());
+                if let Err(e) = yake.add_sub_yake(sub_yake) {
+                    problems.push(format!("{}: {}", sub_path.display(), e));
+                }
+            }
+            Err(e) => problems.push(format!("Unable to load {}: {}", sub_path.display(), e)),
+        }
+    }
+    problems.extend(yake.check());
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_yakefile() {
+        let problems = check_yakefile(Path::new("tests/fixtures/check/Yakefile")).unwrap();
+
+        assert_eq!(
+            problems,
+            vec![
+                "tests/fixtures/check/Yakefile:41: target deploy: Unknown field exce",
+                "tests/fixtures/check/Yakefile:52: target docs.api: Unknown field dependss in meta",
+                "tests/fixtures/check/Yakefile:56: target docs.api: Unknown field ignore_error in exec",
+                "Unable to load tests/fixtures/check/broken/Yakefile: Unable to parse: targets: invalid type: sequence, expected a map at line 5 column 3\n 5 |   - build\n   |   ^",
+                "tests/fixtures/check/Yakefile:8: target build: Unknown dependency: compile",
+                "tests/fixtures/check/Yakefile:8: target build: Unable to load tests/fixtures/check/../shared/Yakefile: File not found: No such file or directory (os error 2)",
+                "tests/fixtures/check/Yakefile:43: target docs: A callable target must not have targets, only groups have sub targets",
+                "tests/fixtures/check/Yakefile:23: target lint: A parallel target must not be interactive",
+                "tests/fixtures/check/Yakefile:17: target test: A group must not have exec, before or after, only callable targets run commands",
+                "Dependency cycle detected: deploy -> lint -> deploy",
+                "Unknown default target: release",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_yakefile_valid() {
+        let problems = check_yakefile(Path::new("tests/fixtures/groups/Yakefile")).unwrap();
+        assert!(problems.is_empty());
+    }
+}
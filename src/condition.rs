@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::Path;
+
+/// Condition of a target's `when` field, e.g.
+/// `env.CI == 'true' && os != 'windows' && !exists('target/release')`.
+///
+/// Operands are quoted strings, `env.NAME` for env variables, `os` and `arch` for the
+/// platform yake runs on. Operands are compared with `==` and `!=`, a single operand is
+/// true if it is not empty. `exists('path')` checks whether a file or directory exists,
+/// relative to the Yakefile. Conditions are combined with `!`, `&&`, `||` and braces.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition(Expression);
+
+#[derive(Debug, PartialEq, Clone)]
+enum Expression {
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Equals(Operand, Operand),
+    NotEquals(Operand, Operand),
+    Exists(String),
+    NotEmpty(Operand),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Operand {
+    Literal(String),
+    Env(String),
+    Os,
+    Arch,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Name(String),
+    Literal(String),
+    Equals,
+    NotEquals,
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Name(ref name) => write!(f, "{}", name),
+            Token::Literal(ref value) => write!(f, "'{}'", value),
+            Token::Equals => write!(f, "=="),
+            Token::NotEquals => write!(f, "!="),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Not => write!(f, "!"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+impl Condition {
+    /// Parses a condition, failing for invalid syntax and unknown names.
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let tokens =
+            tokenize(text).map_err(|e| format!("Invalid condition \"{}\": {}", text, e))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expression = parser
+            .parse_or()
+            .and_then(|expression| match parser.tokens.get(parser.pos) {
+                Some(token) => Err(format!("unexpected {}", token)),
+                None => Ok(expression),
+            })
+            .map_err(|e| format!("Invalid condition \"{}\": {}", text, e))?;
+        Ok(Condition(expression))
+    }
+
+    /// Evaluates the condition. Env variables are looked up in `env` first, then in
+    /// the environment of yake, unset variables are empty.
+    pub fn evaluate(&self, env: &HashMap<String, String>, base_dir: &Path) -> bool {
+        self.0.evaluate(env, base_dir)
+    }
+}
+
+impl Expression {
+    fn evaluate(&self, env: &HashMap<String, String>, base_dir: &Path) -> bool {
+        match *self {
+            Expression::Not(ref e) => !e.evaluate(env, base_dir),
+            Expression::And(ref a, ref b) => a.evaluate(env, base_dir) && b.evaluate(env, base_dir),
+            Expression::Or(ref a, ref b) => a.evaluate(env, base_dir) || b.evaluate(env, base_dir),
+            Expression::Equals(ref a, ref b) => a.value(env) == b.value(env),
+            Expression::NotEquals(ref a, ref b) => a.value(env) != b.value(env),
+            Expression::Exists(ref path) => base_dir.join(path).exists(),
+            Expression::NotEmpty(ref a) => !a.value(env).is_empty(),
+        }
+    }
+}
+
+impl Operand {
+    fn value(&self, env: &HashMap<String, String>) -> String {
+        match *self {
+            Operand::Literal(ref value) => value.clone(),
+            Operand::Env(ref name) => env
+                .get(name)
+                .cloned()
+                .or_else(|| env::var(name).ok())
+                .unwrap_or_default(),
+            Operand::Os => env::consts::OS.to_string(),
+            Operand::Arch => env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+/// Recursive descent parser over the tokens of a condition, `||` binds weaker than
+/// `&&`, which binds weaker than `!`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn accept(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref next) if next == token => Ok(()),
+            Some(next) => Err(format!("expected {}, got {}", token, next)),
+            None => Err(format!("expected {}", token)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+        while self.accept(&Token::Or) {
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_not()?;
+        while self.accept(&Token::And) {
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_not()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, String> {
+        if self.accept(&Token::Not) {
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        if self.accept(&Token::Open) {
+            let expression = self.parse_or()?;
+            self.expect(&Token::Close)?;
+            return Ok(expression);
+        }
+        if self.tokens.get(self.pos) == Some(&Token::Name("exists".to_string())) {
+            self.pos += 1;
+            self.expect(&Token::Open)?;
+            let path = match self.next() {
+                Some(Token::Literal(path)) => path,
+                _ => return Err("exists expects a quoted path".to_string()),
+            };
+            self.expect(&Token::Close)?;
+            return Ok(Expression::Exists(path));
+        }
+
+        let left = self.parse_operand()?;
+        if self.accept(&Token::Equals) {
+            Ok(Expression::Equals(left, self.parse_operand()?))
+        } else if self.accept(&Token::NotEquals) {
+            Ok(Expression::NotEquals(left, self.parse_operand()?))
+        } else {
+            Ok(Expression::NotEmpty(left))
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Literal(value)) => Ok(Operand::Literal(value)),
+            Some(Token::Name(ref name)) if name == "os" => Ok(Operand::Os),
+            Some(Token::Name(ref name)) if name == "arch" => Ok(Operand::Arch),
+            Some(Token::Name(name)) => match name.strip_prefix("env.") {
+                Some(var) if !var.is_empty() => Ok(Operand::Env(var.to_string())),
+                _ => Err(format!("unknown name {}", name)),
+            },
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+/// Splits a condition into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' | '\t' | '\n' => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Equals,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::NotEquals,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '\'' | '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(v) => value.push(v),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+                Token::Literal(value)
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(n) =
+                    chars.next_if(|&n| n.is_ascii_alphanumeric() || "_.".contains(n))
+                {
+                    name.push(n);
+                }
+                Token::Name(name)
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(text: &str) -> bool {
+        let mut env = HashMap::new();
+        env.insert("CI".to_string(), "true".to_string());
+        env.insert("EMPTY".to_string(), String::new());
+        Condition::parse(text)
+            .unwrap()
+            .evaluate(&env, Path::new("tests/fixtures"))
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(evaluate("env.CI == 'true'"));
+        assert!(evaluate("env.CI != \"false\""));
+        assert!(!evaluate("env.CI == 'false'"));
+        assert!(evaluate("env.CI"));
+        assert!(!evaluate("env.EMPTY"));
+        assert!(!evaluate("env.YAKE_UNSET_VARIABLE"));
+        assert!(evaluate("env.CARGO_PKG_NAME == 'yake'"));
+        assert!(evaluate(&format!("os == '{}'", env::consts::OS)));
+        assert!(evaluate(&format!("arch == '{}'", env::consts::ARCH)));
+    }
+
+    #[test]
+    fn test_exists() {
+        assert!(evaluate("exists('groups/Yakefile')"));
+        assert!(evaluate("exists('groups')"));
+        assert!(!evaluate("exists('groups/Makefile')"));
+    }
+
+    #[test]
+    fn test_operators() {
+        assert!(evaluate("env.CI && !env.EMPTY"));
+        assert!(evaluate("env.EMPTY || env.CI == 'true'"));
+        assert!(!evaluate("!(env.EMPTY || env.CI)"));
+        assert!(evaluate("env.EMPTY && env.CI || env.CI"));
+        assert!(!evaluate("env.EMPTY && (env.CI || env.CI)"));
+        assert!(evaluate("!!env.CI"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            Condition::parse("platform == 'linux'").unwrap_err(),
+            "Invalid condition \"platform == 'linux'\": unknown name platform"
+        );
+        assert_eq!(
+            Condition::parse("env.CI == 'true").unwrap_err(),
+            "Invalid condition \"env.CI == 'true\": unterminated quote"
+        );
+        assert_eq!(
+            Condition::parse("env.CI = 'true'").unwrap_err(),
+            "Invalid condition \"env.CI = 'true'\": unexpected character '='"
+        );
+        assert_eq!(
+            Condition::parse("(env.CI").unwrap_err(),
+            "Invalid condition \"(env.CI\": expected )"
+        );
+        assert!(Condition::parse("env.CI env.CI").is_err());
+        assert!(Condition::parse("exists(env.CI)").is_err());
+        assert!(Condition::parse("").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Reads an env file with one `NAME=value` assignment per line, see `parse_env_file`.
+pub fn read_env_file(path: &Path) -> Result<HashMap<String, String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read env file {}: {}", path.display(), e))?;
+    parse_env_file(&content).map_err(|e| format!("Invalid env file {}: {}", path.display(), e))
+}
+
+/// Parses the content of an env file in the format of `.env` files. Empty lines and
+/// lines starting with `#` are ignored, assignments may be prefixed with `export`.
+/// Values in single quotes are taken literally, values in double quotes support `\n`,
+/// `\"` and `\\` escapes. Unquoted values end at a ` #` comment.
+pub fn parse_env_file(content: &str) -> Result<HashMap<String, String>, String> {
+    let mut env = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected NAME=value", i + 1))?;
+        let value = parse_value(value.trim()).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        env.insert(name.trim().to_string(), value);
+    }
+    Ok(env)
+}
+
+/// Parses the value of an assignment, removing quotes and comments.
+fn parse_value(value: &str) -> Result<String, String> {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        return match quoted.find('\'') {
+            Some(end) => Ok(quoted[..end].to_string()),
+            None => Err("unterminated single quote".to_string()),
+        };
+    }
+
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut result = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Ok(result),
+                '\\' => match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some(c @ '"') | Some(c @ '\\') => result.push(c),
+                    Some(c) => {
+                        result.push('\\');
+                        result.push(c);
+                    }
+                    None => break,
+                },
+                c => result.push(c),
+            }
+        }
+        return Err("unterminated double quote".to_string());
+    }
+
+    let value = match value.find(" #") {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_file() {
+        let content = r#"
+# database settings
+DB_HOST=localhost
+export DB_PORT = 5432
+DB_USER=yake # the default user
+DB_PASSWORD='se#cret $x'
+GREETING="hello \"world\"\nsecond line"
+EMPTY=
+URL=http://localhost/#anchor
+"#;
+
+        let env = parse_env_file(content).unwrap();
+        assert_eq!(env.len(), 7);
+        assert_eq!(env.get("DB_HOST").unwrap(), "localhost");
+        assert_eq!(env.get("DB_PORT").unwrap(), "5432");
+        assert_eq!(env.get("DB_USER").unwrap(), "yake");
+        assert_eq!(env.get("DB_PASSWORD").unwrap(), "se#cret $x");
+        assert_eq!(env.get("GREETING").unwrap(), "hello \"world\"\nsecond line");
+        assert_eq!(env.get("EMPTY").unwrap(), "");
+        assert_eq!(env.get("URL").unwrap(), "http://localhost/#anchor");
+    }
+
+    #[test]
+    fn test_parse_env_file_errors() {
+        assert_eq!(
+            parse_env_file("A=1\nB\n").unwrap_err(),
+            "line 2: expected NAME=value"
+        );
+        assert_eq!(
+            parse_env_file("A=\"open").unwrap_err(),
+            "line 1: unterminated double quote"
+        );
+        assert_eq!(
+            parse_env_file("A='open").unwrap_err(),
+            "line 1: unterminated single quote"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Exit code for executions in which several targets failed, and for a failed command
+/// whose exit code would be mistaken for one yake reserves, see `command_exit_code`.
+pub const FAILED_EXIT_CODE: i32 = 1;
+
+/// Exit code for errors which are not caused by a failing command, e.g. an invalid
+/// Yakefile or an unknown target.
+pub const USAGE_EXIT_CODE: i32 = 2;
+
+/// Exit code for commands killed after their timeout, the one of `timeout(1)`.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Exit code for yake being interrupted, the one shells report for processes ended by
+/// an interrupt.
+pub const INTERRUPT_EXIT_CODE: i32 = 130;
+
+/// Errors of yake, reported to the user along with an exit code.
+#[derive(Debug, PartialEq)]
+pub enum YakeError {
+    /// No Yakefile in the current directory or any parent directory
+    NoYakefile,
+    /// A Yakefile could not be read, parsed or contains invalid options
+    Load { path: PathBuf, message: String },
+    /// A target which is not defined, along with the available targets
+    UnknownTarget {
+        target: String,
+        available: Vec<String>,
+    },
+    /// Targets can not be executed as they are defined, e.g. because of unknown
+    /// dependencies, dependency cycles or missing parameters
+    Invalid(String),
+    /// Execution of a target failed
+    Failed {
+        message: String,
+        /// Exit code for yake, the one of the failed command if a command failed
+        exit_code: i32,
+    },
+}
+
+impl YakeError {
+    /// Exit code of yake for the error.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            YakeError::Failed { exit_code, .. } => exit_code,
+            _ => USAGE_EXIT_CODE,
+        }
+    }
+}
+
+impl fmt::Display for YakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            YakeError::NoYakefile => write!(
+                f,
+                "No Yakefile found in the current directory or any parent directory"
+            ),
+            YakeError::Load {
+                ref path,
+                ref message,
+            } => write!(f, "Unable to load {}: {}", path.display(), message),
+            YakeError::UnknownTarget {
+                ref target,
+                ref available,
+            } => write!(
+                f,
+                "Unknown target: '{}' Available targets are: {:?}",
+                target, available
+            ),
+            YakeError::Invalid(ref message) => write!(f, "{}", message),
+            YakeError::Failed { ref message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Failures while running a target, e.g. of a command which could not be started.
+impl From<String> for YakeError {
+    fn from(message: String) -> YakeError {
+        YakeError::Failed {
+            message,
+            exit_code: 1,
+        }
+    }
+}
+
+/// Exit code of yake for a failed command: it's own, unless yake reserves it for
+/// invalid Yakefiles, timeouts or interrupts, then `FAILED_EXIT_CODE`. So a command
+/// exiting with e.g. 124 is not mistaken for a command which timed out.
+pub fn command_exit_code(exit_code: i32) -> i32 {
+    match exit_code {
+        USAGE_EXIT_CODE | TIMEOUT_EXIT_CODE | INTERRUPT_EXIT_CODE => FAILED_EXIT_CODE,
+        exit_code => exit_code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        let failed = YakeError::Failed {
+            message: "Command \"false\" exited with code 3".to_string(),
+            exit_code: 3,
+        };
+        assert_eq!(failed.exit_code(), 3);
+        assert_eq!(YakeError::from("oops".to_string()).exit_code(), 1);
+        assert_eq!(YakeError::NoYakefile.exit_code(), USAGE_EXIT_CODE);
+        assert_eq!(
+            YakeError::Invalid("Dependency cycle detected: a -> a".to_string()).exit_code(),
+            USAGE_EXIT_CODE
+        );
+    }
+
+    #[test]
+    fn test_command_exit_code() {
+        assert_eq!(command_exit_code(3), 3);
+        assert_eq!(command_exit_code(USAGE_EXIT_CODE), FAILED_EXIT_CODE);
+        assert_eq!(command_exit_code(TIMEOUT_EXIT_CODE), FAILED_EXIT_CODE);
+        assert_eq!(command_exit_code(INTERRUPT_EXIT_CODE), FAILED_EXIT_CODE);
+    }
+
+    #[test]
+    fn test_display() {
+        let error = YakeError::Load {
+            path: PathBuf::from("ci/Yakefile"),
+            message: "File not found".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Unable to load ci/Yakefile: File not found"
+        );
+
+        let error = YakeError::UnknownTarget {
+            target: "lint".to_string(),
+            available: vec!["build".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "Unknown target: 'lint' Available targets are: [\"build\"]"
+        );
+    }
+}
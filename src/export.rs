@@ -0,0 +1,482 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json;
+
+use interpolation::shell_quote;
+use yake::{ExternalReference, Yake, YakeCommand, YakeTarget, YakeTargetType};
+
+/// Formats a Yakefile can be exported to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    /// A Makefile for GNU make
+    Make,
+    /// A justfile for just
+    Just,
+}
+
+/// A target prepared for the export, with placeholders and env files resolved.
+#[derive(Debug, Default)]
+struct ExportTarget {
+    name: String,
+    doc: String,
+    depends: Vec<String>,
+    env: BTreeMap<String, String>,
+    /// Directory the commands run in, relative to the Yakefile, if it differs
+    dir: Option<String>,
+    /// Commands of `before` and `exec`
+    commands: Vec<YakeCommand>,
+    after: Vec<YakeCommand>,
+}
+
+/// Exports the targets of a yake as a Makefile or justfile, for running them where
+/// yake is not installed. Groups become targets depending on their children, env
+/// variables shared by all targets are exported globally, the others for their
+/// target only.
+///
+/// Options without an equivalent, e.g. `when`, `timeout` or dependencies on targets
+/// of other Yakefiles, are left out and listed in a comment at the top.
+pub fn export_yake(yake: &Yake, format: ExportFormat) -> String {
+    let mut skipped = Vec::new();
+    let mut names = Vec::new();
+    collect_names(&yake.targets, None, &mut names);
+    names.sort();
+    let mut targets = Vec::new();
+    for name in &names {
+        match prepare_target(yake, name, &mut skipped) {
+            Ok(target) => targets.push(target),
+            Err(e) => skipped.push(format!("target {}: {}", name, e)),
+        }
+    }
+
+    // variables with the same value in all targets are exported once for all of them
+    let callables: Vec<&ExportTarget> = targets
+        .iter()
+        .filter(|target| !target.commands.is_empty() || !target.after.is_empty())
+        .collect();
+    let mut global = callables
+        .first()
+        .map(|target| target.env.clone())
+        .unwrap_or_default();
+    global.retain(|name, value| {
+        callables
+            .iter()
+            .all(|target| target.env.get(name) == Some(value))
+    });
+
+    let content = match format {
+        ExportFormat::Make => write_makefile(yake, &targets, &global, &mut skipped),
+        ExportFormat::Just => write_justfile(yake, &targets, &global),
+    };
+    let mut exported = String::from("# Exported from a Yakefile by yake export\n");
+    if !skipped.is_empty() {
+        exported.push_str("# Not exported, needs to be converted by hand:\n");
+        for skipped in &skipped {
+            exported.push_str(&format!("#   {}\n", skipped));
+        }
+    }
+    exported.push_str(&content);
+    exported
+}
+
+/// Collects the names of all targets, those of sub targets prefixed with their parent.
+fn collect_names(
+    targets: &HashMap<String, YakeTarget>,
+    prefix: Option<&str>,
+    names: &mut Vec<String>,
+) {
+    for (name, target) in targets {
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        if let Some(ref children) = target.targets {
+            collect_names(children, Some(&name), names);
+        }
+        names.push(name);
+    }
+}
+
+/// Resolves the dependencies, env, directory and commands of a target. Options which
+/// can not be exported are added to `skipped`.
+fn prepare_target(
+    yake: &Yake,
+    name: &str,
+    skipped: &mut Vec<String>,
+) -> Result<ExportTarget, String> {
+    let target = yake.get_target_by_name(name).unwrap();
+    let mut skip = |option: &str| skipped.push(format!("target {}: {}", name, option));
+
+    let mut depends = Vec::new();
+    for dependency in target.meta.depends.clone().unwrap_or_default() {
+        match ExternalReference::parse(&dependency) {
+            Some(_) => skip(&format!("dependency {} of another Yakefile", dependency)),
+            None => depends.push(dependency),
+        }
+    }
+    if target.meta.target_type == YakeTargetType::Group {
+        let mut children: Vec<String> = target
+            .targets
+            .iter()
+            .flat_map(|children| children.keys())
+            .map(|child| format!("{}.{}", name, child))
+            .collect();
+        children.sort();
+        depends.extend(children);
+        return Ok(ExportTarget {
+            name: name.to_string(),
+            doc: target.meta.doc,
+            depends,
+            ..ExportTarget::default()
+        });
+    }
+
+    if target.is_service() {
+        skip("service, it would not run in the background");
+        return Ok(ExportTarget {
+            name: name.to_string(),
+            doc: target.meta.doc,
+            depends,
+            ..ExportTarget::default()
+        });
+    }
+
+    let unsupported = [
+        ("when", target.when.is_some()),
+        ("container", yake.get_target_container(name).is_some()),
+        ("remote", yake.get_target_remote(name).is_some()),
+        ("parallel", target.is_parallel()),
+        ("script_mode", target.is_script_mode()),
+        ("shell_opts", yake.get_target_shell_opts(name).is_some()),
+        ("timeout", target.timeout.is_some()),
+        ("retries", target.retries.is_some()),
+        ("expected_exit_codes", target.expected_exit_codes.is_some()),
+        ("inputs", target.inputs.is_some()),
+        ("outputs", target.outputs.is_some()),
+        ("requires_env", target.requires_env.is_some()),
+    ];
+    for (option, _) in unsupported.iter().filter(|(_, given)| *given) {
+        skip(option);
+    }
+    let host = yake.get_target_container(name).is_none() && yake.get_target_remote(name).is_none();
+    if yake.get_target_shell(name) != ["bash", "-c"] && host {
+        skip("shell");
+    }
+
+    let mut commands = yake.expand_commands(name, &target.before)?;
+    commands.extend(yake.expand_commands(name, &target.exec)?);
+    let mut after = yake.expand_commands(name, &target.after)?;
+    for command in commands.iter().chain(&after) {
+        if let Some(name) = command.get_output_var() {
+            skip(&format!("set {}", name));
+        }
+        if let YakeCommand::Detailed(ref details) = *command {
+            if details.stdin.is_some()
+                || details.stdin_file.is_some()
+                || details.expected_exit_codes.is_some()
+                || details.timeout.is_some()
+                || details.retries.is_some()
+                || details.retry_delay.is_some()
+            {
+                skip(&format!("options of command {}", details.cmd));
+            }
+        }
+    }
+
+    // the output of `set` commands can not be passed on
+    commands.retain(|command| command.get_output_var().is_none());
+    after.retain(|command| command.get_output_var().is_none());
+
+    let dir = yake.get_target_dir(name)?;
+    let base_dir = yake.get_base_dir();
+    let dir = if dir == base_dir {
+        None
+    } else {
+        let relative = dir.strip_prefix(&base_dir).unwrap_or(&dir);
+        Some(relative.to_string_lossy().to_string())
+    };
+
+    Ok(ExportTarget {
+        name: name.to_string(),
+        doc: target.meta.doc,
+        depends,
+        env: yake.get_target_env_vars(name)?.into_iter().collect(),
+        dir,
+        commands,
+        after,
+    })
+}
+
+/// Quotes a value as a double quoted string, JSON strings are valid in just.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("Unable to quote value")
+}
+
+/// Escapes a value for make, `$` starts references to variables and `#` comments.
+fn escape_make(value: &str) -> String {
+    value.replace('$', "$$").replace('#', "\\#")
+}
+
+/// Writes the targets as a Makefile. Each command runs in a shell of it's own, so
+/// multi line commands are not exported, and `after` only runs if the commands
+/// before succeeded.
+fn write_makefile(
+    yake: &Yake,
+    targets: &[ExportTarget],
+    global: &BTreeMap<String, String>,
+    skipped: &mut Vec<String>,
+) -> String {
+    let mut makefile = String::from("SHELL := bash\n.SHELLFLAGS := -c\n");
+    for (name, value) in global {
+        makefile.push_str(&format!("export {} := {}\n", name, escape_make(value)));
+    }
+    if let Some(ref default_target) = yake.meta.default_target {
+        makefile.push_str(&format!(".DEFAULT_GOAL := {}\n", default_target));
+    }
+    let names: Vec<&str> = targets.iter().map(|target| target.name.as_str()).collect();
+    makefile.push_str(&format!(".PHONY: {}\n", names.join(" ")));
+
+    for target in targets {
+        makefile.push('\n');
+        if !target.doc.is_empty() {
+            makefile.push_str(&format!("# {}\n", target.doc));
+        }
+        makefile.push_str(&format!("{}:", target.name));
+        for dependency in &target.depends {
+            makefile.push_str(&format!(" {}", dependency));
+        }
+        makefile.push('\n');
+        for (name, value) in &target.env {
+            if global.get(name) != Some(value) {
+                makefile.push_str(&format!(
+                    "{}: export {} := {}\n",
+                    target.name,
+                    name,
+                    escape_make(value)
+                ));
+            }
+        }
+        if !target.after.is_empty() {
+            skipped.push(format!(
+                "target {}: after, it only runs if the commands before succeed",
+                target.name
+            ));
+        }
+        for command in target.commands.iter().chain(&target.after) {
+            let line = command.get_command().trim_end();
+            if line.contains('\n') {
+                skipped.push(format!(
+                    "target {}: multi line command {}",
+                    target.name,
+                    line.lines().next().unwrap_or_default()
+                ));
+                continue;
+            }
+            let ignore = if command.ignores_errors() { "-" } else { "" };
+            let cd = match target.dir {
+                Some(ref dir) => format!("cd {} && ", shell_quote(dir)),
+                None => String::new(),
+            };
+            makefile.push_str(&format!("\t{}{}{}\n", ignore, cd, escape_make(line)));
+        }
+    }
+    makefile
+}
+
+/// Writes the targets as a justfile. The commands of a target run as a single bash
+/// script, which runs the `after` commands on exit.
+fn write_justfile(
+    yake: &Yake,
+    targets: &[ExportTarget],
+    global: &BTreeMap<String, String>,
+) -> String {
+    let recipe = |name: &str| name.replace('.', "_");
+    let mut justfile = String::from("set shell := [\"bash\", \"-c\"]\n");
+    for (name, value) in global {
+        justfile.push_str(&format!("export {} := {}\n", name, quote(value)));
+    }
+    if let Some(ref default_target) = yake.meta.default_target {
+        justfile.push_str(&format!("\n_default: {}\n", recipe(default_target)));
+    }
+
+    for target in targets {
+        justfile.push('\n');
+        if !target.doc.is_empty() {
+            justfile.push_str(&format!("# {}\n", target.doc));
+        }
+        justfile.push_str(&recipe(&target.name));
+        justfile.push(':');
+        for dependency in &target.depends {
+            justfile.push_str(&format!(" {}", recipe(dependency)));
+        }
+        justfile.push('\n');
+        if target.commands.is_empty() && target.after.is_empty() {
+            continue;
+        }
+
+        let mut script = vec!["#!/usr/bin/env bash".to_string(), "set -e".to_string()];
+        for (name, value) in &target.env {
+            if global.get(name) != Some(value) {
+                script.push(format!("export {}={}", name, shell_quote(value)));
+            }
+        }
+        if let Some(ref dir) = target.dir {
+            script.push(format!("cd {}", shell_quote(dir)));
+        }
+        if !target.after.is_empty() {
+            script.push("after() {".to_string());
+            for command in &target.after {
+                let lines = command_lines(command.get_command(), true);
+                script.extend(lines.into_iter().map(|line| format!("    {}", line)));
+            }
+            script.push("}".to_string());
+            script.push("trap after EXIT".to_string());
+        }
+        for command in &target.commands {
+            script.extend(command_lines(
+                command.get_command(),
+                command.ignores_errors(),
+            ));
+        }
+        for line in script {
+            if line.is_empty() {
+                justfile.push('\n');
+            } else {
+                justfile.push_str(&format!("    {}\n", line.replace("{{", "{{{{")));
+            }
+        }
+    }
+    justfile
+}
+
+/// Lines of a command within a script, the script continues after failures of the
+/// command if `ignore` is set.
+fn command_lines(command: &str, ignore: bool) -> Vec<String> {
+    let lines: Vec<String> = command.trim_end().lines().map(str::to_string).collect();
+    if !ignore {
+        return lines;
+    }
+    let mut wrapped = vec!["{".to_string()];
+    wrapped.extend(lines.into_iter().map(|line| format!("    {}", line)));
+    wrapped.push("} || true".to_string());
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml;
+
+    fn yake() -> Yake {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+          default_target: test
+        env:
+          APP: demo
+        targets:
+          test:
+            meta:
+              doc: "All tests"
+              type: group
+              depends:
+                - build
+            targets:
+              unit:
+                meta:
+                  doc: "Unit tests"
+                  type: callable
+                exec:
+                  - cargo test $APP
+                  - cmd: rm -rf target/tmp
+                    ignore_errors: true
+          build:
+            meta:
+              doc: "Builds the app"
+              type: callable
+              depends:
+                - ../shared/Yakefile:setup
+            env:
+              MODE: release
+            exec:
+              - echo "{{ $APP }}"
+            after:
+              - echo done
+            timeout: 10s
+        "###;
+        serde_yaml::from_str(yml).expect("Unable to parse")
+    }
+
+    #[test]
+    fn test_export_makefile() {
+        assert_eq!(
+            export_yake(&yake(), ExportFormat::Make),
+            r#"# Exported from a Yakefile by yake export
+# Not exported, needs to be converted by hand:
+#   target build: dependency ../shared/Yakefile:setup of another Yakefile
+#   target build: timeout
+#   target build: after, it only runs if the commands before succeed
+SHELL := bash
+.SHELLFLAGS := -c
+export APP := demo
+.DEFAULT_GOAL := test
+.PHONY: build test test.unit
+
+# Builds the app
+build:
+build: export MODE := release
+	echo "{{ $$APP }}"
+	echo done
+
+# All tests
+test: build test.unit
+
+# Unit tests
+test.unit:
+	cargo test $$APP
+	-rm -rf target/tmp
+"#
+        );
+    }
+
+    #[test]
+    fn test_export_justfile() {
+        assert_eq!(
+            export_yake(&yake(), ExportFormat::Just),
+            r#"# Exported from a Yakefile by yake export
+# Not exported, needs to be converted by hand:
+#   target build: dependency ../shared/Yakefile:setup of another Yakefile
+#   target build: timeout
+set shell := ["bash", "-c"]
+export APP := "demo"
+
+_default: test
+
+# Builds the app
+build:
+    #!/usr/bin/env bash
+    set -e
+    export MODE=release
+    after() {
+        {
+            echo done
+        } || true
+    }
+    trap after EXIT
+    echo "{{{{ $APP }}"
+
+# All tests
+test: build test_unit
+
+# Unit tests
+test_unit:
+    #!/usr/bin/env bash
+    set -e
+    cargo test $APP
+    {
+        rm -rf target/tmp
+    } || true
+"#
+        );
+    }
+}
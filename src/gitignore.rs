@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+/// A pattern of a `.gitignore` file.
+struct IgnoreRule {
+    /// Canonical path of the directory of the `.gitignore` file
+    dir: PathBuf,
+    pattern: Pattern,
+    /// Whether the pattern starts with `!` and includes paths ignored before
+    negated: bool,
+    /// Whether the pattern ends with `/` and only matches directories
+    dir_only: bool,
+    /// Whether the pattern contains a `/` and is matched against the path relative to
+    /// the directory instead of the name
+    anchored: bool,
+}
+
+/// Decides which paths of a directory tree git ignores, by the rules of the
+/// `.gitignore` files of the tree and of it's parent directories up to the root of
+/// the repository. Later rules take precedence over earlier ones, the rules of
+/// subdirectories over the ones of their parents.
+pub struct GitIgnore {
+    /// Root of the tree, as given
+    root: PathBuf,
+    /// Canonical path of the root
+    canonical_root: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl GitIgnore {
+    /// Loads the rules applying to a directory. Rules of subdirectories are added
+    /// with `add_dir` while walking the tree.
+    pub fn new(root: &Path) -> GitIgnore {
+        let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let mut ignore = GitIgnore {
+            root: root.to_path_buf(),
+            canonical_root: canonical_root.clone(),
+            rules: Vec::new(),
+        };
+
+        // outside of a repository only the .gitignore of the directory itself counts
+        let mut dirs: Vec<&Path> = Vec::new();
+        for dir in canonical_root.ancestors() {
+            dirs.push(dir);
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        if !dirs.last().is_some_and(|dir| dir.join(".git").exists()) {
+            dirs.truncate(1);
+        }
+        for dir in dirs.into_iter().rev() {
+            ignore.add_rules(dir);
+        }
+        ignore
+    }
+
+    /// Adds the rules of the `.gitignore` file of a directory within the tree.
+    pub fn add_dir(&mut self, dir: &Path) {
+        let dir = self.to_canonical(dir);
+        if dir != self.canonical_root {
+            self.add_rules(&dir);
+        }
+    }
+
+    /// Checks whether git ignores a path within the tree. The `.git` directory is
+    /// always ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        let path = self.to_canonical(path);
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+
+        let mut ignored = false;
+        for rule in &self.rules {
+            let relative = match path.strip_prefix(&rule.dir) {
+                Ok(relative) if is_dir || !rule.dir_only => relative,
+                _ => continue,
+            };
+            let matches = if rule.anchored {
+                let relative: Vec<String> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                rule.pattern.matches_with(&relative.join("/"), options)
+            } else {
+                relative
+                    .file_name()
+                    .is_some_and(|name| rule.pattern.matches_with(&name.to_string_lossy(), options))
+            };
+            if matches {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+
+    /// Canonical form of a path within the tree, without resolving it on disk.
+    fn to_canonical(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => self.canonical_root.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Reads the `.gitignore` of a directory given by it's canonical path, if it has
+    /// one. Invalid patterns are skipped.
+    fn add_rules(&mut self, dir: &Path) {
+        let contents = match fs::read_to_string(dir.join(".gitignore")) {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            if let Ok(pattern) = Pattern::new(line.trim_start_matches('/')) {
+                self.rules.push(IgnoreRule {
+                    dir: dir.to_path_buf(),
+                    pattern,
+                    negated,
+                    dir_only,
+                    anchored,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_is_ignored() {
+        let dir = env::temp_dir().join(format!("yake-gitignore-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("web/build")).unwrap();
+        fs::write(
+            dir.join(".gitignore"),
+            "# build output\ntarget/\nnode_modules\n/vendor/*\n!/vendor/own\n*.log\n",
+        )
+        .unwrap();
+        fs::write(dir.join("web/.gitignore"), "build/\n").unwrap();
+
+        let mut ignore = GitIgnore::new(&dir);
+        ignore.add_dir(&dir.join("web"));
+        assert!(ignore.is_ignored(&dir.join("target"), true));
+        assert!(!ignore.is_ignored(&dir.join("target"), false));
+        assert!(ignore.is_ignored(&dir.join("web/node_modules"), true));
+        assert!(ignore.is_ignored(&dir.join("vendor/lib"), true));
+        assert!(!ignore.is_ignored(&dir.join("vendor/own"), true));
+        assert!(!ignore.is_ignored(&dir.join("web/vendor/lib"), true));
+        assert!(ignore.is_ignored(&dir.join("web/build"), true));
+        assert!(!ignore.is_ignored(&dir.join("build"), true));
+        assert!(ignore.is_ignored(&dir.join("web/yake.log"), false));
+        assert!(ignore.is_ignored(&dir.join(".git"), true));
+        assert!(!ignore.is_ignored(&dir.join("web"), true));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use yake::{ExternalReference, Yake, YakeTarget, YakeTargetType};
+
+/// Renders the targets and their dependencies as a Graphviz DOT graph. Edges point
+/// from a target to it's dependencies, groups are drawn as clusters around their
+/// children, services with rounded corners and targets of other Yakefiles as dashed
+/// nodes.
+pub fn graph_to_dot(yake: &Yake) -> String {
+    let mut dot = String::from("digraph yake {\n    node [shape=box];\n");
+    let mut edges = BTreeSet::new();
+    let mut external = BTreeSet::new();
+    write_targets(&mut dot, &mut edges, &mut external, &yake.targets, None, 1);
+
+    for reference in external {
+        dot.push_str(&format!("    {} [style=dashed];\n", quote(&reference)));
+    }
+    for (from, to) in edges {
+        dot.push_str(&format!("    {} -> {};\n", quote(&from), quote(&to)));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Writes the nodes of targets, recursing into the children of groups, and collects
+/// the edges to their dependencies.
+fn write_targets(
+    dot: &mut String,
+    edges: &mut BTreeSet<(String, String)>,
+    external: &mut BTreeSet<String>,
+    targets: &HashMap<String, YakeTarget>,
+    prefix: Option<&str>,
+    depth: usize,
+) {
+    let indent = "    ".repeat(depth);
+    let targets: BTreeMap<&String, &YakeTarget> = targets.iter().collect();
+    for (name, target) in targets {
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        for dependency in target.meta.depends.clone().unwrap_or_default() {
+            if ExternalReference::parse(&dependency).is_some() {
+                external.insert(dependency.clone());
+            }
+            edges.insert((name.clone(), dependency));
+        }
+
+        let node = format!(
+            "{} [tooltip={}{}];\n",
+            quote(&name),
+            quote(&target.meta.doc),
+            match target.meta.target_type {
+                YakeTargetType::Group => ", shape=folder",
+                YakeTargetType::Callable => "",
+                YakeTargetType::Service => ", style=rounded",
+            }
+        );
+        match (&target.meta.target_type, &target.targets) {
+            (YakeTargetType::Group, Some(children)) => {
+                dot.push_str(&format!(
+                    "{}subgraph {} {{\n",
+                    indent,
+                    quote(&format!("cluster_{}", name))
+                ));
+                dot.push_str(&format!("{}    label={};\n", indent, quote(&name)));
+                dot.push_str(&format!("{}    {}", indent, node));
+                write_targets(dot, edges, external, children, Some(&name), depth + 1);
+                dot.push_str(&format!("{}}}\n", indent));
+            }
+            _ => dot.push_str(&format!("{}{}", indent, node)),
+        }
+    }
+}
+
+/// Quotes an ID for DOT.
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml;
+
+    #[test]
+    fn test_to_dot() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          test:
+            meta:
+              doc: "All tests"
+              type: group
+              depends:
+                - build
+            targets:
+              unit:
+                meta:
+                  doc: "Unit \"fast\" tests"
+                  type: callable
+          build:
+            meta:
+              doc: "Builds the app"
+              type: callable
+              depends:
+                - ../shared/Yakefile:setup
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+
+        assert_eq!(
+            graph_to_dot(&yake),
+            r#"digraph yake {
+    node [shape=box];
+    "build" [tooltip="Builds the app"];
+    subgraph "cluster_test" {
+        label="test";
+        "test" [tooltip="All tests", shape=folder];
+        "test.unit" [tooltip="Unit \"fast\" tests"];
+    }
+    "../shared/Yakefile:setup" [style=dashed];
+    "build" -> "../shared/Yakefile:setup";
+    "test" -> "build";
+}
+"#
+        );
+    }
+}
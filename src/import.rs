@@ -0,0 +1,548 @@
+use std::collections::HashSet;
+
+use serde_json;
+
+use version::YAKE_VERSION;
+
+/// Directives of make which are not imported, along with everything up to a line
+/// with the second value for blocks.
+const UNSUPPORTED_DIRECTIVES: [(&str, Option<&str>); 12] = [
+    ("include", None),
+    ("-include", None),
+    ("sinclude", None),
+    ("ifeq", Some("endif")),
+    ("ifneq", Some("endif")),
+    ("ifdef", Some("endif")),
+    ("ifndef", Some("endif")),
+    ("define", Some("endef")),
+    ("vpath", None),
+    ("unexport", None),
+    ("undefine", None),
+    ("load", None),
+];
+
+/// Variables, rules and phony targets of a Makefile, along with the parts which could
+/// not be imported.
+#[derive(Debug, Default)]
+struct Makefile {
+    variables: Vec<(String, String)>,
+    rules: Vec<Rule>,
+    phony: HashSet<String>,
+    skipped: Vec<String>,
+}
+
+/// A rule of a Makefile, rules for the same target are merged.
+#[derive(Debug, Default)]
+struct Rule {
+    target: String,
+    doc: String,
+    prerequisites: Vec<String>,
+    recipe: Vec<String>,
+}
+
+/// Converts a simple Makefile into a Yakefile doing the same. Variables become the env
+/// of the Yakefile, rules become callable targets depending on the prerequisites which
+/// are targets as well. Rules of files get their file as `outputs` and the other
+/// prerequisites as `inputs`, so that they only run if the file is outdated. The
+/// first rule becomes the default target and comments right above a rule it's doc.
+///
+/// Conditionals, includes, pattern rules and make functions other than `$(shell)` are
+/// not imported, they are listed in a comment at the top of the Yakefile.
+pub fn import_makefile(content: &str) -> String {
+    write_yakefile(&parse_makefile(content))
+}
+
+/// Parses the variables and rules of a Makefile.
+fn parse_makefile(content: &str) -> Makefile {
+    let mut makefile = Makefile::default();
+    let mut comments: Vec<String> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut block_end: Option<&str> = None;
+
+    for (number, line) in join_continuations(content) {
+        if let Some(end) = block_end {
+            if line.trim() == end {
+                block_end = None;
+            }
+            continue;
+        }
+        if let Some(command) = line.strip_prefix('\t') {
+            for &i in &current {
+                makefile.rules[i].recipe.push(command.to_string());
+            }
+            continue;
+        }
+
+        let (code, comment) = match line.find('#') {
+            Some(i) => (
+                line[..i].trim(),
+                Some(line[i..].trim_start_matches('#').trim()),
+            ),
+            None => (line.trim(), None),
+        };
+        if code.is_empty() {
+            match comment {
+                Some(comment) => comments.push(comment.to_string()),
+                None => comments.clear(),
+            }
+            continue;
+        }
+        let doc = comments.join(" ");
+        comments.clear();
+        current.clear();
+
+        let directive = code.split_whitespace().next().unwrap_or_default();
+        if let Some(&(_, end)) = UNSUPPORTED_DIRECTIVES
+            .iter()
+            .find(|&&(name, _)| name == directive)
+        {
+            makefile.skipped.push(format!("line {}: {}", number, code));
+            block_end = end;
+        } else if let Some((name, op, value)) = parse_assignment(code) {
+            let existing = makefile.variables.iter_mut().find(|(n, _)| *n == name);
+            match (existing, op) {
+                (Some(_), "?=") => {}
+                (Some((_, existing)), "+=") => {
+                    existing.push(' ');
+                    existing.push_str(&value);
+                }
+                (Some((_, existing)), _) => *existing = value,
+                (None, _) => makefile.variables.push((name, value)),
+            }
+        } else if let Some((targets, prerequisites, recipe)) = parse_rule(code) {
+            if targets.iter().any(|target| target.contains('%')) {
+                makefile
+                    .skipped
+                    .push(format!("line {}: pattern rule {}", number, code));
+            } else if prerequisites.iter().any(|p| p.contains('=')) {
+                makefile.skipped.push(format!(
+                    "line {}: target specific variable {}",
+                    number, code
+                ));
+            } else if targets == [".PHONY"] {
+                makefile.phony.extend(prerequisites);
+            } else if targets.iter().all(|target| !target.starts_with('.')) {
+                for target in targets {
+                    let i = match makefile.rules.iter().position(|r| r.target == target) {
+                        Some(i) => i,
+                        None => {
+                            makefile.rules.push(Rule {
+                                target,
+                                ..Rule::default()
+                            });
+                            makefile.rules.len() - 1
+                        }
+                    };
+                    let rule = &mut makefile.rules[i];
+                    if rule.doc.is_empty() {
+                        rule.doc = doc.clone();
+                    }
+                    rule.prerequisites.extend(prerequisites.iter().cloned());
+                    rule.recipe.extend(recipe.clone());
+                    current.push(i);
+                }
+            }
+        } else {
+            makefile.skipped.push(format!("line {}: {}", number, code));
+        }
+    }
+    makefile
+}
+
+/// Joins lines ending with a backslash with the next line, along with the number of
+/// their first line.
+fn join_continuations(content: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut continued = false;
+    for (i, line) in content.lines().enumerate() {
+        let (line, continues) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        match lines.last_mut() {
+            Some((_, last)) if continued => {
+                last.truncate(last.trim_end().len());
+                last.push(' ');
+                last.push_str(line.trim_start());
+            }
+            _ => lines.push((i + 1, line.to_string())),
+        }
+        continued = continues;
+    }
+    lines
+}
+
+/// Parses a variable assignment like `CC := gcc`, returns the name, operator and value.
+fn parse_assignment(code: &str) -> Option<(String, &str, String)> {
+    let code = code
+        .strip_prefix("export ")
+        .or_else(|| code.strip_prefix("override "))
+        .unwrap_or(code);
+    let (left, value) = code.split_once('=')?;
+    let name = left.trim_end_matches([':', '?', '+', '!']);
+    let op = &code[name.len()..=left.len()];
+    let name = name.trim();
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ':') {
+        return None;
+    }
+    Some((name.to_string(), op.trim(), value.trim().to_string()))
+}
+
+/// Parses a rule like `app: main.o util.o ; cc -o app main.o util.o`, returns the
+/// targets, prerequisites and the recipe given on the same line.
+fn parse_rule(code: &str) -> Option<(Vec<String>, Vec<String>, Vec<String>)> {
+    let (targets, rest) = code.split_once(':')?;
+    let rest = rest.strip_prefix(':').unwrap_or(rest);
+    let (prerequisites, recipe) = match rest.split_once(';') {
+        Some((prerequisites, recipe)) => (prerequisites, vec![recipe.trim().to_string()]),
+        None => (rest, Vec::new()),
+    };
+    let words = |text: &str| -> Vec<String> {
+        text.split_whitespace()
+            .filter(|word| *word != "|")
+            .map(str::to_string)
+            .collect()
+    };
+    let targets = words(targets);
+    if targets.is_empty() {
+        return None;
+    }
+    Some((targets, words(prerequisites), recipe))
+}
+
+/// Converts references to variables of make to ones of the shell, e.g. `$(CC)` to
+/// `${CC}`. The automatic variables `$@`, `$<`, `$^` and `$+` are replaced by the
+/// target and prerequisites of `rule`, `$(shell command)` by `$(command)`.
+fn convert_references(text: &str, rule: Option<&Rule>) -> Result<String, String> {
+    let mut converted = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            converted.push(c);
+            continue;
+        }
+        let automatic = |name: char| -> Result<String, String> {
+            let rule = rule.ok_or_else(|| format!("${} outside of a recipe", name))?;
+            let mut prerequisites = rule.prerequisites.clone();
+            match name {
+                '@' => Ok(rule.target.clone()),
+                '<' => Ok(prerequisites.first().cloned().unwrap_or_default()),
+                '+' => Ok(prerequisites.join(" ")),
+                '^' => {
+                    let mut seen = HashSet::new();
+                    prerequisites.retain(|p| seen.insert(p.clone()));
+                    Ok(prerequisites.join(" "))
+                }
+                name => Err(format!("automatic variable ${}", name)),
+            }
+        };
+        match chars.next() {
+            Some('$') => converted.push('$'),
+            Some(open) if open == '(' || open == '{' => {
+                let close = if open == '(' { ')' } else { '}' };
+                let mut inner = String::new();
+                let mut depth = 0;
+                loop {
+                    match chars.next() {
+                        Some(c) if c == close && depth == 0 => break,
+                        Some(c) => {
+                            if c == open {
+                                depth += 1;
+                            } else if c == close {
+                                depth -= 1;
+                            }
+                            inner.push(c);
+                        }
+                        None => return Err(format!("unterminated ${}{}", open, inner)),
+                    }
+                }
+                match inner.split_once(char::is_whitespace) {
+                    Some(("shell", command)) => {
+                        converted.push_str(&format!("$({})", convert_references(command, rule)?))
+                    }
+                    Some(_) => return Err(format!("function ${}{}{}", open, inner, close)),
+                    None if inner.len() == 1 => {
+                        converted.push_str(&automatic(inner.chars().next().unwrap())?)
+                    }
+                    None => converted.push_str(&format!("${{{}}}", inner)),
+                }
+            }
+            Some(name) if name.is_ascii_alphanumeric() || name == '_' => {
+                converted.push_str(&format!("${{{}}}", name))
+            }
+            Some(name) => converted.push_str(&automatic(name)?),
+            None => converted.push('$'),
+        }
+    }
+    Ok(converted)
+}
+
+/// Name of the target of a rule, `.` would nest targets and `/` is not allowed.
+fn target_name(target: &str) -> String {
+    target.replace(['.', '/'], "_")
+}
+
+/// Quotes a value for yaml, JSON strings are valid yaml.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("Unable to quote value")
+}
+
+/// Writes the Yakefile for a parsed Makefile.
+fn write_yakefile(makefile: &Makefile) -> String {
+    let mut skipped = makefile.skipped.clone();
+    let mut variables = Vec::new();
+    for (name, value) in &makefile.variables {
+        match convert_references(value, None) {
+            Ok(value) => variables.push((name, value)),
+            Err(e) => skipped.push(format!("variable {}: {}", name, e)),
+        }
+    }
+    let targets: HashSet<&String> = makefile.rules.iter().map(|rule| &rule.target).collect();
+
+    let mut yakefile = String::from("# Imported from a Makefile by yake import\n");
+    let mut body = String::new();
+    body.push_str("meta:\n  doc: \"Imported from a Makefile\"\n");
+    body.push_str(&format!("  version: {}\n", YAKE_VERSION));
+    if let Some(rule) = makefile.rules.first() {
+        body.push_str(&format!(
+            "  default_target: {}\n",
+            target_name(&rule.target)
+        ));
+    }
+    if !variables.is_empty() {
+        body.push_str("env:\n");
+        for (name, value) in variables {
+            body.push_str(&format!("  {}: {}\n", name, quote(&value)));
+        }
+    }
+    body.push_str("targets:\n");
+    for rule in &makefile.rules {
+        body.push_str(&format!("  {}:\n    meta:\n", target_name(&rule.target)));
+        body.push_str(&format!(
+            "      doc: {}\n      type: callable\n",
+            quote(&rule.doc)
+        ));
+        let (depends, files): (Vec<&String>, Vec<&String>) = rule
+            .prerequisites
+            .iter()
+            .partition(|prerequisite| targets.contains(prerequisite));
+        write_list(
+            &mut body,
+            "      depends:",
+            &depends.iter().map(|d| target_name(d)).collect::<Vec<_>>(),
+        );
+
+        let is_file = !makefile.phony.contains(&rule.target) && rule.target.contains(['.', '/']);
+        if is_file {
+            write_list(
+                &mut body,
+                "    inputs:",
+                &files.iter().map(|f| quote(f)).collect::<Vec<_>>(),
+            );
+            write_list(&mut body, "    outputs:", &[quote(&rule.target)]);
+        }
+
+        let mut commands = Vec::new();
+        for line in &rule.recipe {
+            let command = line.trim_start_matches(['@', '-', '+', ' ', '\t']);
+            let prefix = &line[..line.len() - command.len()];
+            let command = match convert_references(command, Some(rule)) {
+                Ok(command) => command,
+                Err(e) => {
+                    skipped.push(format!("target {}: {}", rule.target, e));
+                    command.to_string()
+                }
+            };
+            if prefix.contains('-') {
+                commands.push(format!(
+                    "cmd: {}\n        ignore_errors: true",
+                    quote(&command)
+                ));
+            } else {
+                commands.push(quote(&command));
+            }
+        }
+        write_list(&mut body, "    exec:", &commands);
+    }
+
+    if !skipped.is_empty() {
+        yakefile.push_str("# Not imported, needs to be converted by hand:\n");
+        for skipped in &skipped {
+            yakefile.push_str(&format!("#   {}\n", skipped));
+        }
+    }
+    yakefile.push_str(&body);
+    yakefile
+}
+
+/// Writes a yaml list under a key, nothing if it is empty.
+fn write_list(yakefile: &mut String, key: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    let indent = key.len() - key.trim_start().len();
+    yakefile.push_str(key);
+    yakefile.push('\n');
+    for item in items {
+        yakefile.push_str(&format!("{}  - {}\n", " ".repeat(indent), item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml;
+    use yake::Yake;
+
+    #[test]
+    fn test_import_makefile() {
+        let makefile = "CC := gcc
+CFLAGS = -O2
+CFLAGS += -Wall
+
+.PHONY: all clean
+
+# Builds everything
+all: app
+
+app: main.o util.o
+\t$(CC) $(CFLAGS) -o $@ $^
+
+main.o: main.c util.h
+\t@$(CC) -c $< \\
+\t  -o $@
+
+util.o: util.c ; $(CC) -c util.c
+
+## Removes build results
+clean:
+\t-rm -f app *.o
+\techo \"cleaned $$HOME\"
+";
+        assert_eq!(
+            import_makefile(makefile),
+            r#"# Imported from a Makefile by yake import
+meta:
+  doc: "Imported from a Makefile"
+  version: 0.1.0
+  default_target: all
+env:
+  CC: "gcc"
+  CFLAGS: "-O2 -Wall"
+targets:
+  all:
+    meta:
+      doc: "Builds everything"
+      type: callable
+      depends:
+        - app
+  app:
+    meta:
+      doc: ""
+      type: callable
+      depends:
+        - main_o
+        - util_o
+    exec:
+      - "${CC} ${CFLAGS} -o app main.o util.o"
+  main_o:
+    meta:
+      doc: ""
+      type: callable
+    inputs:
+      - "main.c"
+      - "util.h"
+    outputs:
+      - "main.o"
+    exec:
+      - "${CC} -c main.c -o main.o"
+  util_o:
+    meta:
+      doc: ""
+      type: callable
+    inputs:
+      - "util.c"
+    outputs:
+      - "util.o"
+    exec:
+      - "${CC} -c util.c"
+  clean:
+    meta:
+      doc: "Removes build results"
+      type: callable
+    exec:
+      - cmd: "rm -f app *.o"
+        ignore_errors: true
+      - "echo \"cleaned $HOME\""
+"#
+        );
+    }
+
+    #[test]
+    fn test_import_makefile_is_valid() {
+        let makefile =
+            "VERSION ?= 1.0\n\nbuild: src/main.rs\n\tcargo build\n\ntest: build\n\tcargo test\n";
+        let yake: Yake = serde_yaml::from_str(&import_makefile(makefile)).unwrap();
+
+        assert!(yake.validate().is_ok());
+        assert!(yake.check().is_empty());
+        assert_eq!(yake.meta.default_target, Some("build".to_string()));
+    }
+
+    #[test]
+    fn test_unsupported_parts_are_listed() {
+        let makefile = "include config.mk
+ifeq ($(OS),Windows_NT)
+EXE = .exe
+endif
+SOURCES = $(wildcard src/*.c)
+
+%.o: %.c
+\t$(CC) -c $< -o $@
+
+app:
+\t$(CC) -o $@ $(patsubst %.c,%.o,$(SOURCES))
+";
+        let yakefile = import_makefile(makefile);
+
+        assert!(yakefile.starts_with(
+            "# Imported from a Makefile by yake import
+# Not imported, needs to be converted by hand:
+#   line 1: include config.mk
+#   line 2: ifeq ($(OS),Windows_NT)
+#   line 7: pattern rule %.o: %.c
+#   variable SOURCES: function $(wildcard src/*.c)
+#   target app: function $(patsubst %.c,%.o,$(SOURCES))
+meta:
+"
+        ));
+        assert!(!yakefile.contains("EXE"));
+    }
+
+    #[test]
+    fn test_convert_references() {
+        let rule = Rule {
+            target: "app".to_string(),
+            prerequisites: vec!["a.o".to_string(), "b.o".to_string(), "a.o".to_string()],
+            ..Rule::default()
+        };
+        let convert = |text: &str| convert_references(text, Some(&rule));
+
+        assert_eq!(
+            convert("$(CC) ${LD} $X $$PATH").unwrap(),
+            "${CC} ${LD} ${X} $PATH"
+        );
+        assert_eq!(
+            convert("$@: $< $^ / $+").unwrap(),
+            "app: a.o a.o b.o / a.o b.o a.o"
+        );
+        assert_eq!(convert("$(@)").unwrap(), "app");
+        assert_eq!(
+            convert("v=$(shell git describe)").unwrap(),
+            "v=$(git describe)"
+        );
+        assert_eq!(convert("$*").unwrap_err(), "automatic variable $*");
+        assert!(convert_references("$@", None).is_err());
+        assert!(convert("$(CC").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use glob::{glob, Pattern};
+use serde_json;
+use sha2::{Digest, Sha256};
+
+/// File keeping the fingerprints of targets between runs, relative to the Yakefile.
+pub const FINGERPRINTS_FILE: &str = ".yake/fingerprints.json";
+
+/// Serializes updates of fingerprint files by concurrently running targets.
+static FINGERPRINTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Checks that glob patterns of `inputs` or `outputs` are valid.
+pub fn validate_patterns(patterns: &[String]) -> Result<(), String> {
+    for pattern in patterns {
+        Pattern::new(pattern).map_err(|e| format!("Invalid pattern \"{}\": {}", pattern, e))?;
+    }
+    Ok(())
+}
+
+/// Files matching any of the glob patterns, relative to `base_dir`. Directories are
+/// not included, the files are sorted and unique.
+pub fn expand_patterns(base_dir: &Path, patterns: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let full_pattern = if Path::new(pattern).is_absolute() {
+            pattern.clone()
+        } else {
+            format!(
+                "{}/{}",
+                Pattern::escape(&base_dir.to_string_lossy()),
+                pattern
+            )
+        };
+        let paths =
+            glob(&full_pattern).map_err(|e| format!("Invalid pattern \"{}\": {}", pattern, e))?;
+        for path in paths {
+            let path = path.map_err(|e| format!("Unable to read {}", e))?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Checks whether the outputs of a target are newer than all of it's inputs, the way
+/// make does. Targets are never up to date if an output pattern matches no file.
+pub fn is_up_to_date(
+    base_dir: &Path,
+    inputs: &[String],
+    outputs: &[String],
+) -> Result<bool, String> {
+    let mut oldest_output = None;
+    for pattern in outputs {
+        let files = expand_patterns(base_dir, std::slice::from_ref(pattern))?;
+        if files.is_empty() {
+            return Ok(false);
+        }
+        for file in files {
+            let modified = modified(&file)?;
+            if oldest_output.is_none_or(|oldest| modified < oldest) {
+                oldest_output = Some(modified);
+            }
+        }
+    }
+    let oldest_output = match oldest_output {
+        Some(oldest_output) => oldest_output,
+        None => return Ok(false),
+    };
+
+    for file in expand_patterns(base_dir, inputs)? {
+        if modified(&file)? >= oldest_output {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Checks whether every output pattern matches at least one file.
+pub fn outputs_exist(base_dir: &Path, outputs: &[String]) -> Result<bool, String> {
+    for pattern in outputs {
+        if expand_patterns(base_dir, std::slice::from_ref(pattern))?.is_empty() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Hex encoded sha256 hash over the commands, the env and the paths and contents of
+/// the input files of a target.
+pub fn fingerprint(
+    commands: &[String],
+    env: &HashMap<String, String>,
+    files: &[PathBuf],
+) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    // each value is followed by a null byte, so that values can not run into each other
+    let mut update = |value: &[u8]| {
+        hasher.update(value);
+        hasher.update([0]);
+    };
+
+    for command in commands {
+        update(command.as_bytes());
+    }
+    let env: BTreeMap<&String, &String> = env.iter().collect();
+    for (name, value) in env {
+        update(name.as_bytes());
+        update(value.as_bytes());
+    }
+    for file in files {
+        let content =
+            fs::read(file).map_err(|e| format!("Unable to read {}: {}", file.display(), e))?;
+        update(file.to_string_lossy().as_bytes());
+        update(&content);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Fingerprint of the last successful run of a target, stored under `key` in the
+/// fingerprints file within `base_dir`.
+pub fn read_fingerprint(base_dir: &Path, key: &str) -> Result<Option<String>, String> {
+    let _lock = FINGERPRINTS_LOCK.lock().unwrap();
+    Ok(read_fingerprints(base_dir)?.remove(key))
+}
+
+/// Stores the fingerprint of a successful run of a target.
+pub fn write_fingerprint(base_dir: &Path, key: &str, fingerprint: &str) -> Result<(), String> {
+    let _lock = FINGERPRINTS_LOCK.lock().unwrap();
+    let mut fingerprints = read_fingerprints(base_dir)?;
+    fingerprints.insert(key.to_string(), fingerprint.to_string());
+
+    let path = base_dir.join(FINGERPRINTS_FILE);
+    let error = |e: String| format!("Unable to write {}: {}", path.display(), e);
+    fs::create_dir_all(path.parent().unwrap()).map_err(|e| error(e.to_string()))?;
+    let content = serde_json::to_string_pretty(&fingerprints).map_err(|e| error(e.to_string()))?;
+    fs::write(&path, content).map_err(|e| error(e.to_string()))
+}
+
+/// All fingerprints stored within `base_dir`, none if the file does not exist yet.
+fn read_fingerprints(base_dir: &Path) -> Result<BTreeMap<String, String>, String> {
+    let path = base_dir.join(FINGERPRINTS_FILE);
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+/// Modification time of a file.
+fn modified(path: &Path) -> Result<SystemTime, String> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::time::Duration;
+
+    /// Creates an empty directory for a test within the temp directory.
+    fn get_test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("yake-incremental-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        dir
+    }
+
+    /// Creates a file with the given modification time, in seconds after the epoch.
+    fn touch(path: &Path, seconds: u64) {
+        let file = File::create(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_expand_patterns() {
+        let dir = get_test_dir("expand");
+        touch(&dir.join("src/main.rs"), 100);
+        touch(&dir.join("src/lib.rs"), 100);
+        touch(&dir.join("README"), 100);
+
+        let files = expand_patterns(&dir, &patterns(&["src/*.rs", "src/main.rs", "*"])).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("README"),
+                dir.join("src/lib.rs"),
+                dir.join("src/main.rs")
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_up_to_date() {
+        let dir = get_test_dir("up-to-date");
+        let inputs = patterns(&["src/**/*.rs"]);
+        let outputs = patterns(&["out/*.bin"]);
+        touch(&dir.join("src/main.rs"), 100);
+        assert!(!is_up_to_date(&dir, &inputs, &outputs).unwrap());
+
+        fs::create_dir(dir.join("out")).unwrap();
+        touch(&dir.join("out/app.bin"), 200);
+        touch(&dir.join("out/lib.bin"), 150);
+        assert!(is_up_to_date(&dir, &inputs, &outputs).unwrap());
+
+        touch(&dir.join("src/lib.rs"), 160);
+        assert!(!is_up_to_date(&dir, &inputs, &outputs).unwrap());
+        assert!(!is_up_to_date(&dir, &inputs, &patterns(&["out/*.bin", "out/*.map"])).unwrap());
+        assert!(is_up_to_date(&dir, &[], &outputs).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_patterns() {
+        assert!(validate_patterns(&patterns(&["src/**/*.rs", "Cargo.*"])).is_ok());
+        assert_eq!(
+            validate_patterns(&patterns(&["src/[.rs"])).unwrap_err(),
+            "Invalid pattern \"src/[.rs\": Pattern syntax error near position 4: invalid range pattern"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let dir = get_test_dir("fingerprint");
+        touch(&dir.join("src/main.rs"), 100);
+        let files = vec![dir.join("src/main.rs")];
+        let commands = vec!["cargo build".to_string()];
+        let mut env = HashMap::new();
+        env.insert("PROFILE".to_string(), "release".to_string());
+
+        let original = fingerprint(&commands, &env, &files).unwrap();
+        assert_eq!(original.len(), 64);
+        assert_eq!(fingerprint(&commands, &env, &files).unwrap(), original);
+
+        env.insert("PROFILE".to_string(), "debug".to_string());
+        assert_ne!(fingerprint(&commands, &env, &files).unwrap(), original);
+        env.insert("PROFILE".to_string(), "release".to_string());
+
+        let other_commands = vec!["cargo build --release".to_string()];
+        assert_ne!(
+            fingerprint(&other_commands, &env, &files).unwrap(),
+            original
+        );
+
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        assert_ne!(fingerprint(&commands, &env, &files).unwrap(), original);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_write_fingerprint() {
+        let dir = get_test_dir("fingerprints");
+        assert_eq!(read_fingerprint(&dir, "Yakefile:build").unwrap(), None);
+
+        write_fingerprint(&dir, "Yakefile:build", "abc").unwrap();
+        write_fingerprint(&dir, "Yakefile:test", "def").unwrap();
+        write_fingerprint(&dir, "Yakefile:build", "123").unwrap();
+        assert_eq!(
+            read_fingerprint(&dir, "Yakefile:build").unwrap(),
+            Some("123".to_string())
+        );
+        assert_eq!(
+            read_fingerprint(&dir, "Yakefile:test").unwrap(),
+            Some("def".to_string())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
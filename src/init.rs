@@ -0,0 +1,251 @@
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use error::YakeError;
+use version::YAKE_VERSION;
+
+/// Templates of a new Yakefile.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InitTemplate {
+    /// Targets echoing what they would do, for any kind of project
+    Generic,
+    /// Targets running cargo
+    Rust,
+    /// Targets running npm
+    Node,
+    /// Targets building and running a docker image
+    Docker,
+}
+
+const GENERIC_TARGETS: &str = r#"env:
+  APP: app
+
+targets:
+  # a callable target runs it's commands, `yake build` runs this one
+  build:
+    meta:
+      doc: "Builds the app"
+      type: callable
+    exec:
+      - echo "building $APP"
+
+  # a group has no commands of it's own, running it runs all of it's children,
+  # e.g. `yake test` runs test.unit and test.lint, `yake test.unit` only one of them
+  test:
+    meta:
+      doc: "Runs all checks"
+      type: group
+    targets:
+      unit:
+        meta:
+          doc: "Runs the unit tests"
+          type: callable
+          depends:
+            - build
+        exec:
+          - echo "testing $APP"
+      lint:
+        meta:
+          doc: "Lints the sources"
+          type: callable
+        exec:
+          - echo "linting $APP"
+"#;
+
+const RUST_TARGETS: &str = r#"env:
+  RUST_BACKTRACE: "1"
+
+targets:
+  # a callable target runs it's commands, `yake build` runs this one
+  build:
+    meta:
+      doc: "Builds the crate"
+      type: callable
+    exec:
+      - cargo build
+
+  # a group has no commands of it's own, running it runs all of it's children,
+  # e.g. `yake test` runs test.unit and test.lint, `yake test.unit` only one of them
+  test:
+    meta:
+      doc: "Runs all checks"
+      type: group
+    targets:
+      unit:
+        meta:
+          doc: "Runs the tests"
+          type: callable
+        exec:
+          - cargo test
+      lint:
+        meta:
+          doc: "Checks formatting and lints"
+          type: callable
+        exec:
+          - cargo fmt -- --check
+          - cargo clippy --all-targets -- -D warnings
+
+  release:
+    meta:
+      doc: "Builds the crate with optimizations"
+      type: callable
+      depends:
+        - test
+    exec:
+      - cargo build --release
+"#;
+
+const NODE_TARGETS: &str = r#"env:
+  NODE_ENV: development
+
+targets:
+  install:
+    meta:
+      doc: "Installs the dependencies"
+      type: callable
+    inputs:
+      - package.json
+      - package-lock.json
+    exec:
+      - npm ci
+
+  # a callable target runs it's commands, `yake build` runs this one after install
+  build:
+    meta:
+      doc: "Builds the app"
+      type: callable
+      depends:
+        - install
+    exec:
+      - npm run build
+
+  # a group has no commands of it's own, running it runs all of it's children,
+  # e.g. `yake test` runs test.unit and test.lint, `yake test.unit` only one of them
+  test:
+    meta:
+      doc: "Runs all checks"
+      type: group
+      depends:
+        - install
+    targets:
+      unit:
+        meta:
+          doc: "Runs the tests"
+          type: callable
+        exec:
+          - npm test
+      lint:
+        meta:
+          doc: "Lints the sources"
+          type: callable
+        exec:
+          - npm run lint
+"#;
+
+const DOCKER_TARGETS: &str = r#"env:
+  IMAGE: app
+  TAG: "{{git.short_sha}}"
+
+targets:
+  # a callable target runs it's commands, `yake build` runs this one
+  build:
+    meta:
+      doc: "Builds the image"
+      type: callable
+    exec:
+      - docker build -t $IMAGE:$TAG .
+
+  run:
+    meta:
+      doc: "Runs the image, arguments after -- are passed on"
+      type: callable
+      depends:
+        - build
+    exec:
+      - docker run --rm -it $IMAGE:$TAG {{ARGS}}
+    interactive: true
+
+  # a group has no commands of it's own, running it runs all of it's children,
+  # e.g. `yake image` runs image.scan and image.push
+  image:
+    meta:
+      doc: "Checks and publishes the image"
+      type: group
+      depends:
+        - build
+    targets:
+      scan:
+        meta:
+          doc: "Scans the image for vulnerabilities"
+          type: callable
+        exec:
+          - docker scout cves $IMAGE:$TAG
+      push:
+        meta:
+          doc: "Pushes the image"
+          type: callable
+          depends:
+            - image.scan
+        exec:
+          - docker push $IMAGE:$TAG
+"#;
+
+/// Content of a new Yakefile from a template.
+pub fn yakefile_template(template: InitTemplate) -> String {
+    let (doc, targets) = match template {
+        InitTemplate::Generic => ("Targets of the project", GENERIC_TARGETS),
+        InitTemplate::Rust => ("Targets of the crate", RUST_TARGETS),
+        InitTemplate::Node => ("Targets of the package", NODE_TARGETS),
+        InitTemplate::Docker => ("Targets of the image", DOCKER_TARGETS),
+    };
+    format!(
+        "# Run `yake <target>` to run a target, `yake` runs the default target and\n\
+         # `yake --list` lists all of them.\n\
+         meta:\n  doc: \"{}\"\n  # yake version the Yakefile needs at least\n  version: {}\n  \
+         default_target: build\n\n{}",
+        doc, YAKE_VERSION, targets
+    )
+}
+
+/// Writes a new Yakefile from a template into a directory. Fails if there is one
+/// already, returns it's path otherwise.
+pub fn init_yakefile(dir: &Path, template: InitTemplate) -> Result<PathBuf, YakeError> {
+    let path = dir.join("Yakefile");
+    let error = |message: String| {
+        YakeError::Invalid(format!("Unable to create {}: {}", path.display(), message))
+    };
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => error("it exists already".to_string()),
+            _ => error(e.to_string()),
+        })?;
+    file.write_all(yakefile_template(template).as_bytes())
+        .map_err(|e| error(e.to_string()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml;
+    use yake::Yake;
+
+    #[test]
+    fn test_yakefile_templates() {
+        for template in [
+            InitTemplate::Generic,
+            InitTemplate::Rust,
+            InitTemplate::Node,
+            InitTemplate::Docker,
+        ] {
+            let yake: Yake = serde_yaml::from_str(&yakefile_template(template))
+                .unwrap_or_else(|e| panic!("Unable to parse {:?}: {}", template, e));
+            assert_eq!(yake.validate(), Ok(()), "{:?}", template);
+            assert!(yake.has_target_name("build").is_ok(), "{:?}", template);
+        }
+    }
+}
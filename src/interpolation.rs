@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::env;
+
+/// Expands `$VAR` and `${VAR}` references with values of the given environment.
+///
+/// References to unknown variables are kept as they are, so content which is not meant
+/// for a shell (e.g. sql with `$1` or `$$` quoting) passes through unchanged.
+pub fn expand_env_vars(text: &str, env: &HashMap<String, String>) -> String {
+    replace_vars(text, false, |name| Ok(env.get(name).cloned()))
+        .expect("lookup of env vars never fails")
+}
+
+/// Variables which are used before any shell gets to see them, e.g. `PATH` to find the
+/// shell itself. Their values are expanded like a shell would, `$VAR` included.
+const SYSTEM_VARS: [&str; 5] = ["PATH", "HOME", "TERM", "TZ", "LANG"];
+
+/// Resolves `${VAR}` references between the values of an environment, regardless of
+/// the order the variables were declared in. References to variables which are not
+/// part of the environment are looked up in the environment of the yake process, so are
+/// references of a variable to itself if it is set there, e.g. `PATH: $HOME/bin:$PATH`.
+///
+/// Fails for references to unknown variables and for cyclic references.
+pub fn resolve_env_references(
+    env: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    let mut names: Vec<&String> = env.keys().collect();
+    names.sort();
+
+    let mut resolved = HashMap::new();
+    for name in names {
+        resolve_env_reference(name, env, &mut resolved, &mut Vec::new())?;
+    }
+    Ok(resolved)
+}
+
+/// Resolves the value of a single variable, `stack` holds the variables currently being
+/// resolved.
+fn resolve_env_reference(
+    name: &str,
+    env: &HashMap<String, String>,
+    resolved: &mut HashMap<String, String>,
+    stack: &mut Vec<String>,
+) -> Result<String, String> {
+    if let Some(value) = resolved.get(name) {
+        return Ok(value.clone());
+    }
+    if stack.iter().any(|n| n == name) {
+        let mut cycle = stack.clone();
+        cycle.push(name.to_string());
+        return Err(format!(
+            "Cyclic reference in env variables: {}",
+            cycle.join(" -> ")
+        ));
+    }
+
+    stack.push(name.to_string());
+    let braced_only = !SYSTEM_VARS.contains(&name);
+    let value = replace_vars(&env[name], braced_only, |reference| {
+        if reference == name {
+            if let Ok(inherited) = env::var(reference) {
+                return Ok(Some(inherited));
+            }
+        }
+        if env.contains_key(reference) {
+            return resolve_env_reference(reference, env, resolved, stack).map(Some);
+        }
+        env::var(reference).map(Some).map_err(|_| {
+            format!(
+                "Unknown variable ${{{}}} referenced by env variable {}",
+                reference, name
+            )
+        })
+    })?;
+    stack.pop();
+
+    resolved.insert(name.to_string(), value.clone());
+    Ok(value)
+}
+
+/// Replaces `{{VAR}}` placeholders with the given values, falling back to the
+/// environment of the yake process. Spaces around the name are allowed, names may
+/// contain dots (e.g. `{{meta.version}}`).
+///
+/// Braces which do not enclose a name are kept, so templates of other tools like
+/// `{{.Names}}` or `{{json .}}` pass through unchanged. Unknown names are an error.
+pub fn expand_placeholders(text: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    replace_placeholders(text, |name| match values.get(name) {
+        Some(value) => Ok(Some(value.clone())),
+        None => match env::var(name) {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(format!("Unknown variable {{{{{}}}}}", name)),
+        },
+    })
+}
+
+/// Replaces `{{VAR}}` placeholders, see `expand_placeholders`. `lookup` gets the value
+/// of a placeholder, `None` keeps the placeholder as it is.
+pub fn replace_placeholders<F>(text: &str, mut lookup: F) -> Result<String, String>
+where
+    F: FnMut(&str) -> Result<Option<String>, String>,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                result.push_str(&rest[start..]);
+                return Ok(result);
+            }
+        };
+
+        let name = after[..end].trim();
+        if !is_placeholder_name(name) {
+            result.push_str("{{");
+            rest = after;
+            continue;
+        }
+        match lookup(name)? {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Checks whether a string is a valid placeholder name, variable names separated by
+/// dots.
+fn is_placeholder_name(name: &str) -> bool {
+    name.split('.').all(is_var_name)
+}
+
+/// Checks that names and values of an environment can be passed to a process. Names
+/// must not be empty or contain `=`, neither names nor values may contain null bytes.
+pub fn validate_env_vars(env: &HashMap<String, String>) -> Result<(), String> {
+    let mut names: Vec<&String> = env.keys().collect();
+    names.sort();
+
+    for name in names {
+        if name.is_empty() || name.contains('=') || name.contains('\0') {
+            return Err(format!("Invalid env variable name {}", shell_quote(name)));
+        }
+        if env[name].contains('\0') {
+            return Err(format!("Env variable {} contains a null byte", name));
+        }
+    }
+    Ok(())
+}
+
+/// Quotes a value for use as a single word in a posix shell command. Values consisting
+/// of safe characters only are returned unchanged.
+pub fn shell_quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-.,:/=@%+".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Replaces variable references in a text, either `${VAR}` only or `$VAR` as well.
+/// `lookup` gets the value of a variable, `None` keeps the reference as it is.
+fn replace_vars<F>(text: &str, braced_only: bool, mut lookup: F) -> Result<String, String>
+where
+    F: FnMut(&str) -> Result<Option<String>, String>,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, len) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) if is_var_name(&braced[..end]) => (&braced[..end], end + 2),
+                _ => ("", 0),
+            }
+        } else if braced_only {
+            ("", 0)
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            if is_var_name(&after[..end]) {
+                (&after[..end], end)
+            } else {
+                ("", 0)
+            }
+        };
+
+        match if len > 0 { lookup(name)? } else { None } {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[pos..pos + 1 + len]),
+        }
+        rest = &after[len..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Checks whether a string is a valid environment variable name.
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_env() -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        env.insert("NAME".to_string(), "yake".to_string());
+        env.insert("PORT".to_string(), "5432".to_string());
+        env
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        let env = get_env();
+        assert_eq!(expand_env_vars("hello $NAME", &env), "hello yake");
+        assert_eq!(expand_env_vars("${NAME}_db:${PORT}", &env), "yake_db:5432");
+        assert_eq!(expand_env_vars("$NAME.$PORT", &env), "yake.5432");
+    }
+
+    #[test]
+    fn test_expand_env_vars_keeps_unknown() {
+        let env = get_env();
+        assert_eq!(
+            expand_env_vars("$UNKNOWN ${UNKNOWN}", &env),
+            "$UNKNOWN ${UNKNOWN}"
+        );
+        assert_eq!(
+            expand_env_vars("select $1, $$body$$", &env),
+            "select $1, $$body$$"
+        );
+        assert_eq!(expand_env_vars("${NAME", &env), "${NAME");
+        assert_eq!(expand_env_vars("costs 5$", &env), "costs 5$");
+    }
+
+    #[test]
+    fn test_resolve_env_references() {
+        let mut env = get_env();
+        env.insert("HEALTH_URL".to_string(), "${BASE_URL}/health".to_string());
+        env.insert(
+            "BASE_URL".to_string(),
+            "https://${HOST}:${PORT}".to_string(),
+        );
+        env.insert("HOST".to_string(), "${NAME}.example.com".to_string());
+        env.insert("SHELL_VAR".to_string(), "$NAME stays".to_string());
+
+        let resolved = resolve_env_references(&env).unwrap();
+        assert_eq!(
+            resolved.get("HEALTH_URL").unwrap(),
+            "https://yake.example.com:5432/health"
+        );
+        assert_eq!(resolved.get("SHELL_VAR").unwrap(), "$NAME stays");
+        assert_eq!(resolved.len(), env.len());
+    }
+
+    #[test]
+    fn test_resolve_env_references_from_process() {
+        let mut env = get_env();
+        env.insert(
+            "CARGO_DIR".to_string(),
+            "${CARGO_MANIFEST_DIR}/".to_string(),
+        );
+
+        let resolved = resolve_env_references(&env).unwrap();
+        assert_eq!(
+            resolved.get("CARGO_DIR").unwrap(),
+            &format!("{}/", env!("CARGO_MANIFEST_DIR"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_references_cycle() {
+        let mut env = get_env();
+        env.insert("A".to_string(), "${B}".to_string());
+        env.insert("B".to_string(), "${C}".to_string());
+        env.insert("C".to_string(), "x${A}".to_string());
+
+        assert_eq!(
+            resolve_env_references(&env).unwrap_err(),
+            "Cyclic reference in env variables: A -> B -> C -> A"
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_references_unknown() {
+        let mut env = get_env();
+        env.insert("A".to_string(), "${YAKE_DOES_NOT_EXIST}".to_string());
+
+        assert_eq!(
+            resolve_env_references(&env).unwrap_err(),
+            "Unknown variable ${YAKE_DOES_NOT_EXIST} referenced by env variable A"
+        );
+    }
+
+    #[test]
+    fn test_validate_env_vars() {
+        let mut env = get_env();
+        env.insert("MULTI".to_string(), "line\n'quoted' \"$x\"".to_string());
+        assert!(validate_env_vars(&env).is_ok());
+
+        env.insert("NULL".to_string(), "a\0b".to_string());
+        assert_eq!(
+            validate_env_vars(&env).unwrap_err(),
+            "Env variable NULL contains a null byte"
+        );
+
+        let mut env = get_env();
+        env.insert("A=B".to_string(), "x".to_string());
+        assert_eq!(
+            validate_env_vars(&env).unwrap_err(),
+            "Invalid env variable name A=B"
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("plain-value_1.0"), "plain-value_1.0");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("say \"hi\""), "'say \"hi\"'");
+        assert_eq!(shell_quote("$HOME `id`"), "'$HOME `id`'");
+        assert_eq!(shell_quote("two\nlines"), "'two\nlines'");
+    }
+
+    #[test]
+    fn test_shell_quote_round_trip() {
+        let values = [
+            "it's",
+            "say \"hi\"",
+            "$HOME and ${PATH} and $(id)",
+            "two\nlines\n",
+            "back\\slash",
+            "",
+        ];
+        for value in values.iter() {
+            let output = std::process::Command::new("bash")
+                .arg("-c")
+                .arg(format!("printf %s {}", shell_quote(value)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), *value);
+        }
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let mut values = get_env();
+        values.insert("meta.version".to_string(), "1.2.0".to_string());
+
+        assert_eq!(
+            expand_placeholders("docker build -t {{NAME}}:{{ meta.version }} .", &values).unwrap(),
+            "docker build -t yake:1.2.0 ."
+        );
+        assert_eq!(
+            expand_placeholders("{{CARGO_PKG_NAME}}", &values).unwrap(),
+            env!("CARGO_PKG_NAME")
+        );
+        assert_eq!(
+            expand_placeholders("{{UNKNOWN_PLACEHOLDER}}", &values).unwrap_err(),
+            "Unknown variable {{UNKNOWN_PLACEHOLDER}}"
+        );
+    }
+
+    #[test]
+    fn test_expand_placeholders_keeps_other_templates() {
+        let values = get_env();
+        for text in [
+            "docker ps --format '{{.Names}}'",
+            "{{json .}} {{",
+            "{{}} $NAME",
+            "{{NAME",
+        ]
+        .iter()
+        {
+            assert_eq!(expand_placeholders(text, &values).unwrap(), *text);
+        }
+        assert_eq!(
+            expand_placeholders("{{{{NAME}}}}", &values).unwrap(),
+            "{{yake}}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_references_system_vars() {
+        let mut env = get_env();
+        env.insert("PATH".to_string(), "$HOME/bin:${PATH}".to_string());
+        env.insert("BIN_DIR".to_string(), "${HOME}/bin".to_string());
+        env.insert("OTHER".to_string(), "$HOME stays".to_string());
+
+        let resolved = resolve_env_references(&env).unwrap();
+        let home = env::var("HOME").unwrap();
+        assert_eq!(
+            resolved.get("PATH").unwrap(),
+            &format!("{}/bin:{}", home, env::var("PATH").unwrap())
+        );
+        assert_eq!(resolved.get("BIN_DIR").unwrap(), &format!("{}/bin", home));
+        assert_eq!(resolved.get("OTHER").unwrap(), "$HOME stays");
+    }
+
+    #[test]
+    fn test_resolve_env_references_self() {
+        let mut env = get_env();
+        env.insert(
+            "CARGO_PKG_NAME".to_string(),
+            "${CARGO_PKG_NAME}-test".to_string(),
+        );
+
+        let resolved = resolve_env_references(&env).unwrap();
+        assert_eq!(
+            resolved.get("CARGO_PKG_NAME").unwrap(),
+            &format!("{}-test", env!("CARGO_PKG_NAME"))
+        );
+    }
+
+    #[test]
+    fn test_replace_placeholders() {
+        let replaced = replace_placeholders("{{ a }} {{b}} {{.Names}}", |name| {
+            Ok(if name == "a" {
+                Some("x".to_string())
+            } else {
+                None
+            })
+        });
+        assert_eq!(replaced.unwrap(), "x {{b}} {{.Names}}");
+    }
+}
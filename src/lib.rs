@@ -0,0 +1,77 @@
+//! Yake
+//! ``make`` with yaml files.
+//!
+//! Use yaml files to specify Makefile-like targets and execute these via CLI.
+//!
+//! Yakefiles can also be loaded and executed from other tools:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use yake::{find_yakefile, load_yml_from_file, ExecutionOptions};
+//!
+//! let file = find_yakefile(Path::new(".")).expect("No Yakefile found");
+//! let yake = load_yml_from_file(&file).unwrap();
+//! for target_name in yake.get_target_names() {
+//!     println!("{}", target_name);
+//! }
+//! yake.execute("build", &ExecutionOptions::default()).unwrap();
+//! ```
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate colored;
+extern crate glob;
+#[cfg(unix)]
+extern crate libc;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate sha2;
+extern crate toml;
+extern crate walkdir;
+extern crate yaml_rust;
+
+mod builtins;
+mod check;
+mod condition;
+mod duration;
+mod env_file;
+pub mod error;
+mod export;
+mod gitignore;
+mod graph;
+mod import;
+mod incremental;
+mod init;
+mod interpolation;
+mod picker;
+mod progress;
+mod prompt;
+mod remote;
+mod reporter;
+mod service;
+mod signals;
+mod version;
+pub mod watch;
+pub mod yake;
+pub mod yaml;
+
+pub use check::check_yakefile;
+pub use error::YakeError;
+pub use export::{export_yake, ExportFormat};
+pub use graph::graph_to_dot;
+pub use import::import_makefile;
+pub use init::{init_yakefile, InitTemplate};
+pub use picker::pick_target;
+pub use remote::pin_remote_includes;
+pub use reporter::{CiFolding, ColorChoice, OutputFormat, Verbosity};
+pub use version::YAKE_VERSION;
+pub use watch::watch_target;
+pub use yake::{
+    CommandResult, ExecutionOptions, ExecutionReport, TargetDefinition, TargetLocation,
+    TargetResult, TargetStatus, Yake,
+};
+pub use yaml::{
+    deny_unknown_fields, find_sub_yakefiles, find_yakefile, load_yml_from_file,
+    load_yml_from_subdirs, parse_yml_from_file, read_yml_from_file,
+};
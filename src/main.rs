@@ -1,52 +1,533 @@
-//! Yake
-//! ``make`` with yaml files.
-//!
-//! Use yaml files to specify Makefile-like targets and execute these via CLI.
+//! Command line interface of yake, a thin wrapper around the library.
 extern crate clap;
-extern crate serde;
-#[macro_use]
-extern crate serde_derive;
 extern crate colored;
-extern crate serde_yaml;
-extern crate walkdir;
+extern crate serde_json;
+extern crate yake;
 
-use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
-use args::create_cli_app;
-use yaml::{load_yml_from_file, load_yml_from_subdirs};
+use args::{create_cli_app, YakeArgs, YakeSubcommand};
+use colored::Colorize;
+use yake::{
+    check_yakefile, deny_unknown_fields, export_yake, find_sub_yakefiles, find_yakefile,
+    graph_to_dot, import_makefile, init_yakefile, load_yml_from_file, pick_target,
+    pin_remote_includes, watch_target, ExecutionOptions, ExecutionReport, OutputFormat,
+    TargetDefinition, TargetLocation, TargetResult, TargetStatus, Verbosity, Yake, YakeError,
+};
 
 mod args;
-pub mod yake;
-mod yaml;
 
 fn main() {
     let yake_args = create_cli_app();
+    yake_args.color.apply();
 
-    let mut yake = load_yml_from_file("Yakefile");
+    if let Err(e) = run(&yake_args) {
+        eprintln!("{}", e);
+        exit(e.exit_code());
+    }
+}
+
+/// Loads the Yakefile and runs the subcommand or target given on the command line.
+fn run(yake_args: &YakeArgs) -> Result<(), YakeError> {
+    // creating a Yakefile and importing a Makefile need no Yakefile
+    if let Some(YakeSubcommand::Init { template }) = yake_args.subcommand {
+        let path = init_yakefile(Path::new("."), template)?;
+        println!(
+            "Created {}, run yake --list to list it's targets",
+            path.display()
+        );
+        return Ok(());
+    }
+    if let Some(YakeSubcommand::Import { ref makefile }) = yake_args.subcommand {
+        let content = fs::read_to_string(makefile).map_err(|e| YakeError::Load {
+            path: PathBuf::from(makefile),
+            message: e.to_string(),
+        })?;
+        print!("{}", import_makefile(&content));
+        return Ok(());
+    }
+    let file = match yake_args.file {
+        Some(ref file) => PathBuf::from(file),
+        None => find_yakefile(Path::new(".")).ok_or(YakeError::NoYakefile)?,
+    };
+    // the Yakefile is checked before it is loaded, loading fails for the first problem
+    if let Some(YakeSubcommand::Check) = yake_args.subcommand {
+        return check(&file);
+    }
+    if yake_args.strict {
+        deny_unknown_fields(&file)?;
+    }
+    let mut yake = load_yml_from_file(&file)?;
+    if let Some(ref profile) = yake_args.profile {
+        yake.apply_profile(profile)
+            .map_err(|message| YakeError::Load {
+                path: file.clone(),
+                message,
+            })?;
+    }
+    yake.args = yake_args.args.clone();
+    yake.params = yake_args.params.clone();
+    yake.env_overrides = yake_args.env.clone();
+    yake.update_includes = yake_args.update_includes;
+    // the includes are pinned before they are loaded, loading fails for changed ones
+    if let Some(YakeSubcommand::Includes { update_hashes }) = yake_args.subcommand {
+        return includes(&yake, update_hashes);
+    }
+
+    for path in find_sub_yakefiles(&yake)? {
+        if yake_args.strict {
+            deny_unknown_fields(&path)?;
+        }
+        yake.add_sub_yake(load_yml_from_file(&path)?)
+            .map_err(|message| YakeError::Load { path, message })?;
+    }
+
+    match yake_args.subcommand {
+        Some(YakeSubcommand::Which { ref target, format }) => return which(&yake, target, format),
+        Some(YakeSubcommand::Graph) => {
+            print!("{}", graph_to_dot(&yake));
+            return Ok(());
+        }
+        Some(YakeSubcommand::Export { format }) => {
+            print!("{}", export_yake(&yake, format));
+            return Ok(());
+        }
+        Some(YakeSubcommand::Stop { ref services }) => return stop(&yake, services),
+        Some(YakeSubcommand::Status { ref services }) => return status(&yake, services),
+        Some(YakeSubcommand::Check)
+        | Some(YakeSubcommand::Includes { .. })
+        | Some(YakeSubcommand::Init { .. })
+        | Some(YakeSubcommand::Import { .. })
+        | Some(YakeSubcommand::Start { .. })
+        | None => {}
+    }
 
-    let path = env::current_dir().unwrap();
+    let targets = if let Some(YakeSubcommand::Start { ref services }) = yake_args.subcommand {
+        for service in services {
+            yake.has_service_name(service)?;
+        }
+        services.clone()
+    } else if yake_args.list {
+        print_targets(&yake);
+        return Ok(());
+    } else if !yake_args.targets.is_empty() {
+        yake_args.targets.clone()
+    } else if let Some(ref target) = yake.meta.default_target {
+        vec![target.clone()]
+    } else if io::stdin().is_terminal() && io::stderr().is_terminal() {
+        let picked = pick_target(&yake)
+            .map_err(|e| YakeError::Invalid(format!("Unable to pick a target: {}", e)))?;
+        match picked {
+            Some(target) => vec![target],
+            None => return Ok(()),
+        }
+    } else {
+        print_targets(&yake);
+        return Ok(());
+    };
+
+    let options = ExecutionOptions {
+        no_silent: yake_args.no_silent,
+        ci_folding: yake_args.ci_folding,
+        deps_only: yake_args.deps_only,
+        skip_deps: yake_args.skip_deps,
+        force: yake_args.force,
+        dry_run: yake_args.dry_run,
+        jobs: yake_args.jobs,
+        keep_going: yake_args.keep_going,
+        output: yake_args.output,
+        verbosity: yake_args.verbosity,
+        strict: yake_args.strict,
+        no_input: yake_args.no_input,
+    };
+
+    if yake_args.watch {
+        return watch_target(&yake, &targets, &options, yake_args.clear);
+    }
 
-    let sub_yakes = match yake.meta.include_recursively {
-        Some(true) => load_yml_from_subdirs(path.to_str().unwrap()).unwrap(),
-        _ => Vec::new(),
+    let result = match options.output {
+        OutputFormat::Text => {
+            let report = yake.execute_with_report(&targets, &options)?;
+            if yake_args.timings {
+                print_timings(&report);
+            }
+            // a single target needs no summary, it's output ends with it's result
+            if options.verbosity >= Verbosity::Normal
+                && !options.dry_run
+                && report.targets.len() > 1
+            {
+                print_summary(&report);
+            }
+            report.into_result()
+        }
+        OutputFormat::Json => {
+            let report = yake.execute_with_report(&targets, &options)?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("Unable to serialize report")
+            );
+            report.into_result()
+        }
     };
+    result.map_err(|e| match e {
+        YakeError::Failed { message, exit_code } => YakeError::Failed {
+            message: format!(
+                "Execution of target: {} failed. {}",
+                targets.join(" "),
+                message
+            ),
+            exit_code,
+        },
+        e => e,
+    })
+}
 
-    sub_yakes
+/// Prints the callable targets along with their docs, with `--list` or if no target
+/// is given, the Yakefile has no default target and yake does not run in a terminal.
+/// The targets are listed as a tree of their groups: each group heads a section with
+/// it's name and doc, followed by it's targets and the sections of it's subgroups,
+/// indented by their depth.
+fn print_targets(yake: &Yake) {
+    let mut names = yake.get_target_names();
+    names.sort();
+    let mut groups = yake.get_group_names();
+    groups.sort();
+    let tree = TargetTree {
+        yake,
+        names: &names,
+        groups: &groups,
+    };
+    let width = names
         .iter()
-        .for_each(|sub_yake| yake.add_sub_yake(sub_yake.clone()));
+        .map(|name| tree.indent(tree.section(name)) + name.len())
+        .chain(
+            groups
+                .iter()
+                .map(|group| tree.header_indent(group) + group.len()),
+        )
+        .max()
+        .unwrap_or(0);
+
+    println!("Available targets:");
+    tree.print_section(None, width);
+}
+
+/// The listed targets and groups of a yake, sorted by name.
+struct TargetTree<'a> {
+    yake: &'a Yake,
+    names: &'a [String],
+    groups: &'a [String],
+}
+
+impl<'a> TargetTree<'a> {
+    /// The closest group containing a target or group which is listed, `None` for the
+    /// top level.
+    fn section(&self, name: &str) -> Option<&'a str> {
+        let mut parent = name;
+        while let Some((group, _)) = parent.rsplit_once('.') {
+            if let Some(group) = self.groups.iter().find(|listed| *listed == group) {
+                return Some(group);
+            }
+            parent = group;
+        }
+        None
+    }
+
+    /// Indentation of the targets of a section, two spaces more than it's header.
+    fn indent(&self, section: Option<&str>) -> usize {
+        match section {
+            Some(group) => self.header_indent(group) + 2,
+            None => 2,
+        }
+    }
+
+    /// Indentation of the header of a group, the one of the targets of the section it
+    /// is in, except for top level groups which are not indented.
+    fn header_indent(&self, group: &str) -> usize {
+        match self.section(group) {
+            Some(parent) => self.indent(Some(parent)),
+            None => 0,
+        }
+    }
 
-    match yake.has_target_name(&yake_args.target) {
-        Err(x) => {
-            eprintln!(
-                "Unknown target: '{}' Available targets are: {:?}",
-                yake_args.target, x
+    /// Prints the targets of a section followed by the sections of it's groups.
+    fn print_section(&self, section: Option<&str>, width: usize) {
+        let indent = self.indent(section);
+        for name in self
+            .names
+            .iter()
+            .filter(|name| self.section(name) == section)
+        {
+            let target = self.yake.get_target_by_name(name).unwrap_or_default();
+            let entry = format!("{:indent$}{}", "", name, indent = indent);
+            match target.meta.deprecated {
+                Some(reason) => println!(
+                    "{:width$}  {} {}",
+                    entry,
+                    target.meta.doc,
+                    format!("(deprecated: {})", reason).yellow(),
+                    width = width
+                ),
+                None => println!("{:width$}  {}", entry, target.meta.doc, width = width),
+            }
+        }
+        for group in self
+            .groups
+            .iter()
+            .filter(|group| self.section(group) == section)
+        {
+            let doc = self
+                .yake
+                .get_target_by_name(group)
+                .unwrap_or_default()
+                .meta
+                .doc;
+            let header = format!(
+                "{:indent$}{}",
+                "",
+                group,
+                indent = self.header_indent(group)
             );
-            exit(1);
+            println!();
+            println!(
+                "{}  {}",
+                format!("{:width$}", header, width = width).bold(),
+                doc
+            );
+            self.print_section(Some(group), width);
         }
-        _ => (),
+    }
+}
+
+/// Maximum length of command lines in the timings, longer ones are shortened.
+const TIMINGS_COMMAND_WIDTH: usize = 48;
+
+/// Prints the duration of each target that ran and of it's commands, along with the
+/// total duration.
+fn print_timings(report: &ExecutionReport) {
+    let mut rows = Vec::new();
+    for target in &report.targets {
+        match target.status {
+            TargetStatus::Succeeded | TargetStatus::Failed => {
+                rows.push((target.target.clone(), format!("{:.2}s", target.duration)))
+            }
+            TargetStatus::Skipped => rows.push((target.target.clone(), "skipped".to_string())),
+            TargetStatus::NotRun => continue,
+        }
+        for command in &target.commands {
+            let mut line: String = command.command.lines().next().unwrap_or_default().into();
+            if line.chars().count() > TIMINGS_COMMAND_WIDTH {
+                line = line.chars().take(TIMINGS_COMMAND_WIDTH - 1).collect();
+                line.push('…');
+            }
+            rows.push((format!("  {}", line), format!("{:.2}s", command.duration)));
+        }
+    }
+    rows.push(("Total".to_string(), format!("{:.2}s", report.duration)));
+
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    println!("Timings:");
+    for (name, duration) in rows {
+        println!("  {:width$}  {:>8}", name, duration, width = width);
+    }
+}
+
+/// Label of the status of a target in the summary, targets skipped as their outputs
+/// are up to date or their inputs unchanged and targets skipped with `--deps-only`
+/// are told apart from other skipped ones.
+fn summary_status(target: &TargetResult) -> String {
+    match target.status {
+        TargetStatus::Succeeded => "ok".to_string(),
+        TargetStatus::Failed => match target.exit_code {
+            Some(exit_code) => format!("failed (exit {})", exit_code),
+            None => "failed".to_string(),
+        },
+        TargetStatus::Skipped => match target.reason.as_deref() {
+            Some("up to date") | Some("unchanged") => "up-to-date".to_string(),
+            Some("deps-only") => "skipped (deps-only)".to_string(),
+            _ => "skipped".to_string(),
+        },
+        TargetStatus::NotRun => "not run".to_string(),
+    }
+}
+
+/// Prints the status and duration of each target after several targets ran, followed
+/// by a line telling whether all of them passed along with the number of targets of
+/// each status.
+fn print_summary(report: &ExecutionReport) {
+    let rows: Vec<(&TargetResult, String)> = report
+        .targets
+        .iter()
+        .map(|target| (target, summary_status(target)))
+        .collect();
+    let name_width = rows
+        .iter()
+        .map(|(target, _)| target.target.len())
+        .max()
+        .unwrap_or(0);
+    let status_width = rows
+        .iter()
+        .map(|(_, status)| status.len())
+        .max()
+        .unwrap_or(0);
+
+    println!("Summary:");
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for (target, status) in &rows {
+        let (symbol, duration) = match target.status {
+            TargetStatus::Succeeded => ("✓".green(), format!("{:.2}s", target.duration)),
+            TargetStatus::Failed => ("✗".red(), format!("{:.2}s", target.duration)),
+            TargetStatus::Skipped => ("↷".yellow(), String::new()),
+            TargetStatus::NotRun => ("-".dimmed(), String::new()),
+        };
+        let row = format!(
+            "  {} {:name_width$}  {:status_width$}  {}",
+            symbol,
+            target.target,
+            status,
+            duration,
+            name_width = name_width,
+            status_width = status_width
+        );
+        println!("{}", row.trim_end());
+        let label = status.split(" (").next().unwrap_or_default().to_string();
+        match counts.iter_mut().find(|(counted, _)| *counted == label) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((label, 1)),
+        }
+    }
+
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(label, count)| format!("{} {}", count, label))
+        .collect();
+    let totals = format!("{} in {:.2}s", counts.join(", "), report.duration);
+    if report.interrupted {
+        println!("{} {}", "✗ Interrupted:".bold().red(), totals);
+    } else if report.success {
+        println!("{} {}", "✓ Passed:".bold().green(), totals);
+    } else {
+        println!("{} {}", "✗ Failed:".bold().red(), totals);
+    }
+}
+
+/// Prints where a target is defined.
+fn which(yake: &Yake, target: &str, format: OutputFormat) -> Result<(), YakeError> {
+    let location = yake.get_target_location(target)?;
+
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&location).expect("Unable to serialize location")
+        ),
+        OutputFormat::Text => print_location(&location),
+    }
+    Ok(())
+}
+
+/// Stops the given services, all running ones if none are given.
+fn stop(yake: &Yake, services: &[String]) -> Result<(), YakeError> {
+    for service in services {
+        yake.has_service_name(service)?;
+    }
+    let named = !services.is_empty();
+    let all = yake.get_service_names();
+    let services = if named { services } else { &all };
+    for service in services {
+        match yake.stop_service(service)? {
+            Some(pid) => println!(
+                "{} {} (pid {})",
+                "↪ Stopped".bold().blue(),
+                service.bold().green(),
+                pid
+            ),
+            None if named => println!("{} is not running", service),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Prints whether the given services are running, all of them if none are given.
+fn status(yake: &Yake, services: &[String]) -> Result<(), YakeError> {
+    for service in services {
+        yake.has_service_name(service)?;
+    }
+    let all = yake.get_service_names();
+    let services = if services.is_empty() { &all } else { services };
+    let width = services.iter().map(|name| name.len()).max().unwrap_or(0);
+    for service in services {
+        let status = match yake.get_service_pid(service) {
+            Some(pid) => format!("running, pid {}", pid).green(),
+            None => "stopped".yellow(),
+        };
+        println!(
+            "{}  {}",
+            format!("{:width$}", service, width = width).bold(),
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Prints the remote includes of the Yakefile pinned to the digests of their content,
+/// as items of `includes`. With `update_hashes` they are downloaded again regardless
+/// of their pins, otherwise the cached content is pinned.
+fn includes(yake: &Yake, update_hashes: bool) -> Result<(), YakeError> {
+    let includes = yake.meta.includes.as_deref().unwrap_or_default();
+    let pinned =
+        pin_remote_includes(&yake.get_base_dir(), includes, update_hashes).map_err(|message| {
+            YakeError::Load {
+                path: yake
+                    .source
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("Yakefile")),
+                message,
+            }
+        })?;
+    for include in pinned {
+        println!("- {}", include);
+    }
+    Ok(())
+}
+
+/// Prints all problems of a Yakefile, fails if there are any.
+fn check(file: &Path) -> Result<(), YakeError> {
+    let problems = check_yakefile(file)?;
+    if problems.is_empty() {
+        println!("No problems found in {}", file.display());
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    Err(YakeError::Invalid(format!(
+        "Found {} problem(s) in {}",
+        problems.len(),
+        file.display()
+    )))
+}
+
+/// Prints a target location as text, e.g. `docker.postgres: ./Yakefile:22`.
+fn print_location(location: &TargetLocation) {
+    let format_definition = |definition: &TargetDefinition| match definition.line {
+        Some(line) => format!("{}:{}", definition.file.display(), line),
+        None => format!("{}", definition.file.display()),
     };
 
-    yake.execute(&yake_args.target)
-        .expect(format!("Execution of target: {} failed.", &yake_args.target).as_str());
+    println!(
+        "{}: {}",
+        location.target,
+        format_definition(&location.definition)
+    );
+    for definition in &location.overrides {
+        println!("  overrides {}", format_definition(definition));
+    }
 }
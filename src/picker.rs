@@ -0,0 +1,176 @@
+use std::io::{self, BufRead, Write};
+
+use colored::Colorize;
+
+use yake::Yake;
+
+/// Lets the user pick one of the callable targets, by typing a filter and then the
+/// number of a target. An empty line picks the best match of the filter, clears the
+/// filter if nothing matches it and quits without a filter, as does the end of the
+/// input. The targets and prompts are written to stderr.
+pub fn pick_target(yake: &Yake) -> io::Result<Option<String>> {
+    let mut targets: Vec<(String, String)> = yake
+        .get_target_names()
+        .into_iter()
+        .map(|name| {
+            let doc = yake.get_target_by_name(&name).unwrap_or_default().meta.doc;
+            (name, doc)
+        })
+        .collect();
+    targets.sort();
+    pick(&targets, io::stdin().lock(), io::stderr())
+}
+
+/// Picks one of the targets, given as name and doc, reading the input line by line.
+fn pick<R: BufRead, W: Write>(
+    targets: &[(String, String)],
+    input: R,
+    mut output: W,
+) -> io::Result<Option<String>> {
+    let mut lines = input.lines();
+    let mut query = String::new();
+    loop {
+        let matches = filter_targets(targets, &query);
+        let width = matches
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        for (i, (name, doc)) in matches.iter().enumerate() {
+            writeln!(
+                output,
+                "{:>3}  {}  {}",
+                i + 1,
+                format!("{:width$}", name, width = width).bold().green(),
+                doc
+            )?;
+        }
+        if matches.is_empty() {
+            writeln!(output, "No target matches \"{}\"", query)?;
+        }
+        write!(output, "{} ", "Filter or number:".bold().blue())?;
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(None),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            if query.is_empty() {
+                return Ok(None);
+            }
+            if let Some((name, _)) = matches.first() {
+                return Ok(Some(name.to_string()));
+            }
+        } else if let Ok(i) = line.parse::<usize>() {
+            if i >= 1 && i <= matches.len() {
+                return Ok(Some(matches[i - 1].0.to_string()));
+            }
+            continue;
+        }
+        query = line.to_string();
+    }
+}
+
+/// Targets matching the query, the best matches first. Targets match if the
+/// characters of the query appear in their name in order, or if their doc contains
+/// the query. Both ignore case.
+fn filter_targets<'a>(targets: &'a [(String, String)], query: &str) -> Vec<&'a (String, String)> {
+    let query = query.to_lowercase();
+    let mut matches: Vec<(usize, &(String, String))> = targets
+        .iter()
+        .filter_map(|target| {
+            let (name, doc) = target;
+            match fuzzy_match(&name.to_lowercase(), &query) {
+                Some(span) => Some((span, target)),
+                None if doc.to_lowercase().contains(&query) => Some((usize::MAX, target)),
+                None => None,
+            }
+        })
+        .collect();
+    matches.sort_by_key(|&(span, _)| span);
+    matches.into_iter().map(|(_, target)| target).collect()
+}
+
+/// Length of the shortest part of `text` containing the characters of `query` in
+/// order, if there is one. Shorter parts are better matches.
+fn fuzzy_match(text: &str, query: &str) -> Option<usize> {
+    let text: Vec<char> = text.chars().collect();
+    let query: Vec<char> = query.chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    (0..text.len())
+        .filter(|&start| text[start] == query[0])
+        .filter_map(|start| {
+            let mut matched = 0;
+            for (i, &c) in text[start..].iter().enumerate() {
+                if c == query[matched] {
+                    matched += 1;
+                    if matched == query.len() {
+                        return Some(i + 1);
+                    }
+                }
+            }
+            None
+        })
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets() -> Vec<(String, String)> {
+        [
+            ("build", "Builds the app"),
+            ("docker.build", "Builds the image"),
+            ("docker.push", "Pushes the image"),
+            ("test", "Runs all tests"),
+        ]
+        .iter()
+        .map(|(name, doc)| (name.to_string(), doc.to_string()))
+        .collect()
+    }
+
+    fn pick_with(input: &str) -> Option<String> {
+        pick(&targets(), input.as_bytes(), io::sink()).unwrap()
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("docker.push", "dp"), Some(8));
+        assert_eq!(fuzzy_match("docker.push", "push"), Some(4));
+        assert_eq!(fuzzy_match("build", "bd"), Some(5));
+        assert_eq!(fuzzy_match("build", "db"), None);
+    }
+
+    #[test]
+    fn test_filter_targets() {
+        let targets = targets();
+        let names = |query: &str| -> Vec<&str> {
+            filter_targets(&targets, query)
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect()
+        };
+
+        assert_eq!(names("build"), vec!["build", "docker.build"]);
+        assert_eq!(names("dpush"), vec!["docker.push"]);
+        assert_eq!(names("image"), vec!["docker.build", "docker.push"]);
+        assert_eq!(names("PUSH"), vec!["docker.push"]);
+        assert!(names("deploy").is_empty());
+    }
+
+    #[test]
+    fn test_pick() {
+        assert_eq!(pick_with("4\n"), Some("test".to_string()));
+        assert_eq!(pick_with("push\n\n"), Some("docker.push".to_string()));
+        assert_eq!(pick_with("docker\n2\n"), Some("docker.push".to_string()));
+        assert_eq!(pick_with("docker\n9\n2\n"), Some("docker.push".to_string()));
+        assert_eq!(pick_with("deploy\n\n4\n"), Some("test".to_string()));
+        assert_eq!(pick_with("\n"), None);
+        assert_eq!(pick_with("docker\n"), None);
+    }
+}
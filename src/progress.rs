@@ -0,0 +1,264 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+/// Frames of the spinner of running targets.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Interval the status area is redrawn in, for the spinners and elapsed times.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Number of finished targets shown in the status area, older ones are dropped.
+const FINISHED_LINES: usize = 4;
+
+/// Maximum length of target names in the status area, longer ones are shortened so
+/// that lines do not wrap.
+const NAME_WIDTH: usize = 48;
+
+/// Status of a target in the status area.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ProgressStatus {
+    Running,
+    Succeeded(Duration),
+    Failed(Duration),
+}
+
+/// A target shown in the status area.
+struct ProgressTarget {
+    name: String,
+    started: Instant,
+    status: ProgressStatus,
+}
+
+/// Lines below the output showing the running targets with a spinner and their
+/// elapsed time, along with the most recently finished ones.
+struct StatusArea {
+    targets: Vec<ProgressTarget>,
+    /// Number of lines currently drawn
+    lines: usize,
+    frame: usize,
+    /// Whether an interactive target has the terminal
+    paused: bool,
+}
+
+impl StatusArea {
+    /// Removes the drawn lines, the cursor is left where the first one was.
+    fn erase(&mut self, out: &mut impl Write) {
+        if self.lines > 0 {
+            // flushed right away, the output following it may go to stderr
+            let _ = write!(out, "\x1b[{}F\x1b[J", self.lines);
+            let _ = out.flush();
+            self.lines = 0;
+        }
+    }
+
+    /// Draws the lines of the area unless it is paused, replacing the drawn ones.
+    fn draw(&mut self, out: &mut impl Write) {
+        self.erase(out);
+        if self.paused {
+            return;
+        }
+        let lines = render(&self.targets, self.frame, Instant::now());
+        self.lines = lines.len();
+        for line in lines {
+            let _ = writeln!(out, "{}", line);
+        }
+        let _ = out.flush();
+    }
+}
+
+/// The status area while targets run, there is at most one.
+static AREA: Mutex<Option<StatusArea>> = Mutex::new(None);
+
+/// Lines of the status area: the finished targets, at most `FINISHED_LINES` of them,
+/// followed by the running ones.
+fn render(targets: &[ProgressTarget], frame: usize, now: Instant) -> Vec<String> {
+    let finished: Vec<&ProgressTarget> = targets
+        .iter()
+        .filter(|target| target.status != ProgressStatus::Running)
+        .collect();
+    let skipped = finished.len().saturating_sub(FINISHED_LINES);
+    let running = targets
+        .iter()
+        .filter(|target| target.status == ProgressStatus::Running);
+
+    finished[skipped..]
+        .iter()
+        .cloned()
+        .chain(running)
+        .map(|target| {
+            let mut name: String = target.name.chars().take(NAME_WIDTH).collect();
+            if target.name.chars().count() > NAME_WIDTH {
+                name.pop();
+                name.push('…');
+            }
+            let (symbol, elapsed) = match target.status {
+                ProgressStatus::Running => (
+                    SPINNER[frame % SPINNER.len()].to_string().bold().cyan(),
+                    now - target.started,
+                ),
+                ProgressStatus::Succeeded(elapsed) => ("✓".bold().green(), elapsed),
+                ProgressStatus::Failed(elapsed) => ("✗".bold().red(), elapsed),
+            };
+            format!(
+                "{} {} {}",
+                symbol,
+                name,
+                format!("{:.1}s", elapsed.as_secs_f64()).dimmed()
+            )
+        })
+        .collect()
+}
+
+/// Shows the status area below the output while it lives, the area is redrawn
+/// regularly by a thread. It is removed once the guard is dropped.
+pub struct ProgressGuard {
+    ticker: Option<JoinHandle<()>>,
+}
+
+impl ProgressGuard {
+    /// Starts showing the status area. Only one is shown at a time, `None` if there is
+    /// one already.
+    pub fn new() -> Option<ProgressGuard> {
+        let mut area = AREA.lock().unwrap();
+        if area.is_some() {
+            return None;
+        }
+        *area = Some(StatusArea {
+            targets: Vec::new(),
+            lines: 0,
+            frame: 0,
+            paused: false,
+        });
+        let ticker = thread::spawn(|| loop {
+            thread::sleep(TICK);
+            let mut area = AREA.lock().unwrap();
+            match *area {
+                Some(ref mut area) => {
+                    area.frame += 1;
+                    area.draw(&mut io::stdout().lock());
+                }
+                None => break,
+            }
+        });
+        Some(ProgressGuard {
+            ticker: Some(ticker),
+        })
+    }
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        if let Some(mut area) = AREA.lock().unwrap().take() {
+            area.erase(&mut io::stdout().lock());
+        }
+        if let Some(ticker) = self.ticker.take() {
+            let _ = ticker.join();
+        }
+    }
+}
+
+/// Updates the status area, if it is shown.
+fn update<F: FnOnce(&mut StatusArea)>(change: F) {
+    if let Some(ref mut area) = *AREA.lock().unwrap() {
+        change(area);
+        area.draw(&mut io::stdout().lock());
+    }
+}
+
+/// Shows a target as running in the status area.
+pub fn progress_started(target_name: &str) {
+    update(|area| {
+        area.targets.push(ProgressTarget {
+            name: target_name.to_string(),
+            started: Instant::now(),
+            status: ProgressStatus::Running,
+        })
+    });
+}
+
+/// Shows a running target as finished in the status area, skipped targets are
+/// removed from it.
+pub fn progress_finished(target_name: &str, result: Option<bool>) {
+    update(|area| {
+        let pos = area.targets.iter().position(|target| {
+            target.name == target_name && target.status == ProgressStatus::Running
+        });
+        if let Some(pos) = pos {
+            let elapsed = area.targets[pos].started.elapsed();
+            match result {
+                Some(true) => area.targets[pos].status = ProgressStatus::Succeeded(elapsed),
+                Some(false) => area.targets[pos].status = ProgressStatus::Failed(elapsed),
+                None => {
+                    area.targets.remove(pos);
+                }
+            }
+        }
+    });
+}
+
+/// Hides the status area while an interactive target has the terminal, or shows it
+/// again.
+pub fn pause_progress(paused: bool) {
+    update(|area| area.paused = paused);
+}
+
+/// Writes output above the status area, if it is shown: the area is removed, the
+/// output written and the area drawn again below it.
+pub fn write_above<F: FnOnce()>(write: F) {
+    let mut area = AREA.lock().unwrap();
+    match *area {
+        Some(ref mut area) if area.lines > 0 => {
+            area.erase(&mut io::stdout().lock());
+            write();
+            area.draw(&mut io::stdout().lock());
+        }
+        _ => write(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        colored::control::set_override(false);
+        let now = Instant::now();
+        let target = |name: &str, status: ProgressStatus| ProgressTarget {
+            name: name.to_string(),
+            started: now - Duration::from_millis(2500),
+            status,
+        };
+        let mut targets: Vec<ProgressTarget> = (1..=5)
+            .map(|i| {
+                target(
+                    &format!("lint.{}", i),
+                    ProgressStatus::Succeeded(Duration::from_millis(i * 100)),
+                )
+            })
+            .collect();
+        targets.insert(0, target("build", ProgressStatus::Running));
+        targets.push(target(
+            "test",
+            ProgressStatus::Failed(Duration::from_secs(3)),
+        ));
+        targets.push(target(&"x".repeat(60), ProgressStatus::Running));
+
+        let lines = render(&targets, 1, now);
+        assert_eq!(
+            lines,
+            vec![
+                "✓ lint.3 0.3s".to_string(),
+                "✓ lint.4 0.4s".to_string(),
+                "✓ lint.5 0.5s".to_string(),
+                "✗ test 3.0s".to_string(),
+                "⠙ build 2.5s".to_string(),
+                format!("⠙ {}… 2.5s", "x".repeat(NAME_WIDTH - 1)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::{self, BufRead, Write};
+
+use colored::Colorize;
+
+/// Asks the user for the value of an env variable required by targets. An empty line
+/// takes the default, if there is one. The input of secrets is not shown. The prompt
+/// is written to stderr, returns `None` at the end of the input or for an empty line
+/// without a default.
+pub fn prompt_env_var(
+    name: &str,
+    targets: &str,
+    default: Option<&str>,
+    secret: bool,
+) -> io::Result<Option<String>> {
+    let _hidden = if secret { HiddenInput::new() } else { None };
+    let value = read_env_var(name, targets, default, io::stdin().lock(), io::stderr());
+    if secret {
+        eprintln!();
+    }
+    value
+}
+
+/// Prompts for an env variable and reads it's value from a line of the input.
+fn read_env_var<R: BufRead, W: Write>(
+    name: &str,
+    targets: &str,
+    default: Option<&str>,
+    mut input: R,
+    mut output: W,
+) -> io::Result<Option<String>> {
+    let default_hint = match default {
+        Some(default) => format!(" [{}]", default),
+        None => String::new(),
+    };
+    write!(
+        output,
+        "{} {} {}{}: ",
+        "Value of".bold().blue(),
+        name.bold().green(),
+        format!("(required by {})", targets).bold().blue(),
+        default_hint
+    )?;
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let value = line.trim_end_matches(['\r', '\n']);
+    if value.is_empty() {
+        return Ok(default.map(|default| default.to_string()));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// Turns off the echo of the terminal while it lives, for reading secrets.
+#[cfg(unix)]
+struct HiddenInput(libc::termios);
+
+#[cfg(unix)]
+impl HiddenInput {
+    /// Turns off the echo, `None` if stdin is no terminal.
+    fn new() -> Option<HiddenInput> {
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return None;
+            }
+            let mut hidden = termios;
+            hidden.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden);
+            Some(HiddenInput(termios))
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for HiddenInput {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct HiddenInput;
+
+#[cfg(not(unix))]
+impl HiddenInput {
+    fn new() -> Option<HiddenInput> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_with(input: &str, default: Option<&str>) -> Option<String> {
+        read_env_var("TOKEN", "deploy", default, input.as_bytes(), io::sink()).unwrap()
+    }
+
+    #[test]
+    fn test_read_env_var() {
+        assert_eq!(read_with("abc\n", None), Some("abc".to_string()));
+        assert_eq!(read_with("a b \r\n", None), Some("a b ".to_string()));
+        assert_eq!(read_with("\n", Some("dev")), Some("dev".to_string()));
+        assert_eq!(read_with("prod\n", Some("dev")), Some("prod".to_string()));
+        assert_eq!(read_with("\n", None), None);
+        assert_eq!(read_with("", Some("dev")), None);
+    }
+}
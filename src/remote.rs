@@ -0,0 +1,236 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+/// Directory caching the Yakefiles of remote includes, relative to the Yakefile.
+pub const REMOTE_INCLUDES_DIR: &str = ".yake/includes";
+
+/// Directory within `REMOTE_INCLUDES_DIR` recording the digest of the last downloaded
+/// content of each URL, for includes which are not pinned.
+const URLS_DIR: &str = "urls";
+
+/// Checks whether an include is a URL rather than a path.
+pub fn is_remote_include(include: &str) -> bool {
+    include.starts_with("https://") || include.starts_with("http://")
+}
+
+/// Splits a remote include into it's URL and the sha256 digest it is pinned to, if
+/// it is pinned.
+pub fn split_pin(include: &str) -> (&str, Option<String>) {
+    match include.split_once("#sha256=") {
+        Some((url, digest)) => (url, Some(digest.to_lowercase())),
+        None => (include, None),
+    }
+}
+
+/// Gets the Yakefile of a remote include, e.g.
+/// `https://example.com/ci/Yakefile#sha256=<digest>`, along with the sha256 digest of
+/// it's content. It is downloaded with curl into the cache within `base_dir` unless
+/// it is cached already or `update` is given.
+///
+/// The cache stores the content keyed by it's digest and is checked against it, so
+/// tampered content is downloaded again. The optional digest pins the content,
+/// downloaded content which does not match it fails. Includes which are not pinned
+/// use the content downloaded last. The cached Yakefile is named after the URL so
+/// that it's targets are added to a group named after the directory of the URL, `ci`
+/// for the example.
+pub fn fetch_remote_include(
+    base_dir: &Path,
+    include: &str,
+    update: bool,
+) -> Result<(PathBuf, String), String> {
+    let (url, pin) = split_pin(include);
+    if !url.starts_with("https://") {
+        return Err(format!("Remote include {} must use https", url));
+    }
+
+    let mut segments: Vec<&str> = url["https://".len()..]
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let file = match segments.len() {
+        0 | 1 => "Yakefile",
+        _ => segments.pop().unwrap(),
+    };
+    let dir = segments.last().cloned().unwrap_or("remote");
+    let cache_dir = base_dir.join(REMOTE_INCLUDES_DIR);
+    let cached_path = |digest: &str| cache_dir.join(digest).join(dir).join(file);
+    let index = cache_dir.join(URLS_DIR).join(&sha256(url.as_bytes())[..16]);
+
+    if !update {
+        let digest = match pin {
+            Some(ref pin) => Some(pin.clone()),
+            None => fs::read_to_string(&index)
+                .ok()
+                .map(|digest| digest.trim().to_string()),
+        };
+        if let Some(digest) = digest {
+            let path = cached_path(&digest);
+            if fs::read(&path).is_ok_and(|content| sha256(&content) == digest) {
+                return Ok((path, digest));
+            }
+        }
+    }
+
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--proto", "=https", url])
+        .output()
+        .map_err(|e| format!("Unable to run curl for {}: {}", url, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to download {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let digest = verify(url, &output.stdout, pin.as_deref())?;
+
+    let error = |e: std::io::Error| format!("Unable to cache {}: {}", url, e);
+    let path = cached_path(&digest);
+    fs::create_dir_all(path.parent().unwrap()).map_err(error)?;
+    fs::write(&path, &output.stdout).map_err(error)?;
+    fs::create_dir_all(index.parent().unwrap()).map_err(error)?;
+    fs::write(&index, &digest).map_err(error)?;
+    Ok((path, digest))
+}
+
+/// The remote ones of the given includes, pinned to the digests of their content. With
+/// `update` they are downloaded again regardless of their pins, for pinning them to
+/// their current content.
+pub fn pin_remote_includes(
+    base_dir: &Path,
+    includes: &[String],
+    update: bool,
+) -> Result<Vec<String>, String> {
+    includes
+        .iter()
+        .filter(|include| is_remote_include(include))
+        .map(|include| {
+            let include = if update {
+                split_pin(include).0
+            } else {
+                include
+            };
+            let (_, digest) = fetch_remote_include(base_dir, include, update)?;
+            Ok(format!("{}#sha256={}", split_pin(include).0, digest))
+        })
+        .collect()
+}
+
+/// Checks content against it's pinned digest, if it has one. The digest of the
+/// content is returned.
+fn verify(url: &str, content: &[u8], pin: Option<&str>) -> Result<String, String> {
+    let digest = sha256(content);
+    match pin {
+        Some(pin) if pin != digest => Err(format!(
+            "Checksum mismatch of {}: expected sha256 {}, got {}",
+            url, pin, digest
+        )),
+        _ => Ok(digest),
+    }
+}
+
+/// Hex encoded sha256 digest.
+fn sha256(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_fetch_remote_include_cached() {
+        let dir = env::temp_dir().join(format!("yake-remote-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let url = "https://yake.invalid/shared/ci/Yakefile";
+        let digest = sha256(b"content");
+        let cached = dir
+            .join(REMOTE_INCLUDES_DIR)
+            .join(&digest)
+            .join("ci/Yakefile");
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, "content").unwrap();
+
+        // the content is found by it's pin
+        let pinned = format!("{}#sha256={}", url, digest.to_uppercase());
+        assert_eq!(
+            fetch_remote_include(&dir, &pinned, false),
+            Ok((cached.clone(), digest.clone()))
+        );
+        assert_eq!(
+            pin_remote_includes(&dir, &[pinned], false),
+            Ok(vec![format!("{}#sha256={}", url, digest)])
+        );
+        // and by the digest recorded for the URL if it is not pinned
+        assert!(fetch_remote_include(&dir, url, false).is_err());
+        let index = dir
+            .join(REMOTE_INCLUDES_DIR)
+            .join(URLS_DIR)
+            .join(&sha256(url.as_bytes())[..16]);
+        fs::create_dir_all(index.parent().unwrap()).unwrap();
+        fs::write(&index, &digest).unwrap();
+        assert_eq!(
+            fetch_remote_include(&dir, url, false),
+            Ok((cached.clone(), digest.clone()))
+        );
+        assert_eq!(
+            pin_remote_includes(&dir, &[url.to_string(), "ci/Yakefile".to_string()], false),
+            Ok(vec![format!("{}#sha256={}", url, digest)])
+        );
+
+        // a cached include not matching the pin is downloaded again
+        let pinned = format!("{}#sha256={}", url, sha256(b"other"));
+        let error = fetch_remote_include(&dir, &pinned, false).unwrap_err();
+        assert!(
+            error.starts_with("Unable to download https://yake.invalid/"),
+            "{}",
+            error
+        );
+        assert!(fetch_remote_include(&dir, url, true).is_err());
+        assert!(pin_remote_includes(&dir, &[url.to_string()], true).is_err());
+
+        // so is tampered content, pinned or not
+        fs::write(&cached, "tampered").unwrap();
+        assert!(fetch_remote_include(&dir, url, false).is_err());
+        let pinned = format!("{}#sha256={}", url, digest);
+        assert!(fetch_remote_include(&dir, &pinned, false).is_err());
+
+        assert_eq!(verify(url, b"content", Some(&digest)), Ok(digest.clone()));
+        assert_eq!(verify(url, b"content", None), Ok(digest.clone()));
+        assert_eq!(
+            verify(url, b"other", Some(&digest)),
+            Err(format!(
+                "Checksum mismatch of {}: expected sha256 {}, got {}",
+                url,
+                digest,
+                sha256(b"other")
+            ))
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_remote_include_http() {
+        assert!(is_remote_include("http://example.com/Yakefile"));
+        assert!(!is_remote_include("services/api/Yakefile"));
+        assert_eq!(
+            fetch_remote_include(Path::new("."), "http://example.com/Yakefile", false),
+            Err("Remote include http://example.com/Yakefile must use https".to_string())
+        );
+        assert_eq!(
+            split_pin("https://example.com/Yakefile#sha256=AB12"),
+            ("https://example.com/Yakefile", Some("ab12".to_string()))
+        );
+    }
+}
@@ -0,0 +1,630 @@
+use std::collections::VecDeque;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use colored::{Color, Colorize};
+
+use progress::write_above;
+
+/// Maximum number of bytes of output kept for a silent target.
+pub const SILENT_OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// Replaces the values of secrets in the output.
+pub const SECRET_MASK: &str = "***";
+
+/// Colors of the tags of targets running concurrently, assigned in turn. Red is kept
+/// for errors.
+const TAG_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Blue,
+    Color::BrightCyan,
+    Color::BrightMagenta,
+];
+
+/// The stream a piece of output is written to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Format of the output of yake.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    /// Colored text for humans
+    #[default]
+    Text,
+    /// A JSON document for machines
+    Json,
+}
+
+/// How much yake reports about what it does, besides the output of the commands.
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy)]
+pub enum Verbosity {
+    /// Only the output of the commands and failures
+    Quiet,
+    /// Which targets and commands run
+    #[default]
+    Normal,
+    /// Dependencies, working directories and shell invocations in addition
+    Verbose,
+    /// The env of each command in addition
+    Debug,
+}
+
+/// Writes messages of yake which are shown depending on the verbosity. Messages go to
+/// stderr with JSON output, stdout is kept for the JSON document then.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Logger {
+    pub verbosity: Verbosity,
+    pub format: OutputFormat,
+}
+
+impl Logger {
+    /// Checks whether messages of the level are shown.
+    pub fn is_enabled(&self, level: Verbosity) -> bool {
+        self.verbosity >= level
+    }
+
+    /// Writes a line if messages of the level are shown.
+    pub fn log(&self, level: Verbosity, text: &str) {
+        if !self.is_enabled(level) {
+            return;
+        }
+        let stream = match self.format {
+            OutputFormat::Text => Stream::Stdout,
+            OutputFormat::Json => Stream::Stderr,
+        };
+        write_stream(stream, &format!("{}\n", text));
+    }
+
+    /// Writes a warning to stderr, regardless of the verbosity.
+    pub fn warn(&self, text: &str) {
+        write_stream(
+            Stream::Stderr,
+            &format!("{} {}\n", "⚠".bold().yellow(), text),
+        );
+    }
+}
+
+/// Whether the output of yake is colored.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum ColorChoice {
+    /// Colored if stdout is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Colors the output of yake as chosen, for the environment yake is running in.
+    pub fn apply(self) {
+        let enabled = self.is_enabled(env::var_os("NO_COLOR"), io::stdout().is_terminal());
+        colored::control::set_override(enabled);
+    }
+
+    /// Checks whether output is colored, `NO_COLOR` only counts if it is not empty.
+    fn is_enabled(self, no_color: Option<OsString>, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => no_color.is_none_or(|v| v.is_empty()) && is_terminal,
+        }
+    }
+}
+
+/// Collapsible log sections of CI systems, each target's output is wrapped in one.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CiFolding {
+    /// `::group::` and `::endgroup::` workflow commands of GitHub Actions
+    Github,
+    /// `section_start` and `section_end` markers of GitLab CI
+    Gitlab,
+}
+
+impl CiFolding {
+    /// Detects the CI system yake is running in from it's environment variables.
+    pub fn detect() -> Option<CiFolding> {
+        let is_set = |name: &str| env::var(name).map(|v| v == "true").unwrap_or(false);
+        if is_set("GITHUB_ACTIONS") {
+            Some(CiFolding::Github)
+        } else if is_set("GITLAB_CI") {
+            Some(CiFolding::Gitlab)
+        } else {
+            None
+        }
+    }
+
+    /// Marker starting the section of a target.
+    fn begin_marker(self, target_name: &str, timestamp: u64) -> String {
+        match self {
+            CiFolding::Github => format!("::group::{}\n", target_name),
+            CiFolding::Gitlab => format!(
+                "\x1b[0Ksection_start:{}:{}[collapsed=true]\r\x1b[0K{}\n",
+                timestamp,
+                section_name(target_name),
+                target_name
+            ),
+        }
+    }
+
+    /// Marker ending the section of a target. GitHub does not show durations for
+    /// groups, so it is written as the last line of the group.
+    fn end_marker(self, target_name: &str, timestamp: u64, elapsed: f64) -> String {
+        match self {
+            CiFolding::Github => format!(
+                "{} finished after {:.1}s\n::endgroup::\n",
+                target_name, elapsed
+            ),
+            CiFolding::Gitlab => format!(
+                "\x1b[0Ksection_end:{}:{}\r\x1b[0K\n",
+                timestamp,
+                section_name(target_name)
+            ),
+        }
+    }
+}
+
+/// GitLab section names may only contain letters, digits, `_`, `.` and `-`.
+fn section_name(target_name: &str) -> String {
+    target_name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Seconds since the unix epoch, as used by GitLab section markers.
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Writes text to stdout or stderr at once, above the status area if it is shown.
+fn write_stream(stream: Stream, text: &str) {
+    write_above(|| match stream {
+        Stream::Stdout => io::stdout()
+            .lock()
+            .write_all(text.as_bytes())
+            .expect("failed to write output to stdout"),
+        Stream::Stderr => io::stderr()
+            .lock()
+            .write_all(text.as_bytes())
+            .expect("failed to write output to stderr"),
+    })
+}
+
+/// Reports a target which is not run, along with the reason.
+pub fn report_skipped(logger: &Logger, target_name: &str, reason: &str) {
+    logger.log(
+        Verbosity::Normal,
+        &format!(
+            "{} {}",
+            format!("↪ {}", target_name).bold().blue(),
+            format!("skipped ({})", reason).yellow()
+        ),
+    );
+}
+
+/// Keeps the most recent output up to a limit of bytes, older output is dropped.
+#[derive(Debug)]
+pub struct OutputBuffer {
+    chunks: VecDeque<(Stream, String)>,
+    size: usize,
+    limit: usize,
+    truncated: usize,
+}
+
+impl OutputBuffer {
+    pub fn new(limit: usize) -> OutputBuffer {
+        OutputBuffer {
+            chunks: VecDeque::new(),
+            size: 0,
+            limit,
+            truncated: 0,
+        }
+    }
+
+    /// Appends output, dropping the oldest output if the limit is exceeded.
+    pub fn push(&mut self, stream: Stream, text: &str) {
+        self.size += text.len();
+        self.chunks.push_back((stream, text.to_string()));
+
+        while self.size > self.limit {
+            let excess = self.size - self.limit;
+            let (_, ref mut chunk) = *self.chunks.front_mut().unwrap();
+            if chunk.len() <= excess {
+                self.size -= chunk.len();
+                self.truncated += chunk.len();
+                self.chunks.pop_front();
+            } else {
+                let mut cut = excess;
+                while !chunk.is_char_boundary(cut) {
+                    cut += 1;
+                }
+                chunk.drain(..cut);
+                self.size -= cut;
+                self.truncated += cut;
+            }
+        }
+    }
+
+    /// Writes the kept output to the streams it was meant for, preceded by a notice
+    /// if output was dropped.
+    pub fn flush(&self) {
+        if self.truncated > 0 {
+            write_stream(
+                Stream::Stderr,
+                &format!(
+                    "{}\n",
+                    format!("… {} bytes of earlier output truncated", self.truncated).yellow()
+                ),
+            );
+        }
+        for (stream, text) in &self.chunks {
+            write_stream(*stream, text);
+        }
+    }
+}
+
+/// Tag of a target prefixing each line of it's output while it runs concurrently
+/// with other targets, e.g. `[build.api] `, padded to `width` names. The color is
+/// picked by the index of the target, so that targets running together differ.
+pub fn target_tag(target_name: &str, index: usize, width: usize) -> String {
+    let tag = format!("{:width$}", format!("[{}]", target_name), width = width + 2);
+    format!("{} ", tag.color(TAG_COLORS[index % TAG_COLORS.len()]))
+}
+
+/// Prefixes each line of a text with a tag.
+fn tag_lines(text: &str, tag: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| format!("{}{}", tag, line))
+        .collect()
+}
+
+/// Replaces the values of secrets within a text by `SECRET_MASK`. Secrets containing
+/// others have to come first.
+pub fn mask_secrets(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        text = text.replace(secret.as_str(), SECRET_MASK);
+    }
+    text
+}
+
+/// Reports the output of a single target. Output of silent targets is buffered and
+/// only written if the target fails. With CI folding the output is wrapped in a
+/// collapsible section, which is closed even if the target is not finished regularly.
+/// With JSON output the output is captured for the caller instead, nothing is written
+/// then. Output of targets running concurrently is tagged with the name of the
+/// target, see `target_tag`.
+pub struct TargetReporter {
+    target_name: String,
+    tag: Option<String>,
+    secrets: Vec<String>,
+    buffer: Option<Mutex<OutputBuffer>>,
+    captured: Option<Mutex<String>>,
+    folding: Option<CiFolding>,
+    verbosity: Verbosity,
+    started: Instant,
+    /// Serializes writing the output of held reporters
+    releasing: Mutex<()>,
+}
+
+impl TargetReporter {
+    pub fn new(
+        target_name: &str,
+        silent: bool,
+        folding: Option<CiFolding>,
+        logger: Logger,
+        secrets: Vec<String>,
+        tag: Option<String>,
+    ) -> TargetReporter {
+        let capture = logger.format == OutputFormat::Json;
+        let folding = if capture { None } else { folding };
+        if let Some(folding) = folding {
+            write_stream(
+                Stream::Stdout,
+                &folding.begin_marker(target_name, unix_timestamp()),
+            );
+        }
+
+        TargetReporter {
+            target_name: target_name.to_string(),
+            tag,
+            secrets,
+            buffer: if silent && !capture {
+                Some(Mutex::new(OutputBuffer::new(SILENT_OUTPUT_LIMIT)))
+            } else {
+                None
+            },
+            captured: if capture {
+                Some(Mutex::new(String::new()))
+            } else {
+                None
+            },
+            folding,
+            verbosity: logger.verbosity,
+            started: Instant::now(),
+            releasing: Mutex::new(()),
+        }
+    }
+
+    /// Reporter for a single command of the target, whose output is held back until
+    /// it is released, so that it does not interleave with the output of commands
+    /// running at the same time. It's output is tagged once it is released.
+    pub fn hold(&self) -> TargetReporter {
+        TargetReporter {
+            target_name: self.target_name.clone(),
+            tag: None,
+            secrets: self.secrets.clone(),
+            buffer: match self.captured {
+                Some(_) => None,
+                None => Some(Mutex::new(OutputBuffer::new(usize::MAX))),
+            },
+            captured: self.captured.as_ref().map(|_| Mutex::new(String::new())),
+            folding: None,
+            verbosity: self.verbosity,
+            started: Instant::now(),
+            releasing: Mutex::new(()),
+        }
+    }
+
+    /// Writes all output held back by a reporter of `hold` at once.
+    pub fn release(&self, mut held: TargetReporter) {
+        let _releasing = self.releasing.lock().unwrap();
+        if let (Some(captured), Some(held)) = (&self.captured, held.captured.take()) {
+            captured
+                .lock()
+                .unwrap()
+                .push_str(&held.into_inner().unwrap());
+        }
+        if let Some(buffer) = held.buffer.take() {
+            for (stream, text) in buffer.into_inner().unwrap().chunks {
+                self.write(stream, &text);
+            }
+        }
+    }
+
+    /// Verbosity of the messages about the target.
+    pub fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    /// Writes a line about the target if messages of the level are shown.
+    pub fn message(&self, level: Verbosity, text: &str) {
+        if self.verbosity >= level {
+            let text = mask_secrets(text, &self.secrets);
+            self.write(Stream::Stdout, &format!("{}\n", text));
+        }
+    }
+
+    /// Writes output of the target, or buffers it for silent targets. Nothing is
+    /// written while the output is captured.
+    pub fn write(&self, stream: Stream, text: &str) {
+        if self.captured.is_some() {
+            return;
+        }
+        let tagged;
+        let text = match self.tag {
+            Some(ref tag) => {
+                tagged = tag_lines(text, tag);
+                &tagged
+            }
+            None => text,
+        };
+        match self.buffer {
+            Some(ref buffer) => buffer.lock().unwrap().push(stream, text),
+            None => write_stream(stream, text),
+        }
+    }
+
+    /// Writes a line of output of a command as formatted for the terminal, or keeps
+    /// the line as it is while the output is captured.
+    pub fn write_line(&self, stream: Stream, line: &str, formatted: &str) {
+        match self.captured {
+            Some(ref captured) => captured
+                .lock()
+                .unwrap()
+                .push_str(&mask_secrets(line, &self.secrets)),
+            None => self.write(stream, &mask_secrets(formatted, &self.secrets)),
+        }
+    }
+
+    /// Reports the end of the target. Silent targets print a single line on success,
+    /// unless quiet, and all of their buffered output on failure. Returns the captured
+    /// output.
+    pub fn finish<E>(mut self, result: &Result<(), E>) -> String {
+        if let Some(captured) = self.captured.take() {
+            return captured.into_inner().unwrap();
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer.into_inner().unwrap(),
+            None => {
+                if result.is_ok() && self.verbosity >= Verbosity::Normal {
+                    self.write(Stream::Stdout, &format!("{}\n", "↪ Done".bold().blue()));
+                }
+                return String::new();
+            }
+        };
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if result.is_ok() && self.verbosity >= Verbosity::Normal {
+            write_stream(
+                Stream::Stdout,
+                &format!(
+                    "{} {} ({:.1}s)\n",
+                    format!("↪ {}", self.target_name).bold().blue(),
+                    "✓".bold().green(),
+                    elapsed
+                ),
+            );
+        } else if result.is_err() {
+            buffer.flush();
+            write_stream(
+                Stream::Stdout,
+                &format!(
+                    "{} {} ({:.1}s)\n",
+                    format!("↪ {}", self.target_name).bold().blue(),
+                    "✗".bold().red(),
+                    elapsed
+                ),
+            );
+        }
+        String::new()
+    }
+
+    /// Closes the CI folding section of the target.
+    fn close_section(&mut self) {
+        if let Some(folding) = self.folding.take() {
+            let elapsed = self.started.elapsed().as_secs_f64();
+            write_stream(
+                Stream::Stdout,
+                &folding.end_marker(&self.target_name, unix_timestamp(), elapsed),
+            );
+        }
+    }
+}
+
+impl Drop for TargetReporter {
+    fn drop(&mut self) {
+        self.close_section();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_markers() {
+        assert_eq!(
+            CiFolding::Github.begin_marker("deps.install", 1700000000),
+            "::group::deps.install\n"
+        );
+        assert_eq!(
+            CiFolding::Github.end_marker("deps.install", 1700000003, 3.21),
+            "deps.install finished after 3.2s\n::endgroup::\n"
+        );
+    }
+
+    #[test]
+    fn test_gitlab_markers() {
+        assert_eq!(
+            CiFolding::Gitlab.begin_marker("deps.install:all", 1700000000),
+            "\x1b[0Ksection_start:1700000000:deps.install_all[collapsed=true]\r\x1b[0Kdeps.install:all\n"
+        );
+        assert_eq!(
+            CiFolding::Gitlab.end_marker("deps.install:all", 1700000003, 3.21),
+            "\x1b[0Ksection_end:1700000003:deps.install_all\r\x1b[0K\n"
+        );
+    }
+
+    fn get_output(buffer: &OutputBuffer, stream: Stream) -> String {
+        buffer
+            .chunks
+            .iter()
+            .filter(|&&(s, _)| s == stream)
+            .map(|(_, text)| text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_output_buffer() {
+        let mut buffer = OutputBuffer::new(100);
+        buffer.push(Stream::Stdout, "out\n");
+        buffer.push(Stream::Stderr, "err\n");
+        buffer.push(Stream::Stdout, "more out\n");
+
+        assert_eq!(get_output(&buffer, Stream::Stdout), "out\nmore out\n");
+        assert_eq!(get_output(&buffer, Stream::Stderr), "err\n");
+        assert_eq!(buffer.truncated, 0);
+    }
+
+    #[test]
+    fn test_output_buffer_truncates_oldest() {
+        let mut buffer = OutputBuffer::new(10);
+        buffer.push(Stream::Stdout, "12345\n");
+        buffer.push(Stream::Stderr, "abc\n");
+        buffer.push(Stream::Stdout, "xyz\n");
+
+        assert_eq!(get_output(&buffer, Stream::Stdout), "5\nxyz\n");
+        assert_eq!(get_output(&buffer, Stream::Stderr), "abc\n");
+        assert_eq!(buffer.truncated, 4);
+
+        buffer.push(Stream::Stdout, "0123456789ab");
+        assert_eq!(get_output(&buffer, Stream::Stdout), "23456789ab");
+        assert_eq!(get_output(&buffer, Stream::Stderr), "");
+        assert_eq!(buffer.truncated, 16);
+    }
+
+    #[test]
+    fn test_output_buffer_keeps_char_boundaries() {
+        let mut buffer = OutputBuffer::new(3);
+        buffer.push(Stream::Stdout, "┆x");
+
+        assert_eq!(get_output(&buffer, Stream::Stdout), "x");
+        assert_eq!(buffer.truncated, 3);
+    }
+
+    #[test]
+    fn test_captured_output() {
+        let logger = Logger {
+            verbosity: Verbosity::Normal,
+            format: OutputFormat::Json,
+        };
+        let reporter = TargetReporter::new(
+            "build",
+            true,
+            Some(CiFolding::Github),
+            logger,
+            Vec::new(),
+            None,
+        );
+        reporter.write(Stream::Stdout, "↪ Executing make:\n");
+        reporter.write_line(Stream::Stdout, "compiling\n", "┆  compiling\n");
+        reporter.write_line(Stream::Stderr, "warning", "┆  warning\n");
+
+        assert_eq!(reporter.finish::<()>(&Ok(())), "compiling\nwarning");
+    }
+
+    #[test]
+    fn test_color_choice() {
+        let set = || Some(OsString::from("1"));
+        assert!(ColorChoice::Auto.is_enabled(None, true));
+        assert!(ColorChoice::Auto.is_enabled(Some(OsString::new()), true));
+        assert!(!ColorChoice::Auto.is_enabled(set(), true));
+        assert!(!ColorChoice::Auto.is_enabled(None, false));
+        assert!(ColorChoice::Always.is_enabled(set(), false));
+        assert!(!ColorChoice::Never.is_enabled(None, true));
+    }
+
+    #[test]
+    fn test_mask_secrets() {
+        let secrets = vec!["token-2".to_string(), "token".to_string()];
+        assert_eq!(
+            mask_secrets("token-2 and token, not tok", &secrets),
+            "*** and ***, not tok"
+        );
+        assert_eq!(mask_secrets("nothing", &[String::new()]), "nothing");
+    }
+
+    #[test]
+    fn test_tag_lines() {
+        assert_eq!(
+            tag_lines("↪ Executing make:\n┆  compiling\n", "[build] "),
+            "[build] ↪ Executing make:\n[build] ┆  compiling\n"
+        );
+        assert_eq!(tag_lines("partial", "[build] "), "[build] partial");
+    }
+}
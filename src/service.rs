@@ -0,0 +1,116 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signals::{is_process_group_running, kill_process_group, terminate_process_group};
+
+/// Directory keeping the pid and log files of services, relative to the Yakefile.
+pub const SERVICES_DIR: &str = ".yake/services";
+
+/// Time a service gets to end after it was asked to, before it is killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval in which a stopping service is checked for having ended.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// File the pid of a running service is kept in.
+fn pid_file(base_dir: &Path, name: &str) -> PathBuf {
+    base_dir.join(SERVICES_DIR).join(format!("{}.pid", name))
+}
+
+/// File the output of a service is written to.
+pub fn log_file(base_dir: &Path, name: &str) -> PathBuf {
+    base_dir.join(SERVICES_DIR).join(format!("{}.log", name))
+}
+
+/// Opens the log file of a service for appending, creating it if needed.
+pub fn open_log_file(base_dir: &Path, name: &str) -> Result<File, String> {
+    let path = log_file(base_dir, name);
+    let error = |e: std::io::Error| format!("Unable to open {}: {}", path.display(), e);
+    fs::create_dir_all(path.parent().unwrap()).map_err(error)?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(error)
+}
+
+/// Records the pid of a started service.
+pub fn write_pid(base_dir: &Path, name: &str, pid: u32) -> Result<(), String> {
+    let path = pid_file(base_dir, name);
+    fs::write(&path, format!("{}\n", pid))
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+/// Pid of a service if it is running. The pid files of services which ended on their
+/// own are removed.
+pub fn running_pid(base_dir: &Path, name: &str) -> Option<u32> {
+    let path = pid_file(base_dir, name);
+    let pid = fs::read_to_string(&path).ok()?.trim().parse().ok();
+    match pid {
+        Some(pid) if is_process_group_running(pid) => Some(pid),
+        _ => {
+            let _ = fs::remove_file(&path);
+            None
+        }
+    }
+}
+
+/// Stops a service along with all processes it started. They are asked to terminate
+/// first and killed if they did not end within `STOP_TIMEOUT`. Returns the pid of the
+/// service, `None` if it was not running.
+pub fn stop_service(base_dir: &Path, name: &str) -> Result<Option<u32>, String> {
+    let pid = match running_pid(base_dir, name) {
+        Some(pid) => pid,
+        None => return Ok(None),
+    };
+    terminate_process_group(pid);
+    let started = Instant::now();
+    while is_process_group_running(pid) {
+        if started.elapsed() >= STOP_TIMEOUT {
+            kill_process_group(pid);
+            break;
+        }
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+    let path = pid_file(base_dir, name);
+    fs::remove_file(&path).map_err(|e| format!("Unable to remove {}: {}", path.display(), e))?;
+    Ok(Some(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process::Command;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_service() {
+        use std::os::unix::process::CommandExt;
+
+        let dir = env::temp_dir().join(format!("yake-service-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        open_log_file(&dir, "sleeper").unwrap();
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & wait"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        // services are not children of yake, they must not linger as zombies here
+        let waiter = thread::spawn(move || child.wait());
+        write_pid(&dir, "sleeper", pid).unwrap();
+
+        assert_eq!(running_pid(&dir, "sleeper"), Some(pid));
+        let started = Instant::now();
+        assert_eq!(stop_service(&dir, "sleeper").unwrap(), Some(pid));
+        assert!(started.elapsed() < STOP_TIMEOUT);
+        waiter.join().unwrap().unwrap();
+        assert_eq!(running_pid(&dir, "sleeper"), None);
+        assert_eq!(stop_service(&dir, "sleeper").unwrap(), None);
+        assert!(!pid_file(&dir, "sleeper").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
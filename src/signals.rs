@@ -0,0 +1,262 @@
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Number of interrupt and terminate signals yake received.
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Process groups of the running commands, signals yake receives are forwarded to
+/// them.
+static PROCESS_GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Write end of the pipe the signal handler passes the signals it receives on through.
+#[cfg(unix)]
+static SIGNAL_PIPE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(-1);
+
+/// Handles interrupt and terminate signals instead of ending yake right away. They are
+/// forwarded to the process groups of the running commands, so that yake can wait for
+/// them to end and still run the cleanup hooks. Only installs the handlers once.
+#[cfg(unix)]
+pub fn handle_interrupts() {
+    use std::sync::Once;
+    use std::thread;
+
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        let mut fds = [0; 2];
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return;
+        }
+        SIGNAL_PIPE.store(fds[1], Ordering::SeqCst);
+        let read_end = fds[0];
+        thread::spawn(move || forward_signals(read_end));
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    });
+}
+
+#[cfg(not(unix))]
+pub fn handle_interrupts() {}
+
+/// Counts a signal and passes it on to `forward_signals`, the only things a signal
+/// handler can safely do.
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    INTERRUPTS.fetch_add(1, Ordering::SeqCst);
+    let byte = signal as u8;
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::SeqCst),
+            &byte as *const u8 as *const libc::c_void,
+            1,
+        );
+    }
+}
+
+/// Forwards the signals read from the pipe to the process groups of the running
+/// commands.
+#[cfg(unix)]
+fn forward_signals(pipe: libc::c_int) {
+    use std::io;
+
+    let mut byte = 0u8;
+    loop {
+        let read = unsafe { libc::read(pipe, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if read == 1 {
+            for &group in PROCESS_GROUPS.lock().unwrap().iter() {
+                signal_process_group(group, libc::c_int::from(byte));
+            }
+        } else if read < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+        } else {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn signal_process_group(child_id: u32, signal: libc::c_int) {
+    unsafe {
+        libc::kill(-(child_id as libc::pid_t), signal);
+    }
+}
+
+/// Number of interrupts yake received so far, see `handle_interrupts`.
+pub fn interrupts() -> usize {
+    INTERRUPTS.load(Ordering::SeqCst)
+}
+
+/// Checks whether yake received an interrupt.
+pub fn interrupted() -> bool {
+    interrupts() > 0
+}
+
+/// Forwards interrupts to the process group of a running command for as long as it
+/// lives, see `new_process_group`.
+pub struct ForwardInterrupts(u32);
+
+impl ForwardInterrupts {
+    /// Starts forwarding to the process group of a child. Commands started right when
+    /// yake was interrupted are interrupted as well, `interrupts` is the number of
+    /// interrupts before the child was started.
+    pub fn new(child_id: u32, interrupts: usize) -> ForwardInterrupts {
+        PROCESS_GROUPS.lock().unwrap().push(child_id);
+        #[cfg(unix)]
+        {
+            if self::interrupts() > interrupts {
+                signal_process_group(child_id, libc::SIGINT);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = interrupts;
+        ForwardInterrupts(child_id)
+    }
+}
+
+impl Drop for ForwardInterrupts {
+    fn drop(&mut self) {
+        PROCESS_GROUPS
+            .lock()
+            .unwrap()
+            .retain(|&group| group != self.0);
+    }
+}
+
+/// Ignores interrupt and quit signals in yake for as long as it lives, so that they are
+/// only handled by the child process in the foreground. The previous handlers are
+/// restored on drop.
+#[cfg(unix)]
+pub struct InterruptGuard {
+    sigint: libc::sighandler_t,
+    sigquit: libc::sighandler_t,
+}
+
+#[cfg(unix)]
+impl InterruptGuard {
+    pub fn new() -> InterruptGuard {
+        unsafe {
+            InterruptGuard {
+                sigint: libc::signal(libc::SIGINT, libc::SIG_IGN),
+                sigquit: libc::signal(libc::SIGQUIT, libc::SIG_IGN),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::signal(libc::SIGINT, self.sigint);
+            libc::signal(libc::SIGQUIT, self.sigquit);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub struct InterruptGuard;
+
+#[cfg(not(unix))]
+impl InterruptGuard {
+    pub fn new() -> InterruptGuard {
+        InterruptGuard
+    }
+}
+
+/// Restores the default interrupt and quit handlers in the child process. Ignored
+/// signals would otherwise be inherited from yake.
+#[cfg(unix)]
+pub fn reset_interrupts(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    unsafe {
+        command.pre_exec(|| {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn reset_interrupts(_command: &mut Command) {}
+
+/// Starts the child process in a new process group, so that it can be killed or
+/// interrupted along with all processes it started.
+#[cfg(unix)]
+pub fn new_process_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(0);
+}
+
+#[cfg(not(unix))]
+pub fn new_process_group(_command: &mut Command) {}
+
+/// Kills the process group of a child started by `new_process_group`.
+#[cfg(unix)]
+pub fn kill_process_group(child_id: u32) {
+    signal_process_group(child_id, libc::SIGKILL);
+}
+
+/// Kills the child process along with all processes it started.
+#[cfg(not(unix))]
+pub fn kill_process_group(child_id: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &child_id.to_string()])
+        .output();
+}
+
+/// Checks whether any process of the process group of a child started by
+/// `new_process_group` is still running.
+#[cfg(unix)]
+pub fn is_process_group_running(child_id: u32) -> bool {
+    unsafe { libc::kill(-(child_id as libc::pid_t), 0) == 0 }
+}
+
+/// Checks whether the child process is still running.
+#[cfg(not(unix))]
+pub fn is_process_group_running(child_id: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", child_id), "/NH"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&child_id.to_string()))
+        .unwrap_or(false)
+}
+
+/// Asks the process group of a child started by `new_process_group` to terminate.
+#[cfg(unix)]
+pub fn terminate_process_group(child_id: u32) {
+    signal_process_group(child_id, libc::SIGTERM);
+}
+
+/// Asks the child process along with all processes it started to terminate.
+#[cfg(not(unix))]
+pub fn terminate_process_group(child_id: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/T", "/PID", &child_id.to_string()])
+        .output();
+}
+
+/// Exit code of a failed process the way shells report it, `128 + n` for processes
+/// terminated by signal `n`. Never 0, even if a process exited with 0 unexpectedly.
+#[cfg(unix)]
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    match (status.code(), status.signal()) {
+        (Some(0), _) => 1,
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    match status.code() {
+        Some(0) | None => 1,
+        Some(code) => code,
+    }
+}
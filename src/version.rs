@@ -0,0 +1,61 @@
+/// Version of yake, which Yakefiles may require as a minimum in `meta.version`.
+pub const YAKE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Checks that yake is at least the version a Yakefile requires. Versions are given as
+/// `major.minor.patch`, missing parts count as 0, e.g. `1.2` requires `1.2.0`.
+pub fn check_version(required: &str, current: &str) -> Result<(), String> {
+    let required_version = parse_version(required)?;
+    if parse_version(current)? < required_version {
+        return Err(format!(
+            "Yakefile requires yake {} or newer, this is yake {}",
+            required, current
+        ));
+    }
+    Ok(())
+}
+
+/// Parses a version into it's major, minor and patch number.
+fn parse_version(text: &str) -> Result<[u64; 3], String> {
+    let invalid = || format!("Invalid version \"{}\", expected major.minor.patch", text);
+    let parts: Vec<&str> = text.trim().split('.').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let mut version = [0; 3];
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        version[i] = part.parse().map_err(|_| invalid())?;
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_version() {
+        assert!(check_version("0.1.0", "0.1.0").is_ok());
+        assert!(check_version("0.1", "0.1.3").is_ok());
+        assert!(check_version("1.2.3", "1.10.0").is_ok());
+        assert_eq!(
+            check_version("1.0.0", "0.9.12").unwrap_err(),
+            "Yakefile requires yake 1.0.0 or newer, this is yake 0.9.12"
+        );
+        assert!(check_version("0.2", "0.1.9").is_err());
+    }
+
+    #[test]
+    fn test_parse_version_invalid() {
+        assert_eq!(
+            parse_version("latest").unwrap_err(),
+            "Invalid version \"latest\", expected major.minor.patch"
+        );
+        assert!(parse_version("1.2.3.4").is_err());
+        assert!(parse_version("1..2").is_err());
+        assert!(parse_version("^1.2").is_err());
+        assert!(parse_version("").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use colored::Colorize;
+use walkdir::{DirEntry, WalkDir};
+
+use error::{YakeError, INTERRUPT_EXIT_CODE};
+use gitignore::GitIgnore;
+use incremental::expand_patterns;
+use reporter::Verbosity;
+use signals::interrupted;
+use yake::{ExecutionOptions, Yake};
+
+/// Interval in which the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time without further changes before a target is run again, so that a burst of
+/// changes (e.g. a `git checkout`) only runs it once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Modification times of the watched files.
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Runs targets and runs them again whenever one of the files they depend on changes.
+/// Watched are the `inputs` of the targets, or all files next to and below the
+/// Yakefile which git does not ignore if any of them has none. Inputs of targets of
+/// sub yakes are relative to their Yakefile. Failures are reported, but do not end
+/// the watch.
+/// Only returns if the watched files can not be determined or yake is interrupted.
+pub fn watch_target(
+    yake: &Yake,
+    target_names: &[String],
+    options: &ExecutionOptions,
+    clear: bool,
+) -> Result<(), YakeError> {
+    let base_dir = yake.get_base_dir();
+    let mut inputs = Some(Vec::new());
+    for target_name in target_names {
+        yake.has_target_name(target_name)?;
+        let target = yake.get_target_by_name(target_name).unwrap();
+        let target_dir = yake.get_target_base_dir(target_name);
+        let target_dir = target_dir.strip_prefix(&base_dir).unwrap_or(&target_dir);
+        inputs = match (inputs, target.inputs) {
+            (Some(mut all), Some(target_inputs)) => {
+                all.extend(
+                    target_inputs
+                        .iter()
+                        .map(|input| target_dir.join(input).to_string_lossy().to_string()),
+                );
+                Some(all)
+            }
+            _ => None,
+        };
+    }
+
+    let take = || take_snapshot(&base_dir, inputs.as_deref()).map_err(YakeError::Invalid);
+    loop {
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        match yake.execute_targets(target_names, options) {
+            Err(e) if interrupted() => return Err(e),
+            Err(e) => eprintln!(
+                "Execution of target: {} failed. {}",
+                target_names.join(" "),
+                e
+            ),
+            Ok(()) => {}
+        }
+
+        // files written by the run itself do not count as changes
+        let snapshot = take()?;
+        options.logger().log(
+            Verbosity::Normal,
+            &format!(
+                "{} {} files for changes",
+                "↪ Watching".bold().blue(),
+                snapshot.len()
+            ),
+        );
+        wait_for_changes(&snapshot, take)?;
+    }
+}
+
+/// Polls for changes of the watched files and returns once they changed and did not
+/// change for the debounce time. Returns the files as of then, fails once yake is
+/// interrupted.
+fn wait_for_changes<F>(snapshot: &Snapshot, take: F) -> Result<Snapshot, YakeError>
+where
+    F: Fn() -> Result<Snapshot, YakeError>,
+{
+    let mut current = take()?;
+    while current == *snapshot {
+        if interrupted() {
+            return Err(YakeError::Failed {
+                message: "Interrupted".to_string(),
+                exit_code: INTERRUPT_EXIT_CODE,
+            });
+        }
+        thread::sleep(POLL_INTERVAL);
+        current = take()?;
+    }
+    loop {
+        thread::sleep(DEBOUNCE);
+        let next = take()?;
+        if next == current {
+            return Ok(next);
+        }
+        current = next;
+    }
+}
+
+/// Takes the modification times of the files matching the input patterns, or of all
+/// files below `base_dir` if there are none. Hidden files and directories (e.g.
+/// `.git`) and the ones git ignores (e.g. build output like `target`) are not
+/// watched.
+fn take_snapshot(base_dir: &Path, inputs: Option<&[String]>) -> Result<Snapshot, String> {
+    let files = match inputs {
+        Some(inputs) => expand_patterns(base_dir, inputs)?,
+        None => {
+            let mut ignore = GitIgnore::new(base_dir);
+            let mut is_watched = |entry: &DirEntry| {
+                if entry.depth() == 0 {
+                    return true;
+                }
+                let is_dir = entry.file_type().is_dir();
+                if is_hidden(entry) || ignore.is_ignored(entry.path(), is_dir) {
+                    return false;
+                }
+                if is_dir {
+                    ignore.add_dir(entry.path());
+                }
+                true
+            };
+            WalkDir::new(base_dir)
+                .into_iter()
+                .filter_entry(&mut is_watched)
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .collect()
+        }
+    };
+
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file).and_then(|m| m.modified()).ok();
+            (file, modified)
+        })
+        .collect())
+}
+
+/// Checks whether a file or directory is hidden by a leading `.` in it's name.
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_take_snapshot() {
+        let dir = env::temp_dir().join(format!("yake-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/main.rs.orig"), "").unwrap();
+        fs::write(dir.join("src/.gitignore"), "*.orig\n").unwrap();
+        fs::write(dir.join("README"), "").unwrap();
+        fs::write(dir.join(".git/HEAD"), "").unwrap();
+        fs::write(dir.join(".gitignore"), "/target\n").unwrap();
+        fs::write(dir.join("target/debug/yake"), "").unwrap();
+
+        // build output and other files git ignores are not watched
+        let all: Vec<PathBuf> = take_snapshot(&dir, None).unwrap().into_keys().collect();
+        assert_eq!(all, vec![dir.join("README"), dir.join("src/main.rs")]);
+
+        let inputs = vec!["src/*.rs".to_string()];
+        let sources: Vec<PathBuf> = take_snapshot(&dir, Some(&inputs))
+            .unwrap()
+            .into_keys()
+            .collect();
+        assert_eq!(sources, vec![dir.join("src/main.rs")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_wait_for_changes_debounces() {
+        let snapshot = |modified: u64| -> Snapshot {
+            let mut snapshot = BTreeMap::new();
+            snapshot.insert(
+                PathBuf::from("src/main.rs"),
+                Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+            );
+            snapshot
+        };
+
+        // unchanged twice, then changed on every poll until it settles at 5
+        let polls = AtomicUsize::new(0);
+        let changed = wait_for_changes(&snapshot(1), || {
+            let poll = polls.fetch_add(1, Ordering::SeqCst) as u64;
+            Ok(snapshot(if poll < 2 { 1 } else { poll.min(5) }))
+        })
+        .unwrap();
+
+        assert_eq!(changed, snapshot(5));
+        assert_eq!(polls.load(Ordering::SeqCst), 7);
+    }
+}
@@ -1,13 +1,60 @@
-use std::collections::HashMap;
-use std::io;
-use std::process::Command;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::{ChildStdin, Command, ExitStatus, Stdio};
 use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use colored::Colorize;
-use serde::de::Error;
+use serde::de::{Error, MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{self, Mapping, Value};
 use std::io::Write;
 
+use builtins::{expand_builtins, BuiltinValues};
+use condition::Condition;
+use duration::{format_duration, parse_duration};
+use env_file::read_env_file;
+use error::{
+    command_exit_code, YakeError, FAILED_EXIT_CODE, INTERRUPT_EXIT_CODE, TIMEOUT_EXIT_CODE,
+};
+use incremental::{
+    expand_patterns, fingerprint, is_up_to_date, outputs_exist, read_fingerprint,
+    validate_patterns, write_fingerprint,
+};
+use interpolation::{
+    expand_env_vars, expand_placeholders, resolve_env_references, shell_quote, validate_env_vars,
+};
+use progress::{pause_progress, progress_finished, progress_started, ProgressGuard};
+use prompt::prompt_env_var;
+use remote::REMOTE_INCLUDES_DIR;
+use reporter::{
+    mask_secrets, report_skipped, target_tag, CiFolding, Logger, OutputFormat, Stream,
+    TargetReporter, Verbosity, SECRET_MASK,
+};
+use service::{log_file, open_log_file, running_pid, stop_service, write_pid};
+use signals::{
+    exit_code, handle_interrupts, interrupted, interrupts, kill_process_group, new_process_group,
+    reset_interrupts, ForwardInterrupts, InterruptGuard,
+};
+use version::{check_version, YAKE_VERSION};
+use yaml::{find_target_line, read_yml_from_file};
+
+/// Delay before the first retry of a failed command, unless `retry_delay` is given.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Directory within a container the directory of the target is mounted at, unless
+/// `workdir` is given.
+const DEFAULT_CONTAINER_WORKDIR: &str = "/workspace";
+
 /// Represents the full yaml structure.
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Yake {
@@ -15,8 +62,31 @@ pub struct Yake {
     pub meta: YakeMeta,
     /// Environment variables
     pub env: Option<HashMap<String, String>>,
+    /// Names of env variables whose values are masked in the output of all targets
+    pub secrets: Option<Vec<String>>,
     /// Main targets
+    #[serde(
+        deserialize_with = "deserialize_targets",
+        serialize_with = "serialize_targets"
+    )]
     pub targets: HashMap<String, YakeTarget>,
+    /// Commands run once before the first target of each invocation, no target runs
+    /// if one of them fails. Only the ones of the Yakefile yake is invoked with are run.
+    pub before_all: Option<Vec<YakeCommand>>,
+    /// Commands run once after the last target of each invocation, even if a target
+    /// or `before_all` failed.
+    pub after_all: Option<Vec<YakeCommand>>,
+    /// Named lists of commands shared by targets, a `use: name` command of a target
+    /// or of the hooks is replaced by the commands of the snippet, see
+    /// `YakeUseCommand`. Snippets may use other snippets.
+    pub snippets: Option<HashMap<String, Vec<YakeCommand>>>,
+    /// Yakefile this one extends, relative to it. This one is merged over it: env
+    /// variables, targets and meta data are merged by name, other fields like the
+    /// commands of a target replace the ones of the base.
+    pub extends: Option<String>,
+    /// Overrides for environments like `dev` or `prod` by name, one of them is applied
+    /// with `--profile`. Only the profiles of the Yakefile yake is invoked with apply.
+    pub profiles: Option<HashMap<String, YakeProfile>>,
     /// Normalized, flattened map of all targets.
     /// Not deserialized from yaml.
     #[serde(skip)]
@@ -25,6 +95,91 @@ pub struct Yake {
     /// Not deserialized from yaml.
     #[serde(skip)]
     dependencies: HashMap<String, Vec<YakeTarget>>,
+    /// Path of the Yakefile this yake was loaded from.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// Yakefiles which defined a target merged in from a sub yake, in the order they
+    /// were merged, along with the name of the target within them. The last one is the
+    /// effective definition.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    merged_sources: HashMap<String, Vec<(PathBuf, String)>>,
+    /// Arguments given after `--` on the command line, passed on to the commands.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    pub args: Vec<String>,
+    /// Values of target parameters given on the command line.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    pub params: HashMap<String, String>,
+    /// Env variables given on the command line, overriding the ones of the Yakefile
+    /// and it's targets.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    pub env_overrides: HashMap<String, String>,
+    /// Whether remote includes are downloaded again instead of using the cached ones.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    pub update_includes: bool,
+    /// Output of the `set` commands of the target being executed and of the targets it
+    /// depends on, available as env variables.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    captured: CapturedVars,
+    /// Values of the built-in placeholders during an execution, shared by clones.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    builtins: BuiltinValues,
+}
+
+/// Env variables set by `set` commands while a target runs. Clones get a copy of the
+/// variables.
+#[derive(Debug, Default)]
+struct CapturedVars(Mutex<HashMap<String, String>>);
+
+impl CapturedVars {
+    /// Copy of the variables.
+    fn get(&self) -> HashMap<String, String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Sets a variable.
+    fn insert(&self, name: &str, value: String) {
+        self.0.lock().unwrap().insert(name.to_string(), value);
+    }
+}
+
+impl Clone for CapturedVars {
+    fn clone(&self) -> CapturedVars {
+        CapturedVars(Mutex::new(self.get()))
+    }
+}
+
+impl PartialEq for CapturedVars {
+    fn eq(&self, other: &CapturedVars) -> bool {
+        std::ptr::eq(self, other) || self.get() == other.get()
+    }
+}
+
+/// Location of a target definition within a Yakefile.
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct TargetDefinition {
+    /// Path of the Yakefile
+    pub file: PathBuf,
+    /// Line of the target's key, if it could be determined
+    pub line: Option<usize>,
+}
+
+/// Describes where a target is defined and which definitions it overrides.
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct TargetLocation {
+    /// Name of the target
+    pub target: String,
+    /// The effective definition of the target
+    pub definition: TargetDefinition,
+    /// Definitions of the target overridden while merging sub yakes
+    pub overrides: Vec<TargetDefinition>,
 }
 
 /// Contains meta data for the yake object.
@@ -35,14 +190,57 @@ pub struct Yake {
 pub struct YakeMeta {
     /// Documentation information
     pub doc: String,
-    /// Version information
+    /// Minimum version of yake the Yakefile requires, e.g. `0.1.0`
     pub version: String,
     /// Include Yakefiles of subfolders
     pub include_recursively: Option<bool>,
+    /// Levels of subfolders searched for Yakefiles with `include_recursively`, e.g. 2
+    /// for `packages/foo/Yakefile`. Defaults to 1, the direct subfolders.
+    pub include_depth: Option<usize>,
+    /// Glob patterns of directories and Yakefiles skipped with `include_recursively`,
+    /// relative to the Yakefile, e.g. `vendor/**`. Directories ignored by git are
+    /// skipped anyway.
+    pub include_exclude: Option<Vec<String>>,
+    /// Yakefiles to include, relative to this one. Directories stand for the Yakefile
+    /// within them, glob patterns like `services/*/Yakefile` for all matching ones and
+    /// HTTPS URLs for remote Yakefiles, pinned with `#sha256=<digest>`. Unpinned ones
+    /// are warned about.
+    pub includes: Option<Vec<String>>,
+    /// What happens if included Yakefiles define targets or env variables defined
+    /// before, defaults to `error`
+    pub include_strategy: Option<IncludeStrategy>,
+    /// Target executed if none is given on the command line
+    pub default_target: Option<String>,
+    /// Default shell of all targets, see `YakeTarget::shell`
+    pub shell: Option<Vec<String>>,
+    /// Default shell options of all targets, see `YakeTarget::shell_opts`
+    pub shell_opts: Option<String>,
+    /// Pass the environment of yake on to the commands, with the env variables of the
+    /// Yakefile on top. Defaults to true, commands only get the env variables of the
+    /// Yakefile otherwise.
+    pub inherit_env: Option<bool>,
+}
+
+/// How the targets and env variables of sub yakes are merged into the Yakefile
+/// including them.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum IncludeStrategy {
+    /// Fails for targets defined by a sub yake and before, unless they are marked with
+    /// `override`, and for env variables a sub yake sets to another value
+    #[default]
+    Error,
+    /// Targets and env variables of sub yakes replace the ones defined before
+    Override,
+    /// Targets of sub yakes are added to groups named after the path of their
+    /// directory, e.g. `services.api`, so sub yakes in directories of the same name do
+    /// not overlap. Other overlapping targets fail like with `error`, env variables of
+    /// sub yakes override the ones of the Yakefile for their targets.
+    Namespace,
 }
 
 /// Contains meta data for a yake target.
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct YakeTargetMeta {
     /// Documentation information
     pub doc: String,
@@ -51,31 +249,626 @@ pub struct YakeTargetMeta {
     pub target_type: YakeTargetType,
     /// List of dependent targets
     pub depends: Option<Vec<String>>,
+    /// Reason the target is deprecated, e.g. `use build.docker instead`. Running it
+    /// prints a warning, or fails with `strict`.
+    pub deprecated: Option<String>,
+    /// Whether the target only runs as a dependency of other targets or as child of a
+    /// group. It can not be run from the command line and is not listed.
+    pub internal: Option<bool>,
+    /// Whether the target of a sub yake replaces a target of the same name defined
+    /// before, which otherwise fails to load. Applies to the children of groups, too.
+    #[serde(rename = "override")]
+    pub overrides: Option<bool>,
 }
 
 /// Defines a yake target. Can have sub-targets.
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct YakeTarget {
     /// Target meta data
     pub meta: YakeTargetMeta,
     /// Subordinate targets
+    #[serde(
+        default,
+        deserialize_with = "deserialize_sub_targets",
+        serialize_with = "serialize_sub_targets"
+    )]
     pub targets: Option<HashMap<String, YakeTarget>>,
+    /// Position of the target among the targets it is declared with, the children of
+    /// groups run in this order. Recorded while parsing.
+    /// Not deserialized from yaml.
+    #[serde(skip)]
+    pub position: usize,
+    /// Name of another target, e.g. `_docker_base`, whose env, commands, cwd, meta
+    /// data and other fields this target inherits, except for it's sub targets and
+    /// whether it is internal. The fields of this target are merged over the
+    /// inherited ones. Resolved while parsing, only for targets of the first document
+    /// of a Yakefile.
+    pub extends: Option<String>,
     /// List of environment variables
     pub env: Option<HashMap<String, String>>,
+    /// Names of env variables whose values are masked in the output of the target.
+    /// Secrets of parent targets and of the Yakefile are masked as well. The output
+    /// of interactive targets is not masked.
+    pub secrets: Option<Vec<String>>,
+    /// File with environment variables in the format of `.env` files, relative to the
+    /// Yakefile. Overrides the variables given in `env`.
+    pub env_file: Option<String>,
     /// List of commands to execute
     /// Will only be executed for `TargetType::Cmd`
-    pub exec: Option<Vec<String>>,
+    pub exec: Option<Vec<YakeCommand>>,
+    /// Commands run one after another before `exec`, which does not run if one of them
+    /// fails
+    pub before: Option<Vec<YakeCommand>>,
+    /// Commands run one after another after `exec`, even if `exec` or `before` failed
+    pub after: Option<Vec<YakeCommand>>,
+    /// Exit codes which count as success for all commands of the target.
+    /// Defaults to `[0]`.
+    pub expected_exit_codes: Option<Vec<i32>>,
+    /// Run the commands attached to the terminal, without capturing their output.
+    pub interactive: Option<bool>,
+    /// Run the commands concurrently instead of one after another
+    pub parallel: Option<bool>,
+    /// Run all commands of `exec` as a single script in one shell, so that they share
+    /// the working directory, variables and functions. Like within any script, a
+    /// failing command does not stop the following ones, unless the shell is told to,
+    /// e.g. with `set -e`.
+    pub script_mode: Option<bool>,
+    /// Maximum number of commands running at the same time for parallel targets.
+    /// Defaults to `--jobs` if given, otherwise to the number of commands.
+    pub max_parallel: Option<usize>,
+    /// Show the output of each command of a parallel target in one piece once the
+    /// command finished, instead of interleaved with the output of the others.
+    pub group_output: Option<bool>,
+    /// Only show the output of the commands if one of them fails
+    pub silent: Option<bool>,
+    /// Program and arguments running the commands, each command line is passed as last
+    /// argument. Inherited from parent targets and the meta data, defaults to
+    /// `[bash, -c]` or `[powershell, -NoProfile, -Command]` on Windows.
+    pub shell: Option<Vec<String>>,
+    /// Options prepended to every command, e.g. `set -euo pipefail` to fail on errors
+    /// within pipelines and on unset variables. Inherited from parent targets and the
+    /// meta data, none by default.
+    pub shell_opts: Option<String>,
+    /// Directory the commands run in, relative to the Yakefile. Inherited from parent
+    /// targets, defaults to the directory of the Yakefile.
+    pub cwd: Option<String>,
+    /// Container the commands run in instead of the host, see `YakeContainer`.
+    /// Inherited from parent targets.
+    pub container: Option<YakeContainer>,
+    /// Host the commands run on over SSH instead of the local one, see `YakeRemote`.
+    /// Inherited from parent targets.
+    pub remote: Option<YakeRemote>,
+    /// Variables with lists of values, the target becomes a group with a callable
+    /// target for each combination of them, see `YakeMatrix`.
+    pub matrix: Option<YakeMatrix>,
+    /// Parameters given as `name=value` on the command line, available as env variables
+    pub params: Option<Vec<YakeParam>>,
+    /// Env variables which have to be set to a non-empty value, either by the env of
+    /// the target, a parameter, a `set` command of a target running before or, unless
+    /// `inherit_env` is false, the env yake runs in. Checked before anything runs, the
+    /// user is asked for missing ones if yake runs in a terminal.
+    pub requires_env: Option<Vec<YakeRequiredEnv>>,
+    /// Condition for running the target, the target is skipped if it does not hold.
+    /// See `Condition` for the syntax.
+    pub when: Option<String>,
+    /// Glob patterns of the files the target reads, relative to the Yakefile. The
+    /// target is skipped if neither the input files, nor it's commands or env changed
+    /// since it's last successful run.
+    pub inputs: Option<Vec<String>>,
+    /// Glob patterns of the files the target writes, relative to the Yakefile. The
+    /// target is skipped if all of them are newer than all of the inputs.
+    pub outputs: Option<Vec<String>>,
+    /// Run the target even if it's outputs are up to date or it's inputs unchanged
+    pub always_run: Option<bool>,
+    /// Time each command of the target may run, e.g. `90s` or `5m`. Commands running
+    /// longer are killed along with all processes they started.
+    pub timeout: Option<String>,
+    /// Number of times a failed command of the target is run again before the target
+    /// fails. Defaults to 0.
+    pub retries: Option<u32>,
+    /// Delay before the first retry of a command, e.g. `2s`. The delay doubles with
+    /// each further retry. Defaults to 1s.
+    pub retry_delay: Option<String>,
+}
+
+/// A single entry of a target's `exec` list. Either a plain command line or an
+/// object with additional options for the command.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum YakeCommand {
+    /// A plain command line
+    Plain(String),
+    /// A command line with options
+    Detailed(YakeCommandDetails),
+    /// A command line whose output is set as env variable, see `YakeSetCommand`
+    Set(YakeSetCommand),
+    /// A command line for each operating system, see `YakeOsCommand`
+    Os(YakeOsCommand),
+    /// The commands of a snippet, see `YakeUseCommand`
+    Use(YakeUseCommand),
+}
+
+/// A command given with variants for operating systems, e.g. `linux: xdg-open x` and
+/// `macos: open x`. The `default` runs on systems without a variant, the command is
+/// skipped on systems with neither a variant nor a default.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct YakeOsCommand {
+    /// Command line on Linux
+    pub linux: Option<String>,
+    /// Command line on macOS
+    pub macos: Option<String>,
+    /// Command line on Windows
+    pub windows: Option<String>,
+    /// Command line on systems without a variant
+    pub default: Option<String>,
+}
+
+/// Implementation for a YakeOsCommand.
+impl YakeOsCommand {
+    /// Command line for the operating system yake runs on, if there is one.
+    pub fn get_command(&self) -> Option<&str> {
+        let variant = match env::consts::OS {
+            "linux" => &self.linux,
+            "macos" => &self.macos,
+            "windows" => &self.windows,
+            _ => &None,
+        };
+        variant
+            .as_ref()
+            .or(self.default.as_ref())
+            .map(|cmd| cmd.as_str())
+    }
+}
+
+/// Env variables and fields of targets overridden for an environment, see
+/// `Yake::apply_profile`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct YakeProfile {
+    /// Env variables of the Yakefile, the given ones are overridden or added
+    pub env: Option<HashMap<String, String>>,
+    /// Fields of targets by the full name of the target, e.g. `timeout` of `deploy`.
+    /// The fields replace the ones of the target, except for `env` whose variables are
+    /// added to the ones of the target.
+    pub targets: Option<HashMap<String, Mapping>>,
+}
+
+/// A command given as `set: NAME = command` in `exec`, `before` or `after`. The output
+/// of the command, without the trailing newline, is set as env variable `NAME` for the
+/// following commands of the target and for the targets depending on it.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct YakeSetCommand {
+    /// Name of the env variable and the command line, separated by `=`
+    pub set: String,
+}
+
+/// A command given as `use: name` in `exec`, `before` or `after`, replaced by the
+/// commands of the snippet of that name while loading the Yakefile, see
+/// `Yake::expand_snippets`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct YakeUseCommand {
+    /// Name of the snippet
+    #[serde(rename = "use")]
+    pub snippet: String,
+}
+
+/// Container running the commands of a target with `docker run`. The directory of the
+/// target is mounted in the container and the env of the target is set in it. The
+/// shell defaults to `[sh, -c]` within containers.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct YakeContainer {
+    /// Image the container is created from
+    pub image: String,
+    /// Further volumes as given to `docker run --volume`, host paths starting with `.`
+    /// are relative to the Yakefile
+    pub volumes: Option<Vec<String>>,
+    /// Directory the directory of the target is mounted at and the commands run in.
+    /// Defaults to `/workspace`.
+    pub workdir: Option<String>,
+    /// Further arguments of `docker run`, e.g. `[--network, host]`
+    pub args: Option<Vec<String>>,
+}
+
+/// Host running the commands of a target over SSH. The env of the target is exported
+/// on the host before each command, the shell defaults to `[sh, -c]` there.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct YakeRemote {
+    /// Name or address of the host
+    pub host: String,
+    /// User to log in as, defaults to the one of the SSH configuration
+    pub user: Option<String>,
+    /// Port of the SSH server, defaults to the one of the SSH configuration
+    pub port: Option<u16>,
+    /// Private key to log in with, relative to the Yakefile
+    pub key: Option<String>,
+    /// Directory on the host the commands run in, defaults to the home directory of
+    /// the user
+    pub dir: Option<String>,
+}
+
+/// A parameter of a target. Either just the name of a required parameter or an object
+/// with additional options.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum YakeParam {
+    /// Name of a required parameter
+    Plain(String),
+    /// A parameter with options
+    Detailed(YakeParamDetails),
+}
+
+/// Options of a parameter given in the object form of a `params` entry.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct YakeParamDetails {
+    /// Name of the parameter and the env variable holding it's value
+    pub name: String,
+    /// Value if the parameter is not given
+    pub default: Option<String>,
+    /// Whether the parameter has to be given, defaults to true for parameters
+    /// without a default
+    pub required: Option<bool>,
+}
+
+/// An env variable required by a target. Either just it's name or an object with
+/// additional options.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum YakeRequiredEnv {
+    /// Name of the env variable
+    Plain(String),
+    /// An env variable with options
+    Detailed(YakeRequiredEnvDetails),
+}
+
+/// Options of an env variable given in the object form of a `requires_env` entry.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct YakeRequiredEnvDetails {
+    /// Name of the env variable
+    pub name: String,
+    /// Value suggested when the user is asked for the variable
+    pub default: Option<String>,
+    /// Hide the input when the user is asked for the variable and mask it's value in
+    /// the output of the target
+    pub secret: Option<bool>,
+}
+
+/// Options of a command given in the object form of an `exec` entry.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct YakeCommandDetails {
+    /// Command line to execute
+    pub cmd: String,
+    /// Inline content written to the command's stdin.
+    /// Environment variables are expanded before writing.
+    pub stdin: Option<String>,
+    /// File whose content is written to the command's stdin,
+    /// relative to the Yakefile
+    pub stdin_file: Option<String>,
+    /// Exit codes which count as success, overrides the ones of the target
+    pub expected_exit_codes: Option<Vec<i32>>,
+    /// Time the command may run, overrides the timeout of the target
+    pub timeout: Option<String>,
+    /// Number of retries if the command fails, overrides the retries of the target
+    pub retries: Option<u32>,
+    /// Delay before the first retry, overrides the retry delay of the target
+    pub retry_delay: Option<String>,
+    /// Continue with the target if the command fails, like make's `-` prefix
+    pub ignore_errors: Option<bool>,
+}
+
+/// A dependency on a target of another Yakefile, written as
+/// `path/to/Yakefile:target.name` in `depends`.
+///
+/// The path is relative to the directory of the referencing Yakefile.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExternalReference {
+    /// Path of the referenced Yakefile
+    pub path: PathBuf,
+    /// Name of the referenced target
+    pub target: String,
+}
+
+/// Implementation for an ExternalReference.
+impl ExternalReference {
+    /// Parses a dependency name. Returns `None` for names of local targets.
+    pub fn parse(dependency_name: &str) -> Option<ExternalReference> {
+        let (path, target) = dependency_name.rsplit_once(':')?;
+        if path.is_empty() || target.is_empty() {
+            return None;
+        }
+        Some(ExternalReference {
+            path: PathBuf::from(path),
+            target: target.to_string(),
+        })
+    }
+}
+
+/// Yakes loaded for cross-file dependencies, keyed by their canonical path.
+type ExternalYakes = HashMap<PathBuf, Arc<Yake>>;
+
+/// Options for the execution of targets, usually given on the command line.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ExecutionOptions {
+    /// Show the output of silent targets
+    pub no_silent: bool,
+    /// Wrap the output of each target in a collapsible section of the CI system
+    pub ci_folding: Option<CiFolding>,
+    /// Run only the dependencies of the requested target, not the target itself
+    pub deps_only: bool,
+    /// Run only the requested targets and the children of groups, not their
+    /// dependencies
+    pub skip_deps: bool,
+    /// Run targets even if their outputs are up to date or their inputs unchanged
+    pub force: bool,
+    /// Print the commands and env of each target instead of running them
+    pub dry_run: bool,
+    /// Number of targets run concurrently, at least one. Also bounds the commands of
+    /// parallel targets running at the same time, unless it is 0 for not given.
+    pub jobs: usize,
+    /// Keep starting the targets which do not depend on a failed target after a target
+    /// failed, instead of starting no more targets
+    pub keep_going: bool,
+    /// Format of the output. With JSON output nothing is written while the targets
+    /// run, their output is captured for the `ExecutionReport` instead.
+    pub output: OutputFormat,
+    /// How much is reported besides the output of the commands
+    pub verbosity: Verbosity,
+    /// Fail instead of warning if deprecated targets are scheduled
+    pub strict: bool,
+    /// Fail for missing env variables instead of asking for them
+    pub no_input: bool,
+}
+
+impl ExecutionOptions {
+    /// Logger for messages about the execution.
+    pub fn logger(&self) -> Logger {
+        Logger {
+            verbosity: self.verbosity,
+            format: self.output,
+        }
+    }
+}
+
+/// Outcome of a target within an execution.
+#[derive(Debug, PartialEq, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetStatus {
+    Succeeded,
+    Failed,
+    Skipped,
+    /// Not run because another target failed before
+    NotRun,
+}
+
+/// Result of a single target of an execution.
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct TargetResult {
+    /// Name of the target
+    pub target: String,
+    /// Path of the Yakefile defining the target
+    pub file: PathBuf,
+    pub status: TargetStatus,
+    /// Why the target was skipped
+    pub reason: Option<String>,
+    /// Seconds the target ran
+    pub duration: f64,
+    /// Exit code of the target, the one of the failed command for failed targets, see
+    /// `command_exit_code`
+    pub exit_code: Option<i32>,
+    /// Error of a failed target
+    pub error: Option<String>,
+    /// Output of the commands of the target, only captured with JSON output
+    pub output: String,
+    /// Commands the target ran, in the order they are defined in
+    pub commands: Vec<CommandResult>,
+}
+
+/// Result of a single command of a target.
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct CommandResult {
+    /// The command line
+    pub command: String,
+    /// Seconds the command ran, retries included
+    pub duration: f64,
+    pub exit_code: i32,
+}
+
+/// Commands run by a target along with their index, collected while it runs.
+type CommandResults = Mutex<Vec<(usize, CommandResult)>>;
+
+/// Results of all targets of an execution, in the order they were scheduled in.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ExecutionReport {
+    pub success: bool,
+    /// Exit code of yake, see `failures_exit_code`
+    pub exit_code: i32,
+    /// Seconds the execution took
+    pub duration: f64,
+    pub targets: Vec<TargetResult>,
+    /// Whether yake was interrupted, no more targets were started then and the exit
+    /// code is 130
+    pub interrupted: bool,
+    /// Failures of targets along with their index
+    #[serde(skip)]
+    failures: Vec<(usize, YakeError)>,
+}
+
+impl ExecutionReport {
+    /// The failures of the targets as a single error, if any. Interrupted executions
+    /// always fail.
+    pub fn into_result(self) -> Result<(), YakeError> {
+        let exit_code = failures_exit_code(&self.failures);
+        let result = join_failures(self.failures).map_err(|e| YakeError::Failed {
+            message: e.to_string(),
+            exit_code,
+        });
+        if !self.interrupted {
+            return result;
+        }
+        Err(YakeError::Failed {
+            message: match result {
+                Err(e) => e.to_string(),
+                Ok(()) => "Interrupted".to_string(),
+            },
+            exit_code: INTERRUPT_EXIT_CODE,
+        })
+    }
+
+    /// Adds the results of the global hooks which ran, `before_all` in front of the
+    /// targets and `after_all` after them.
+    fn add_hooks(&mut self, before_all: Option<HookResult>, after_all: Option<HookResult>) {
+        if let Some((result, outcome)) = before_all {
+            for failure in &mut self.failures {
+                failure.0 += 1;
+            }
+            if let Err(e) = outcome {
+                self.failures.insert(0, (0, e));
+            }
+            self.targets.insert(0, result);
+        }
+        if let Some((result, outcome)) = after_all {
+            if let Err(e) = outcome {
+                self.failures.push((self.targets.len(), e));
+            }
+            self.targets.push(result);
+        }
+        self.success = self.failures.is_empty();
+        self.exit_code = failures_exit_code(&self.failures);
+    }
+
+    /// Marks the execution as interrupted.
+    fn set_interrupted(&mut self) {
+        self.interrupted = true;
+        self.success = false;
+        self.exit_code = INTERRUPT_EXIT_CODE;
+    }
+}
+
+/// Result of a global hook along with it's outcome.
+type HookResult = (TargetResult, Result<(), YakeError>);
+
+/// State of a single execution, shared by all targets executed within it.
+struct Execution<'a> {
+    /// The executed yake
+    root: &'a Yake,
+    /// Options of the execution
+    options: &'a ExecutionOptions,
+    /// Yakefiles loaded for cross-file dependencies
+    yakes: ExternalYakes,
+    /// Canonical Yakefile path, target name and location of every target currently
+    /// being executed, used to detect cycles
+    stack: Vec<(PathBuf, String, String)>,
+    /// Targets to run, each one after the targets it depends on
+    scheduled: Vec<ScheduledTarget>,
+    /// Indices of the scheduled targets by yake and target name
+    scheduled_indices: HashMap<(Option<PathBuf>, String), usize>,
+    /// Env variables set by `set` commands of the scheduled targets, by index
+    captured: Mutex<HashMap<usize, HashMap<String, String>>>,
+    /// Values of required env variables the user was asked for
+    given_env: HashMap<String, String>,
+}
+
+impl<'a> Execution<'a> {
+    /// Env variables set by `set` commands of scheduled targets and the targets they
+    /// depend on, along with the ones the user was asked for.
+    fn get_captured(&self, indices: &[usize]) -> HashMap<String, String> {
+        let captured = self.captured.lock().unwrap();
+        let mut vars = self.given_env.clone();
+        for i in indices {
+            vars.extend(captured.get(i).cloned().unwrap_or_default());
+        }
+        vars
+    }
+
+    /// Adds a target to the scheduled targets, after the targets it depends on.
+    fn add_scheduled_target(
+        &mut self,
+        yake: &Option<PathBuf>,
+        target_name: &str,
+        dependencies: Vec<usize>,
+    ) -> usize {
+        let key = (yake.clone(), target_name.to_string());
+        self.scheduled.push(ScheduledTarget {
+            yake: yake.clone(),
+            target_name: target_name.to_string(),
+            dependencies,
+            after: Vec::new(),
+            skip: false,
+        });
+        self.scheduled_indices.insert(key, self.scheduled.len() - 1);
+        self.scheduled.len() - 1
+    }
+}
+
+/// A target scheduled for execution.
+struct ScheduledTarget {
+    /// Canonical path of the Yakefile for targets of cross-file dependencies, `None`
+    /// for targets of the executed yake
+    yake: Option<PathBuf>,
+    target_name: String,
+    /// Indices of the scheduled targets which have to finish first
+    dependencies: Vec<usize>,
+    /// Indices of the targets requested before this one, they have to finish first as
+    /// well but with `keep_going` they may have failed
+    after: Vec<usize>,
+    /// Whether the commands of the target are skipped
+    skip: bool,
+}
+
+/// State of the worker pool running the scheduled targets.
+struct Scheduler {
+    /// Number of unfinished dependencies of each scheduled target
+    pending: Vec<usize>,
+    /// Scheduled targets whose dependencies are finished
+    ready: BTreeSet<usize>,
+    /// Number of targets currently running
+    running: usize,
+    /// Whether an interactive target is running, no other target may run meanwhile
+    exclusive: bool,
+    /// Failures of targets along with their index
+    failures: Vec<(usize, YakeError)>,
+    /// Targets which are not run as a target they depend on failed
+    blocked: BTreeSet<usize>,
+    /// Results of the finished targets
+    results: Vec<Option<TargetResult>>,
+}
+
+impl Scheduler {
+    /// Updates the targets waiting for a finished target, given along with whether
+    /// they depend on it or were only requested after it. Targets depending on a
+    /// failed target are blocked, the targets waiting for them in turn are updated as
+    /// if they failed as well.
+    fn finish(&mut self, index: usize, succeeded: bool, dependents: &[Vec<(usize, bool)>]) {
+        for &(dependent, requested_after) in &dependents[index] {
+            if succeeded || requested_after {
+                self.pending[dependent] -= 1;
+                if self.pending[dependent] == 0 && !self.blocked.contains(&dependent) {
+                    self.ready.insert(dependent);
+                }
+            } else if self.blocked.insert(dependent) {
+                self.finish(dependent, false, dependents);
+            }
+        }
+    }
 }
 
 // Custom deserialization via:
 // https://github.com/serde-rs/serde/issues/1019#issuecomment-322966402
 /// Defines the different target types.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub enum YakeTargetType {
-    /// A Group has no own commands, just sub-targets.
+    /// A Group has no own commands, just sub-targets. Running a group runs all of it's
+    /// sub-targets.
     Group,
     /// A Callable has no sub-targets, just commands.
+    #[default]
     Callable,
+    /// A Service runs it's commands in the background and keeps running after yake
+    /// ended, until it is stopped with `yake stop`. It is only started if it is not
+    /// running yet.
+    Service,
 }
 
 /// Implements custom serde serializer for the YakeTargetType
@@ -87,6 +880,7 @@ impl Serialize for YakeTargetType {
         serializer.serialize_str(match *self {
             YakeTargetType::Group => "group",
             YakeTargetType::Callable => "callable",
+            YakeTargetType::Service => "service",
         })
     }
 }
@@ -101,22 +895,242 @@ impl<'de> Deserialize<'de> for YakeTargetType {
         match s.as_str() {
             "group" => Ok(YakeTargetType::Group),
             "callable" => Ok(YakeTargetType::Callable),
+            "service" => Ok(YakeTargetType::Service),
             _ => Err(D::Error::custom(format!("unknown target type '{}'", s))),
         }
     }
 }
 
+/// Variables of a target's `matrix` with their values, in the order they are given.
+///
+/// A target with a matrix is expanded into a group with a target for each combination
+/// of the values when the Yakefile is loaded, e.g. `build` with `os: [linux, darwin]`
+/// and `arch: [amd64, arm64]` into `build.linux-amd64`, `build.linux-arm64`, ... The
+/// values are set as env variables of the expanded targets.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct YakeMatrix(pub Vec<(String, Vec<String>)>);
+
+/// Implementation for the YakeMatrix
+impl YakeMatrix {
+    /// All combinations of the values, the values of the first variable change last.
+    pub fn combinations(&self) -> Vec<Vec<(String, String)>> {
+        let mut combinations = vec![Vec::new()];
+        for (name, values) in &self.0 {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination: Vec<(String, String)>| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((name.clone(), value.clone()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+}
+
+/// Implements custom serde serializer for the YakeMatrix, keeping the order
+impl Serialize for YakeMatrix {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|(name, values)| (name, values)))
+    }
+}
+
+/// Implements custom serde deserializer for the YakeMatrix, keeping the order
+impl<'de> Deserialize<'de> for YakeMatrix {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MatrixVisitor;
+
+        impl<'de> Visitor<'de> for MatrixVisitor {
+            type Value = YakeMatrix;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of variables to lists of values")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<YakeMatrix, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut variables = Vec::new();
+                while let Some(entry) = map.next_entry::<String, Vec<String>>()? {
+                    variables.push(entry);
+                }
+                Ok(YakeMatrix(variables))
+            }
+        }
+
+        deserializer.deserialize_map(MatrixVisitor)
+    }
+}
+
+/// Deserializes a map of targets, recording the position each target is declared at,
+/// see `YakeTarget::position`.
+pub fn deserialize_targets<'de, D>(deserializer: D) -> Result<HashMap<String, YakeTarget>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct TargetsVisitor;
+
+    impl<'de> Visitor<'de> for TargetsVisitor {
+        type Value = HashMap<String, YakeTarget>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<HashMap<String, YakeTarget>, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut targets = HashMap::new();
+            while let Some((name, mut target)) = map.next_entry::<String, YakeTarget>()? {
+                target.position = targets.len();
+                targets.insert(name, target);
+            }
+            Ok(targets)
+        }
+    }
+
+    deserializer.deserialize_map(TargetsVisitor)
+}
+
+/// Deserializes optional sub targets like `deserialize_targets`.
+fn deserialize_sub_targets<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<String, YakeTarget>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Targets(#[serde(deserialize_with = "deserialize_targets")] HashMap<String, YakeTarget>);
+
+    Ok(Option::<Targets>::deserialize(deserializer)?.map(|targets| targets.0))
+}
+
+/// Targets serialized in the order of their positions, so that they keep it when
+/// they are parsed again, e.g. the targets of an extended Yakefile.
+struct TargetsByPosition<'a>(&'a HashMap<String, YakeTarget>);
+
+impl Serialize for TargetsByPosition<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut targets: Vec<(&String, &YakeTarget)> = self.0.iter().collect();
+        targets.sort_by_key(|(name, target)| (target.position, *name));
+        serializer.collect_map(targets)
+    }
+}
+
+/// Serializes targets in the order of their positions, see `TargetsByPosition`.
+fn serialize_targets<S>(
+    targets: &HashMap<String, YakeTarget>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    TargetsByPosition(targets).serialize(serializer)
+}
+
+/// Serializes optional sub targets like `serialize_targets`.
+fn serialize_sub_targets<S>(
+    targets: &Option<HashMap<String, YakeTarget>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    targets
+        .as_ref()
+        .map(TargetsByPosition)
+        .serialize(serializer)
+}
+
+/// Position after the ones of the given targets, for targets added to them.
+fn next_position(targets: &HashMap<String, YakeTarget>) -> usize {
+    targets
+        .values()
+        .map(|target| target.position + 1)
+        .max()
+        .unwrap_or(0)
+}
+
 /// Implementation for the Yake object
 impl Yake {
-    /// Get's a list of all existing, callable target names
+    /// Get's a list of all existing, callable target names, services included and
+    /// internal targets left out
     pub fn get_target_names(&self) -> Vec<String> {
         self.get_all_targets()
             .iter()
-            .filter(|&(_name, target)| target.meta.target_type == YakeTargetType::Callable)
+            .filter(|&(_name, target)| target.meta.target_type != YakeTargetType::Group)
+            .filter(|&(_name, target)| !target.is_internal())
             .map(|(name, _target)| name.clone())
             .collect()
     }
 
+    /// Get's a list of the names of all groups, internal ones left out
+    pub fn get_group_names(&self) -> Vec<String> {
+        self.get_all_targets()
+            .iter()
+            .filter(|&(_name, target)| target.meta.target_type == YakeTargetType::Group)
+            .filter(|&(_name, target)| !target.is_internal())
+            .map(|(name, _target)| name.clone())
+            .collect()
+    }
+
+    /// Checks that a target may be run from the command line, which internal targets
+    /// may not.
+    fn check_not_internal(&self, target_name: &str) -> Result<(), YakeError> {
+        if !self.get_target_by_name(target_name).unwrap().is_internal() {
+            return Ok(());
+        }
+        let mut dependents: Vec<String> = self
+            .get_all_targets()
+            .into_iter()
+            .filter(|(_, target)| {
+                target
+                    .meta
+                    .depends
+                    .iter()
+                    .flatten()
+                    .any(|dependency| dependency == target_name)
+            })
+            .map(|(name, _)| name)
+            .collect();
+        dependents.sort();
+        Err(YakeError::Invalid(format!(
+            "Target {} is internal, it only runs as a dependency of {}",
+            target_name,
+            if dependents.is_empty() {
+                "other targets".to_string()
+            } else {
+                dependents.join(", ")
+            }
+        )))
+    }
+
+    /// Gets the names of all services, sorted.
+    pub fn get_service_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .get_all_targets()
+            .into_iter()
+            .filter(|(_name, target)| target.is_service())
+            .map(|(name, _target)| name)
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Gets a flattened, normalized map of all target names and it's respective yake
     /// target.
     fn get_all_targets(&self) -> HashMap<String, YakeTarget> {
@@ -134,478 +1148,5853 @@ impl Yake {
     }
 
     /// Checks, whether a specific target name exists.
-    pub fn has_target_name(&self, target_name: &str) -> Result<(), Vec<String>> {
+    pub fn has_target_name(&self, target_name: &str) -> Result<(), YakeError> {
         if self.get_target_by_name(target_name).is_some() {
             Ok(())
         } else {
-            Err(self.get_target_names().clone())
+            Err(YakeError::UnknownTarget {
+                target: target_name.to_string(),
+                available: self.get_target_names(),
+            })
+        }
+    }
+
+    /// Checks, whether a service of the name exists.
+    pub fn has_service_name(&self, target_name: &str) -> Result<(), YakeError> {
+        self.has_target_name(target_name)?;
+        if self.get_target_by_name(target_name).unwrap().is_service() {
+            Ok(())
+        } else {
+            Err(YakeError::Invalid(format!(
+                "Target {} is not a service, services are {:?}",
+                target_name,
+                self.get_service_names()
+            )))
         }
     }
 
+    /// Pid of a service if it is running.
+    pub fn get_service_pid(&self, target_name: &str) -> Option<u32> {
+        running_pid(&self.get_base_dir(), target_name)
+    }
+
+    /// Stops a service, see `stop_service`. Returns it's pid, `None` if it was not
+    /// running.
+    pub fn stop_service(&self, target_name: &str) -> Result<Option<u32>, YakeError> {
+        self.has_service_name(target_name)?;
+        Ok(stop_service(&self.get_base_dir(), target_name)?)
+    }
+
     /// Gets a YakeTarget by name.
-    fn get_target_by_name(&self, target_name: &str) -> Option<YakeTarget> {
-        self.get_all_targets()
-            .get(&target_name.to_string())
-            .cloned()
+    pub fn get_target_by_name(&self, target_name: &str) -> Option<YakeTarget> {
+        self.get_all_targets().get(target_name).cloned()
     }
 
-    /// Gets a normalized, flattened map of all dependencies for each callable target name.
-    /// Contains a vector for every callable target in the system, even if a target has no
-    /// dependencies.
-    fn get_all_dependencies(&self) -> HashMap<String, Vec<YakeTarget>> {
+    /// Gets a normalized, flattened map of all dependencies for each target name.
+    /// Contains a vector for every target in the system, even if a target has no
+    /// dependencies. Groups depend on their children. Dependencies on targets of other
+    /// Yakefiles are not included.
+    /// Fails if targets depend on each other, directly or transitively.
+    fn get_all_dependencies(&self) -> Result<HashMap<String, Vec<YakeTarget>>, String> {
         let mut ret: HashMap<String, Vec<YakeTarget>> = HashMap::new();
-        for (target_name, target) in self.get_all_targets() {
-            if target.meta.target_type != YakeTargetType::Callable {
-                continue;
-            }
+        let mut names: HashMap<String, Vec<String>> = HashMap::new();
+        let all_targets = self.get_all_targets();
+        for (target_name, target) in &all_targets {
+            let target_name = target_name.clone();
             ret.insert(target_name.clone(), Vec::new());
-            for dependency_name in target.meta.depends.unwrap_or(vec![]).iter() {
-                let dep = self.get_target_by_name(dependency_name);
-                let dep_target = dep.expect(
+            names.insert(target_name.clone(), Vec::new());
+            let mut dependency_names = target.meta.depends.clone().unwrap_or_default();
+            if target.meta.target_type == YakeTargetType::Group {
+                dependency_names.extend(get_child_names(&all_targets, &target_name));
+            }
+            for dependency_name in dependency_names.iter() {
+                if ExternalReference::parse(dependency_name).is_some() {
+                    continue;
+                }
+                let dep_target = self.get_target_by_name(dependency_name).ok_or_else(|| {
                     format!(
-                        "Warning: Unknown dependency: {} in target: {}.",
+                        "Unknown dependency: {} of target: {}",
                         dependency_name, target_name
                     )
-                    .as_str(),
-                );
+                })?;
 
                 ret.get_mut(&target_name).unwrap().push(dep_target);
+                names
+                    .get_mut(&target_name)
+                    .unwrap()
+                    .push(dependency_name.clone());
             }
         }
 
-        ret
+        if let Some(cycle) = find_dependency_cycle(&names) {
+            return Err(format!("Dependency cycle detected: {}", cycle.join(" -> ")));
+        }
+        Ok(ret)
+    }
+
+    /// Names of the direct children of a group, in the order they are declared in, see
+    /// `YakeTarget::position`.
+    fn get_group_children(&self, group_name: &str) -> Vec<String> {
+        let children = self
+            .get_target_by_name(group_name)
+            .and_then(|group| group.targets)
+            .unwrap_or_default();
+        let mut children: Vec<(usize, String)> = children
+            .into_iter()
+            .map(|(name, child)| (child.position, format!("{}.{}", group_name, name)))
+            .collect();
+        children.sort();
+        children.into_iter().map(|(_, name)| name).collect()
     }
 
     /// Gets a list of dependencies for a target name.
-    fn get_dependencies_by_name(&self, target_name: &str) -> Vec<YakeTarget> {
-        self.get_all_dependencies()
+    fn get_dependencies_by_name(&self, target_name: &str) -> Result<Vec<YakeTarget>, String> {
+        Ok(self
+            .get_all_dependencies()?
             .get(target_name)
             .unwrap()
-            .clone()
-    }
-
-    /// add targets from yakes of subordinate yakes
-    pub fn add_sub_yake(&mut self, yake: Yake) -> () {
-        yake.get_all_targets().iter().for_each(|(name, target)| {
-            &self.targets.insert(name.clone(), target.clone());
-            ()
-        });
+            .clone())
     }
 
-    /// fetches all environment variables of the current target and it's parent targets
-    pub fn get_target_env_vars(&self, target_name: &str) -> Result<HashMap<String, String>, String> {
-        if self.has_target_name(target_name).is_err() {
-            return Err(format!("Unknown target: {}", target_name).to_string());
+    /// Adds the targets of a sub yake to a group named after the directory of it's
+    /// Yakefile, e.g. `build` of `services/api/Yakefile` becomes `api.build`, or
+    /// `services.api.build` with the `namespace` include strategy. The groups are
+    /// created unless the yake defines them already. Targets of the sub yake defined
+    /// before fail to add, adding nothing, unless they are marked with `override` or
+    /// the include strategy is `override`, see `IncludeStrategy`. Dependencies between
+    /// the targets of the sub yake are renamed along with them, the env and shell of
+    /// the sub yake are passed on to it's targets.
+    pub fn add_sub_yake(&mut self, yake: Yake) -> Result<(), String> {
+        let strategy = self.meta.include_strategy.unwrap_or_default();
+        let sub_source = yake.get_source();
+        let namespace = match strategy {
+            IncludeStrategy::Namespace => self.get_relative_namespace(&yake),
+            IncludeStrategy::Error | IncludeStrategy::Override => yake.get_namespace(),
+        };
+        let existing_targets = self.get_all_targets();
+        let is_group = |name: &str| {
+            existing_targets
+                .get(name)
+                .is_some_and(|group| group.meta.target_type == YakeTargetType::Group)
+        };
+        let groups: Vec<String> = namespace
+            .match_indices('.')
+            .map(|(i, _)| namespace[..i].to_string())
+            .chain(Some(namespace.clone()))
+            .collect();
+        let sub_targets = yake.get_all_targets();
+        let mut names: Vec<(String, String)> = sub_targets
+            .keys()
+            .map(|name| (format!("{}.{}", namespace, name), name.clone()))
+            .collect();
+        for group in &groups {
+            if !is_group(group) {
+                names.push((group.clone(), String::new()));
+            }
         }
+        names.sort();
 
-        let mut envs = self.env.clone().unwrap_or_default();
-        let parent_targets: Vec<&str> = target_name.split(".").collect();
-
-        // iterate over parent targets and extend the env with each of them, starting from the
-        // highest hierarchy level
-        for (i, _t) in parent_targets.iter().enumerate() {
-            let parent_target_name = parent_targets[0..i+1].join(".");
-            let p = self.get_target_by_name(&parent_target_name).expect(&format!("Unknown Target {}", parent_target_name));
-            envs.extend(p.env.unwrap_or_default());
+        let source = self.get_source();
+        let mut conflicts: Vec<String> = names
+            .iter()
+            .filter(|(name, sub_name)| {
+                existing_targets.contains_key(name)
+                    && strategy != IncludeStrategy::Override
+                    && !overrides(&sub_targets, sub_name)
+            })
+            .map(|(name, sub_name)| {
+                let defined_by = self
+                    .merged_sources
+                    .get(name)
+                    .and_then(|sources| sources.last())
+                    .map_or(&source, |(file, _)| file);
+                let mut conflict = format!(
+                    "Target {} is defined by {} and {}",
+                    name,
+                    defined_by.display(),
+                    sub_source.display()
+                );
+                if !sub_name.is_empty() {
+                    conflict.push_str(", mark it with `override: true` to replace it");
+                }
+                conflict
+            })
+            .collect();
+        if strategy == IncludeStrategy::Error {
+            let env = self.env.clone().unwrap_or_default();
+            let mut variables: Vec<_> = yake.env.iter().flatten().collect();
+            variables.sort();
+            for (variable, value) in variables {
+                if env.get(variable).is_some_and(|defined| defined != value) {
+                    conflicts.push(format!(
+                        "Env variable {} is set to different values by {} and {}",
+                        variable,
+                        source.display(),
+                        sub_source.display()
+                    ));
+                }
+            }
         }
-
-        let target = self.get_target_by_name(target_name).unwrap();
-        envs.extend(target.env.unwrap_or_default());
-
-        // filter blacklisted vars like PATH. If not not filtered,
-        // the subprocess execution would panic due to path expansion.
-        let (invalid, valid): (HashMap<&String, &String>, HashMap<&String, &String>) = envs.iter().partition(|&k| {
-            k.0 == "TERM" || k.0 == "TZ" || k.0 == "LANG" || k.0 == "PATH" || k.0 == "HOME"
-        });
-
-        if invalid.len() > 0 {
-            panic!("{} {:?}", "Found invalid/forbidden env variables".bold().red(), invalid.keys());
+        if !conflicts.is_empty() {
+            return Err(conflicts.join("\n"));
         }
 
-        Ok(valid.iter().map(|(&k, &v)| {
-            (k.clone(), v.clone())
-        }).collect())
-    }
-
-    /// Execute a target and it's dependencies.
-    pub fn execute(&self, target_name: &str) -> Result<String, String> {
-        if self.has_target_name(target_name).is_err() {
-            return Err(format!("Unknown target: {}", target_name).to_string());
+        for (name, sub_name) in names {
+            let sources = self.merged_sources.entry(name.clone()).or_insert_with(|| {
+                if existing_targets.contains_key(&name) {
+                    vec![(source.clone(), name)]
+                } else {
+                    Vec::new()
+                }
+            });
+            sources.push((sub_source.clone(), sub_name));
         }
 
-        let target = self.get_target_by_name(target_name).unwrap();
-        let dependencies = self.get_dependencies_by_name(target_name);
-
-        let run_target = |target: &YakeTarget| match target.exec {
-            Some(ref commands) => {
-                for command in commands {
-                    println!(
-                        "{} {}:",
-                        "↪ Executing".bold().blue(),
-                        command.as_str().bold().green()
-                    );
-                    let output = Command::new("bash")
-                        .arg("-c")
-                        .arg(command.clone())
-                        .envs(self.get_target_env_vars(target_name).unwrap_or_default())
-                        .output()
-                        .expect(&format!("failed to execute command \"{}\"", command));
-
-                    let stdout_str = str::from_utf8(&output.stdout).unwrap();
-                    let stderr_str = str::from_utf8(&output.stderr).unwrap();
-                    stdout_str.lines().into_iter().for_each(|line| {
-                        io::stdout()
-                            .write_all(format!("{}  {}\n", "┆".bold().green(), line).as_bytes())
-                            .expect(&format!("failed to write line to stdout \"{}\"", line));
-                    });
-                    stderr_str.lines().into_iter().for_each(|line| {
-                        io::stderr()
-                            .write_all(format!("{}  {}\n", "┆".bold().red(), line).as_bytes())
-                            .expect(&format!("failed to write line to stderr \"{}\"", line));
-                    });
-                }
-                io::stdout()
-                    .write_all(format!("{}\n", "↪ Done".bold().blue()).as_bytes())
-                    .expect(&format!("failed to write line to stdout"));
+        let mut children = &mut self.targets;
+        for (group_name, name) in groups.iter().zip(namespace.split('.')) {
+            let position = next_position(children);
+            let group = children.entry(name.to_string()).or_default();
+            if !is_group(group_name) {
+                let doc = if *group_name == namespace {
+                    yake.meta.doc.clone()
+                } else {
+                    format!(
+                        "Targets of the Yakefiles in {}",
+                        group_name.replace('.', "/")
+                    )
+                };
+                *group = YakeTarget {
+                    meta: YakeTargetMeta {
+                        doc,
+                        target_type: YakeTargetType::Group,
+                        depends: None,
+                        deprecated: None,
+                        internal: None,
+                        overrides: None,
+                    },
+                    position,
+                    ..YakeTarget::default()
+                };
             }
-            None => (),
-        };
+            children = group.targets.get_or_insert_with(HashMap::new);
+        }
+        // the targets of the sub yake follow the children the group has already
+        let offset = next_position(children);
+        for (name, target) in &yake.targets {
+            let mut target = target.clone();
+            target.position += offset;
+            let mut env = yake.env.clone().unwrap_or_default();
+            env.extend(target.env.unwrap_or_default());
+            target.env = Some(env);
+            let mut secrets = yake.secrets.clone().unwrap_or_default();
+            secrets.extend(target.secrets.unwrap_or_default());
+            target.secrets = Some(secrets);
+            target.shell = target.shell.or_else(|| yake.meta.shell.clone());
+            target.shell_opts = target.shell_opts.or_else(|| yake.meta.shell_opts.clone());
+            add_namespace(&mut target, &namespace, &yake);
+            children.insert(name.clone(), target);
+        }
+        Ok(())
+    }
 
-        // run dependencies first
-        for dep in dependencies {
-            run_target(&dep);
+    /// Name of the group a sub yake is added to, the name of the directory of it's
+    /// Yakefile.
+    fn get_namespace(&self) -> String {
+        let dir = self.get_base_dir();
+        let dir = fs::canonicalize(&dir).unwrap_or(dir);
+        match dir.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => dir.display().to_string(),
         }
+    }
 
-        // then run the actual target
-        run_target(&target);
+    /// Name of the group a sub yake is added to with the `namespace` include strategy,
+    /// the path of it's directory relative to the one of this yake, e.g. `services.api`.
+    /// Sub yakes outside of the directory are added to a group named after their
+    /// directory.
+    fn get_relative_namespace(&self, sub_yake: &Yake) -> String {
+        let canonical = |dir: PathBuf| fs::canonicalize(&dir).unwrap_or(dir);
+        let base_dir = canonical(self.get_base_dir());
+        let dir = canonical(sub_yake.get_base_dir());
+        match dir.strip_prefix(&base_dir) {
+            Ok(relative) if relative.components().next().is_some() => relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("."),
+            _ => sub_yake.get_namespace(),
+        }
+    }
 
-        Ok("All cool".to_string())
+    /// Path of the Yakefile this yake was loaded from, `Yakefile` if unknown.
+    fn get_source(&self) -> PathBuf {
+        self.source
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("Yakefile"))
     }
-}
 
-/// Implementation for a YakeTarget.
-impl YakeTarget {
-    /// Get a map of subordinate targets.
-    pub fn get_sub_targets(&self, prefix: Option<String>) -> HashMap<String, YakeTarget> {
-        let mut targets = HashMap::new();
-        match self.targets {
-            Some(ref x) => {
-                for (target_name, target) in x {
-                    if target.meta.target_type == YakeTargetType::Callable {
-                        let name = match prefix {
-                            Some(ref x) => format!("{}.{}", x, target_name),
-                            None => target_name.to_string(),
-                        };
-                        targets.insert(name, target.clone());
-                    } else {
-                        let p = match prefix {
-                            Some(ref x) => Some(format!("{}.{}", x, target_name)),
-                            None => None,
-                        };
-                        targets.extend(target.get_sub_targets(p))
+    /// Finds the Yakefile (and line) defining a target, including definitions which
+    /// were overridden by sub yakes.
+    pub fn get_target_location(&self, target_name: &str) -> Result<TargetLocation, YakeError> {
+        self.has_target_name(target_name)?;
+
+        let mut sources = self
+            .merged_sources
+            .get(target_name)
+            .cloned()
+            .unwrap_or_else(|| vec![(self.get_source(), target_name.to_string())]);
+        let definitions: Vec<TargetDefinition> = sources
+            .drain(..)
+            .map(|(file, name)| TargetDefinition {
+                line: find_target_line(&file, &name),
+                file,
+            })
+            .collect();
+
+        let (definition, overrides) = definitions.split_last().unwrap();
+        Ok(TargetLocation {
+            target: target_name.to_string(),
+            definition: definition.clone(),
+            overrides: overrides.to_vec(),
+        })
+    }
+
+    /// Checks all targets for invalid or conflicting options.
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_meta()?;
+        for (target_name, target) in self.get_all_targets() {
+            target
+                .validate()
+                .map_err(|e| format!("Invalid target {}: {}", target_name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Applies the overrides of a profile to the env and the targets, see
+    /// `YakeProfile`. Fails for unknown profiles, targets or fields and if the
+    /// overridden targets are invalid.
+    pub fn apply_profile(&mut self, profile_name: &str) -> Result<(), String> {
+        let profiles = self.profiles.clone().unwrap_or_default();
+        let profile = profiles.get(profile_name).ok_or_else(|| {
+            let mut names: Vec<&String> = profiles.keys().collect();
+            names.sort();
+            let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+            format!(
+                "Unknown profile: {}, the Yakefile defines {}",
+                profile_name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            )
+        })?;
+
+        if let Some(ref env) = profile.env {
+            self.env
+                .get_or_insert_with(HashMap::new)
+                .extend(env.clone());
+        }
+        let mut overrides: Vec<_> = profile.targets.iter().flatten().collect();
+        overrides.sort_by_key(|(target_name, _)| target_name.as_str());
+        for (target_name, fields) in overrides {
+            let error = |e: String| {
+                format!(
+                    "Invalid target {} of profile {}: {}",
+                    target_name, profile_name, e
+                )
+            };
+            let target = get_target_mut(&mut self.targets, target_name)
+                .ok_or_else(|| error("Unknown target".to_string()))?;
+            override_target_fields(target, fields).map_err(error)?;
+        }
+        // the commands of the profile may use snippets, too
+        self.expand_snippets()?;
+        self.validate()
+    }
+
+    /// Expands the targets with a `matrix` into groups of targets, see `YakeMatrix`.
+    pub fn expand_matrices(&mut self) -> Result<(), String> {
+        expand_matrix_targets(&mut self.targets, None)
+    }
+
+    /// Replaces the `use` commands of the targets and hooks by the commands of their
+    /// snippets, see `YakeUseCommand`.
+    pub fn expand_snippets(&mut self) -> Result<(), String> {
+        let snippets = self.snippets.clone().unwrap_or_default();
+        for (name, hook) in [
+            ("before_all", &mut self.before_all),
+            ("after_all", &mut self.after_all),
+        ] {
+            if let Some(ref mut commands) = *hook {
+                *commands = expand_commands(commands, &snippets, &mut Vec::new())
+                    .map_err(|e| format!("Invalid {}: {}", name, e))?;
+            }
+        }
+        expand_snippet_targets(&mut self.targets, None, &snippets)
+    }
+
+    /// Checks the meta data and the global hooks for invalid options and that yake is at
+    /// least the version the Yakefile requires.
+    pub fn validate_meta(&self) -> Result<(), String> {
+        check_version(&self.meta.version, YAKE_VERSION)?;
+        if self
+            .meta
+            .shell
+            .as_ref()
+            .is_some_and(|shell| shell.is_empty())
+        {
+            return Err("Invalid meta data: shell must not be empty".to_string());
+        }
+        if self.meta.include_depth == Some(0) {
+            return Err("Invalid meta data: include_depth must be at least 1".to_string());
+        }
+        validate_patterns(self.meta.include_exclude.as_deref().unwrap_or_default())
+            .map_err(|e| format!("Invalid meta data: include_exclude: {}", e))?;
+        for (name, hook) in [
+            ("before_all", &self.before_all),
+            ("after_all", &self.after_all),
+        ] {
+            if let Some(ref commands) = *hook {
+                get_hook_target(commands.clone())
+                    .validate()
+                    .map_err(|e| format!("Invalid {}: {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds all problems of the targets without running anything: invalid options,
+    /// dependencies which do not resolve, dependency cycles, groups with commands,
+    /// callable targets with sub targets and an unknown default target. Problems of a
+    /// target start with it's location.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let all_targets = self.get_all_targets();
+        let mut target_names: Vec<&String> = all_targets.keys().collect();
+        target_names.sort();
+
+        let mut dependencies = HashMap::new();
+        for target_name in target_names {
+            let target = &all_targets[target_name];
+            let mut report = |message: String| {
+                problems.push(format!(
+                    "{}: target {}: {}",
+                    self.format_location(target_name),
+                    target_name,
+                    message
+                ))
+            };
+            if let Err(e) = target.validate() {
+                report(e);
+            }
+            match target.meta.target_type {
+                YakeTargetType::Group if target.has_commands() => report(
+                    "A group must not have exec, before or after, only callable targets run commands"
+                        .to_string(),
+                ),
+                YakeTargetType::Callable | YakeTargetType::Service if target.targets.is_some() => {
+                    report(
+                        "A callable target must not have targets, only groups have sub targets"
+                            .to_string(),
+                    )
+                }
+                _ => {}
+            }
+
+            let mut names = Vec::new();
+            for dependency in target.meta.depends.as_deref().unwrap_or_default() {
+                match ExternalReference::parse(dependency) {
+                    Some(reference) => {
+                        if let Err(e) = self.check_external_reference(target_name, &reference) {
+                            report(e);
+                        }
                     }
+                    None if !all_targets.contains_key(dependency) => {
+                        report(format!("Unknown dependency: {}", dependency))
+                    }
+                    None => names.push(dependency.clone()),
                 }
             }
-            None => (),
+            if target.meta.target_type == YakeTargetType::Group {
+                names.extend(get_child_names(&all_targets, target_name));
+            }
+            dependencies.insert(target_name.clone(), names);
         }
-        targets
+
+        if let Some(cycle) = find_dependency_cycle(&dependencies) {
+            problems.push(format!("Dependency cycle detected: {}", cycle.join(" -> ")));
+        }
+        if let Some(ref default_target) = self.meta.default_target {
+            match all_targets.get(default_target) {
+                None => problems.push(format!("Unknown default target: {}", default_target)),
+                Some(target) if target.is_internal() => {
+                    problems.push(format!("Default target {} is internal", default_target))
+                }
+                Some(_) => {}
+            }
+        }
+        problems
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use serde_yaml;
+    /// Checks that the Yakefile of a cross-file dependency can be loaded and defines
+    /// the target.
+    fn check_external_reference(
+        &self,
+        target_name: &str,
+        reference: &ExternalReference,
+    ) -> Result<(), String> {
+        let path = self.get_target_base_dir(target_name).join(&reference.path);
+        let yake = read_yml_from_file(&path)
+            .map_err(|e| format!("Unable to load {}: {}", path.display(), e))?;
+        if yake.get_target_by_name(&reference.target).is_none() {
+            return Err(format!(
+                "Unknown dependency: {} is not defined in {}",
+                reference.target,
+                path.display()
+            ));
+        }
+        Ok(())
+    }
 
-    use super::*;
+    /// Location of a target as text, e.g. `api/Yakefile:12`.
+    fn format_location(&self, target_name: &str) -> String {
+        match self.get_target_location(target_name) {
+            Ok(TargetLocation {
+                definition:
+                    TargetDefinition {
+                        file,
+                        line: Some(line),
+                    },
+                ..
+            }) => format!("{}:{}", file.display(), line),
+            Ok(location) => location.definition.file.display().to_string(),
+            Err(_) => self.get_source().display().to_string(),
+        }
+    }
 
-    fn get_yake_targets() -> HashMap<String, YakeTarget> {
-        let mut env = HashMap::new();
-        env.insert("WEBAPP_PORT".to_string(), "6543".to_string());
-        env.insert("POSTGRES_PORT".to_string(), "5432".to_string());
-        let callable_target = YakeTarget {
-            targets: None,
-            meta: YakeTargetMeta {
-                doc: "Huhu".to_string(),
-                target_type: YakeTargetType::Callable,
-                depends: Some(vec!["base".to_string()]),
-            },
-            env: Some(env),
-            exec: None,
+    /// Directory of the Yakefile this yake was loaded from. Commands are executed
+    /// relative to it.
+    pub fn get_base_dir(&self) -> PathBuf {
+        get_dir(self.source.as_deref())
+    }
+
+    /// Directory of the Yakefile defining a target, which is the one of the sub yake
+    /// for targets merged in from it. Paths of a target are relative to it. Targets
+    /// of remote includes run in the directory of the Yakefile including them.
+    pub fn get_target_base_dir(&self, target_name: &str) -> PathBuf {
+        let base_dir = self.get_base_dir();
+        match self
+            .merged_sources
+            .get(target_name)
+            .and_then(|sources| sources.last())
+        {
+            Some((source, _)) if !source.starts_with(base_dir.join(REMOTE_INCLUDES_DIR)) => {
+                get_dir(Some(source))
+            }
+            _ => base_dir,
+        }
+    }
+
+    /// Canonical path of the Yakefile this yake was loaded from. Used to identify
+    /// yakes across cross-file dependencies.
+    fn get_canonical_source(&self) -> PathBuf {
+        let source = self.get_source();
+        fs::canonicalize(&source).unwrap_or(source)
+    }
+
+    /// Human readable location of a target, e.g. `../shared/Yakefile:docker.login`.
+    fn get_location(&self, target_name: &str) -> String {
+        match self.source {
+            Some(ref source) => format!("{}:{}", source.display(), target_name),
+            None => target_name.to_string(),
+        }
+    }
+
+    /// Loads the Yakefile of a cross-file dependency. Each file is only loaded once
+    /// per execution. Returns the key of the yake in the scheduled targets along with
+    /// it. The Yakefile of the executed yake is not loaded again, references back to
+    /// it get the executed yake, with it's sub yakes, under the key `None`, so that
+    /// it's targets are not scheduled twice.
+    fn load_external_yake(
+        &self,
+        reference: &ExternalReference,
+        target_name: &str,
+        execution: &mut Execution,
+    ) -> Result<(Option<PathBuf>, Arc<Yake>), String> {
+        let path = self.get_target_base_dir(target_name).join(&reference.path);
+        let error = |e: String| {
+            format!(
+                "Unable to load {} referenced by {}: {}",
+                path.display(),
+                self.get_location(target_name),
+                e
+            )
         };
 
-        let mut env_sub = HashMap::new();
-        env_sub.insert("BASE".to_string(), "OVERWRITE".to_string());
-        env_sub.insert("DOCKER_PORT".to_string(), "1234".to_string());
-        env_sub.insert("POSTGRES_PORT".to_string(), "54322".to_string());
-        let sub_target = YakeTarget {
-            targets: None,
-            meta: YakeTargetMeta {
-                doc: "Subtarget".to_string(),
-                target_type: YakeTargetType::Callable,
-                depends: Some(vec!["base".to_string()]),
-            },
-            env: Some(env_sub),
-            exec: None,
+        let canonical_path = fs::canonicalize(&path).map_err(|e| error(e.to_string()))?;
+        let root = execution.root;
+        let key = if root.source.is_some() && canonical_path == root.get_canonical_source() {
+            None
+        } else {
+            Some(canonical_path.clone())
         };
+        if let Some(yake) = execution.yakes.get(&canonical_path) {
+            return Ok((key, yake.clone()));
+        }
 
-        let group_target = YakeTarget {
-            targets: Some([("sub".to_string(), sub_target)].iter().cloned().collect()),
-            meta: YakeTargetMeta {
-                doc: "Grouptarget".to_string(),
-                target_type: YakeTargetType::Group,
-                depends: None,
-            },
-            env: None,
-            exec: None,
+        let yake = match key {
+            None => root.clone(),
+            Some(_) => {
+                let mut yake = read_yml_from_file(&path).map_err(error)?;
+                yake.args = self.args.clone();
+                yake.params = self.params.clone();
+                yake.env_overrides = self.env_overrides.clone();
+                yake.builtins = self.builtins.clone();
+                yake
+            }
         };
+        let yake = Arc::new(yake);
+        execution.yakes.insert(canonical_path, yake.clone());
+        Ok((key, yake))
+    }
 
-        [
-            (
-                "base".to_string(),
-                YakeTarget {
-                    targets: None,
-                    meta: YakeTargetMeta {
-                        doc: "Base".to_string(),
-                        target_type: YakeTargetType::Callable,
-                        depends: None,
-                    },
-                    env: None,
-                    exec: None,
+    /// Values of the parameters of a target, either given on the command line or their
+    /// defaults. Fails if a required parameter is not given.
+    fn get_param_values(&self, target_name: &str) -> Result<HashMap<String, String>, String> {
+        let target = self
+            .get_target_by_name(target_name)
+            .ok_or_else(|| format!("Unknown target: {}", target_name))?;
+
+        let mut values = HashMap::new();
+        for param in target.params.unwrap_or_default() {
+            let name = param.get_name();
+            match self.params.get(name).map(|v| v.as_str()) {
+                Some(value) => values.insert(name.to_string(), value.to_string()),
+                None => match param.get_default() {
+                    Some(value) => values.insert(name.to_string(), value.to_string()),
+                    None if param.is_required() => {
+                        return Err(format!(
+                            "Missing parameter {} of target {}, give it as {}=<value>",
+                            name, target_name, name
+                        ));
+                    }
+                    None => None,
                 },
-            ),
-            ("test".to_string(), callable_target),
-            ("group".to_string(), group_target),
-        ]
-        .iter()
-        .cloned()
-        .collect()
+            };
+        }
+        Ok(values)
     }
 
-    fn get_yake_dependencies(
-        targets: &HashMap<String, YakeTarget>,
-    ) -> HashMap<String, Vec<YakeTarget>> {
-        let mut dependencies = HashMap::new();
-        dependencies.insert(
-            "test".to_string(),
-            vec![targets.get(&"base".to_string()).unwrap().clone()],
+    /// fetches all environment variables of the current target and it's parent targets.
+    /// Later ones take precedence: the env of the Yakefile, the `.env` file next to it,
+    /// the env and env file of each parent target and of the target, the env variables
+    /// given on the command line, the output of `set` commands, `YAKE_ARGS` and the
+    /// parameters.
+    pub fn get_target_env_vars(
+        &self,
+        target_name: &str,
+    ) -> Result<HashMap<String, String>, String> {
+        let mut envs = self.get_declared_env_vars(target_name)?;
+        let dir = self.get_target_base_dir(target_name);
+        for value in envs.values_mut() {
+            *value = expand_builtins(value, &dir, &HashMap::new(), &self.builtins)?;
+        }
+
+        // arguments and the output of commands are added once references are resolved,
+        // they are passed on as they were given
+        envs.extend(self.captured.get());
+        if !self.args.is_empty() {
+            envs.insert("YAKE_ARGS".to_string(), self.args.join(" "));
+        }
+        envs.extend(self.get_param_values(target_name)?);
+
+        Ok(envs)
+    }
+
+    /// The env variables of a target as declared by the Yakefile, it's env files and
+    /// the command line, with references between them resolved, see
+    /// `get_target_env_vars`. Fails for cyclic or unknown references and invalid
+    /// variables, which are problems of the Yakefile.
+    fn get_declared_env_vars(&self, target_name: &str) -> Result<HashMap<String, String>, String> {
+        if self.has_target_name(target_name).is_err() {
+            return Err(format!("Unknown target: {}", target_name));
+        }
+
+        // a .env file next to the Yakefile overrides the env of the Yakefile, as do
+        // env files of targets override the env of their target
+        let mut envs = self.env.clone().unwrap_or_default();
+        let dotenv = self.get_base_dir().join(".env");
+        if dotenv.is_file() {
+            envs.extend(read_env_file(&dotenv)?);
+        }
+        let parent_targets: Vec<&str> = target_name.split('.').collect();
+
+        // iterate over parent targets and extend the env with each of them, starting from the
+        // highest hierarchy level
+        for (i, _t) in parent_targets.iter().enumerate() {
+            let parent_target_name = parent_targets[0..i + 1].join(".");
+            let p = self
+                .get_target_by_name(&parent_target_name)
+                .ok_or_else(|| format!("Unknown target: {}", parent_target_name))?;
+            envs.extend(p.env.unwrap_or_default());
+            if let Some(env_file) = p.env_file {
+                let dir = self.get_target_base_dir(&parent_target_name);
+                envs.extend(read_env_file(&dir.join(env_file))?);
+            }
+        }
+        envs.extend(self.env_overrides.clone());
+
+        // resolve references between the variables once all levels are merged, so that
+        // a target can refer to variables of it's parents.
+        let envs = resolve_env_references(&envs)?;
+        validate_env_vars(&envs)?;
+        Ok(envs)
+    }
+
+    /// Values of the env variables named in `secrets` of the Yakefile and of the target
+    /// and it's parents and of their secret `requires_env` variables, including values
+    /// inherited from the env of yake. Longest first so that secrets containing others
+    /// are masked completely.
+    pub fn get_target_secrets(&self, target_name: &str) -> Result<Vec<String>, String> {
+        let envs = self.get_target_env_vars(target_name)?;
+        let mut names = self.secrets.clone().unwrap_or_default();
+        let parts: Vec<&str> = target_name.split('.').collect();
+        for i in 1..=parts.len() {
+            if let Some(target) = self.get_target_by_name(&parts[..i].join(".")) {
+                names.extend(target.secrets.unwrap_or_default());
+                names.extend(
+                    target
+                        .requires_env
+                        .iter()
+                        .flatten()
+                        .filter(|variable| variable.is_secret())
+                        .map(|variable| variable.get_name().to_string()),
+                );
+            }
+        }
+
+        let inherits = self.meta.inherit_env.unwrap_or(true);
+        let mut secrets: Vec<String> = names
+            .iter()
+            .filter_map(|name| {
+                envs.get(name)
+                    .cloned()
+                    .or_else(|| env::var(name).ok().filter(|_| inherits))
+            })
+            .filter(|value| !value.is_empty())
+            .collect();
+        secrets.sort();
+        secrets.dedup();
+        secrets.sort_by_key(|secret| Reverse(secret.len()));
+        Ok(secrets)
+    }
+
+    /// Execute a target and it's dependencies. With more than one job, targets whose
+    /// dependencies are finished run concurrently.
+    pub fn execute(
+        &self,
+        target_name: &str,
+        options: &ExecutionOptions,
+    ) -> Result<String, YakeError> {
+        self.execute_targets(&[target_name.to_string()], options)?;
+        Ok("All cool".to_string())
+    }
+
+    /// Execute several targets in the given order, along with their dependencies.
+    /// Targets shared by them only run once. A target and the dependencies it does not
+    /// share with the targets before it only start once the target before it finished.
+    pub fn execute_targets(
+        &self,
+        target_names: &[String],
+        options: &ExecutionOptions,
+    ) -> Result<(), YakeError> {
+        self.execute_with_report(target_names, options)?
+            .into_result()
+    }
+
+    /// Execute targets like `execute_targets`, reporting the result of each target.
+    /// Fails only if the targets can not be executed at all, failures of targets are
+    /// part of the report. Interrupts are forwarded to the running commands, once they
+    /// ended the `after` hooks of their targets and `after_all` still run.
+    pub fn execute_with_report(
+        &self,
+        target_names: &[String],
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionReport, YakeError> {
+        for target_name in target_names {
+            self.has_target_name(target_name)?;
+            self.check_not_internal(target_name)?;
+        }
+        // each execution, e.g. in watch mode, gets new built-in values
+        self.builtins.clear();
+
+        let mut execution = Execution {
+            root: self,
+            options,
+            yakes: HashMap::new(),
+            stack: Vec::new(),
+            scheduled: Vec::new(),
+            scheduled_indices: HashMap::new(),
+            captured: Mutex::new(HashMap::new()),
+            given_env: HashMap::new(),
+        };
+        let mut previous: Option<usize> = None;
+        for target_name in target_names {
+            let first_new = execution.scheduled.len();
+            let index = self
+                .schedule_target(&None, target_name, &mut execution)
+                .map_err(YakeError::Invalid)?;
+            execution.scheduled[index].skip = options.deps_only;
+            // the newly scheduled targets wait for the ones scheduled before, which can
+            // not depend on them, so this adds no cycles
+            if let Some(previous) = previous {
+                for scheduled in &mut execution.scheduled[first_new..] {
+                    scheduled.after.push(previous);
+                }
+            }
+            previous = Some(previous.map_or(index, |previous| previous.max(index)));
+        }
+        self.check_params(&execution).map_err(YakeError::Invalid)?;
+        self.check_env(&execution).map_err(YakeError::Invalid)?;
+        execution.given_env = self
+            .check_required_env(&execution)
+            .map_err(YakeError::Invalid)?;
+        self.check_deprecated(&execution)
+            .map_err(YakeError::Invalid)?;
+        self.report_scheduled_targets(&execution);
+
+        handle_interrupts();
+        let interrupts_before = interrupts();
+        let started = Instant::now();
+        let before_all = self.run_hook("before_all", &self.before_all, options);
+        let run_targets = !matches!(before_all, Some((_, Err(_))));
+        let mut report = self.run_scheduled_targets(&execution, run_targets);
+        let after_all = self.run_hook("after_all", &self.after_all, options);
+        report.add_hooks(before_all, after_all);
+        if interrupts() > interrupts_before {
+            report.set_interrupted();
+        }
+        report.duration = started.elapsed().as_secs_f64();
+        Ok(report)
+    }
+
+    /// Runs the commands of a global hook like the ones of a target of the same name,
+    /// with the env and shell of the Yakefile and in it's directory. Returns `None` if
+    /// the hook is not defined.
+    fn run_hook(
+        &self,
+        name: &str,
+        commands: &Option<Vec<YakeCommand>>,
+        options: &ExecutionOptions,
+    ) -> Option<HookResult> {
+        let mut yake = self.clone();
+        yake.merged_sources = HashMap::new();
+        yake.targets = HashMap::new();
+        yake.targets
+            .insert(name.to_string(), get_hook_target(commands.clone()?));
+        let target = yake.targets[name].clone();
+
+        let started = Instant::now();
+        let mut result = TargetResult {
+            target: name.to_string(),
+            file: self.get_source(),
+            status: TargetStatus::Succeeded,
+            reason: None,
+            duration: 0.0,
+            exit_code: Some(0),
+            error: None,
+            output: String::new(),
+            commands: Vec::new(),
+        };
+        let outcome = yake.run_target(name, &target, options, None, &mut result);
+        result.duration = started.elapsed().as_secs_f64();
+        if let Err(ref e) = outcome {
+            result.status = TargetStatus::Failed;
+            result.exit_code = Some(e.exit_code());
+            result.error = Some(e.to_string());
+        }
+        Some((result, outcome))
+    }
+
+    /// Reports the scheduled targets along with the targets they wait for, with
+    /// verbose output.
+    fn report_scheduled_targets(&self, execution: &Execution) {
+        let logger = execution.options.logger();
+        for scheduled in &execution.scheduled {
+            let mut dependencies: Vec<String> = scheduled
+                .dependencies
+                .iter()
+                .chain(&scheduled.after)
+                .map(|&i| self.get_scheduled_name(&execution.scheduled[i]))
+                .collect();
+            dependencies.dedup();
+            let after = if dependencies.is_empty() {
+                String::new()
+            } else {
+                format!(" after {}", dependencies.join(", "))
+            };
+            logger.log(
+                Verbosity::Verbose,
+                &format!(
+                    "{} {}{}",
+                    "↪ Scheduled".bold().blue(),
+                    self.get_scheduled_name(scheduled).bold().green(),
+                    after
+                ),
+            );
+        }
+    }
+
+    /// Schedules a target after it's dependencies, loading the Yakefiles of cross-file
+    /// dependencies on demand. `yake` is the key of this yake in the scheduled targets.
+    /// Returns the index of the scheduled target, targets are only scheduled once.
+    /// The scheduled targets are sorted topologically, depth first: dependencies are
+    /// scheduled in the order they are declared in, followed by the children of groups
+    /// in the order they are declared in, so the order does not depend on the maps the
+    /// targets are kept in.
+    fn schedule_target(
+        &self,
+        yake: &Option<PathBuf>,
+        target_name: &str,
+        execution: &mut Execution,
+    ) -> Result<usize, String> {
+        let key = (yake.clone(), target_name.to_string());
+        if let Some(&index) = execution.scheduled_indices.get(&key) {
+            return Ok(index);
+        }
+        if self.has_target_name(target_name).is_err() {
+            return Err(format!("Unknown target: {}", target_name));
+        }
+
+        let source = self.get_canonical_source();
+        if execution
+            .stack
+            .iter()
+            .any(|(path, name, _)| *path == source && name == target_name)
+        {
+            let cycle: Vec<String> = execution
+                .stack
+                .iter()
+                .map(|(_, _, location)| location.clone())
+                .chain(Some(self.get_location(target_name)))
+                .collect();
+            return Err(format!("Dependency cycle detected: {}", cycle.join(" -> ")));
+        }
+        execution.stack.push((
+            source,
+            target_name.to_string(),
+            self.get_location(target_name),
+        ));
+
+        let target = self.get_target_by_name(target_name).unwrap();
+        // check the local dependencies for unknown targets and cycles up front
+        self.get_dependencies_by_name(target_name)?;
+
+        // schedule dependencies and their dependencies first, in the order they were
+        // declared. Groups run their children after their own dependencies.
+        let mut dependency_names = if execution.options.skip_deps {
+            Vec::new()
+        } else {
+            target.meta.depends.clone().unwrap_or_default()
+        };
+        if target.meta.target_type == YakeTargetType::Group {
+            dependency_names.extend(self.get_group_children(target_name));
+        }
+        let mut dependencies = Vec::new();
+        for dependency_name in dependency_names {
+            match ExternalReference::parse(&dependency_name) {
+                Some(reference) => {
+                    let (key, yake) =
+                        self.load_external_yake(&reference, target_name, execution)?;
+                    if yake.has_target_name(&reference.target).is_err() {
+                        return Err(format!(
+                            "Unknown target: {} referenced by {}",
+                            yake.get_location(&reference.target),
+                            self.get_location(target_name)
+                        ));
+                    }
+                    dependencies.push(yake.schedule_target(&key, &reference.target, execution)?);
+                }
+                None => {
+                    dependencies.push(self.schedule_target(yake, &dependency_name, execution)?);
+                }
+            }
+        }
+
+        execution.stack.pop();
+        Ok(execution.add_scheduled_target(yake, target_name, dependencies))
+    }
+
+    /// Checks the parameters given on the command line before anything runs. Each one
+    /// has to be declared by a scheduled target, and all required parameters of the
+    /// scheduled targets have to be given.
+    fn check_params(&self, execution: &Execution) -> Result<(), String> {
+        let mut declared = HashSet::new();
+        for scheduled in &execution.scheduled {
+            let yake = self.get_scheduled_yake(scheduled, &execution.yakes);
+            if !scheduled.skip {
+                yake.get_param_values(&scheduled.target_name)?;
+            }
+            let target = yake.get_target_by_name(&scheduled.target_name).unwrap();
+            for param in target.params.unwrap_or_default() {
+                declared.insert(param.get_name().to_string());
+            }
+        }
+
+        let mut names: Vec<&String> = self.params.keys().collect();
+        names.sort();
+        match names.into_iter().find(|name| !declared.contains(*name)) {
+            Some(name) => Err(format!("Unknown parameter {}", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the env variables of the scheduled targets can be resolved, see
+    /// `get_declared_env_vars`, before any of them runs.
+    fn check_env(&self, execution: &Execution) -> Result<(), String> {
+        for scheduled in execution.scheduled.iter().filter(|s| !s.skip) {
+            self.get_scheduled_yake(scheduled, &execution.yakes)
+                .get_declared_env_vars(&scheduled.target_name)
+                .map_err(|e| format!("Invalid env of target {}: {}", scheduled.target_name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the env variables required by the scheduled targets are set, see
+    /// `YakeTarget::requires_env`. The user is asked for missing variables if yake runs
+    /// in a terminal and `no_input` is not given, returns the values given. Fails with
+    /// all variables which are still missing.
+    fn check_required_env(&self, execution: &Execution) -> Result<HashMap<String, String>, String> {
+        let scheduled: Vec<(&Yake, &ScheduledTarget)> = execution
+            .scheduled
+            .iter()
+            .filter(|s| !s.skip)
+            .map(|s| (self.get_scheduled_yake(s, &execution.yakes), s))
+            .collect();
+        let set_by_commands: HashSet<String> = scheduled
+            .iter()
+            .filter_map(|(yake, s)| yake.get_target_by_name(&s.target_name))
+            .flat_map(|target| {
+                target
+                    .get_all_commands()
+                    .filter_map(|command| command.get_output_var().map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        // missing variables along with the targets requiring them and the first of
+        // their requirements with options
+        let mut missing: BTreeMap<String, (Vec<&str>, YakeRequiredEnv)> = BTreeMap::new();
+        for (yake, s) in scheduled {
+            let target = yake.get_target_by_name(&s.target_name).unwrap();
+            let required = target.requires_env.unwrap_or_default();
+            if required.is_empty() {
+                continue;
+            }
+            let envs = yake.get_target_env_vars(&s.target_name)?;
+            let inherits = yake.meta.inherit_env.unwrap_or(true);
+            for variable in required {
+                let name = variable.get_name();
+                let is_set = envs.get(name).is_some_and(|value| !value.is_empty())
+                    || set_by_commands.contains(name)
+                    || inherits && env::var_os(name).is_some_and(|value| !value.is_empty());
+                if is_set {
+                    continue;
+                }
+                let entry = missing
+                    .entry(name.to_string())
+                    .or_insert_with(|| (Vec::new(), variable.clone()));
+                entry.0.push(&s.target_name);
+                if let YakeRequiredEnv::Plain(_) = entry.1 {
+                    entry.1 = variable;
+                }
+            }
+        }
+
+        let mut given = HashMap::new();
+        if !execution.options.no_input && io::stdin().is_terminal() && io::stderr().is_terminal() {
+            for (name, (targets, variable)) in &missing {
+                let value = prompt_env_var(
+                    name,
+                    &targets.join(", "),
+                    variable.get_default(),
+                    variable.is_secret(),
+                )
+                .map_err(|e| format!("Unable to read {}: {}", name, e))?;
+                if let Some(value) = value.filter(|value| !value.is_empty()) {
+                    given.insert(name.clone(), value);
+                }
+            }
+        }
+
+        let missing: Vec<String> = missing
+            .into_iter()
+            .filter(|(name, _)| !given.contains_key(name))
+            .map(|(name, (targets, _))| format!("{} (required by {})", name, targets.join(", ")))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Missing env variables: {}", missing.join(", ")));
+        }
+        Ok(given)
+    }
+
+    /// Warns about deprecated targets which are scheduled to run, fails for them with
+    /// `strict` before anything runs.
+    fn check_deprecated(&self, execution: &Execution) -> Result<(), String> {
+        for scheduled in execution.scheduled.iter().filter(|s| !s.skip) {
+            let yake = self.get_scheduled_yake(scheduled, &execution.yakes);
+            let target = yake.get_target_by_name(&scheduled.target_name).unwrap();
+            if let Some(reason) = target.meta.deprecated {
+                let message = format!("Target {} is deprecated: {}", scheduled.target_name, reason);
+                if execution.options.strict {
+                    return Err(message);
+                }
+                execution.options.logger().warn(&message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the scheduled targets on a pool of `jobs` workers. A target is started once
+    /// all of it's dependencies finished, ready targets start in the order they were
+    /// scheduled. Interactive targets need the terminal for themselves and run alone.
+    /// After a failure or an interrupt no more targets are started, running targets are
+    /// waited for. With `keep_going` the targets not depending on a failed target are
+    /// still started after a failure, but not after an interrupt. If stdout is a
+    /// terminal, a status area below the output shows the running and finished
+    /// targets, see `ProgressGuard`.
+    /// Without `run_targets`, e.g. after `before_all` failed, none of them is started.
+    fn run_scheduled_targets(&self, execution: &Execution, run_targets: bool) -> ExecutionReport {
+        let started = Instant::now();
+        let options = execution.options;
+        let _progress = if options.output == OutputFormat::Text
+            && options.verbosity >= Verbosity::Normal
+            && options.ci_folding.is_none()
+            && !options.dry_run
+            && run_targets
+            && io::stdout().is_terminal()
+        {
+            ProgressGuard::new()
+        } else {
+            None
+        };
+        let scheduled = &execution.scheduled;
+        let mut dependents = vec![Vec::new(); scheduled.len()];
+        for (i, target) in scheduled.iter().enumerate() {
+            for &dependency in &target.dependencies {
+                dependents[dependency].push((i, false));
+            }
+            for &previous in &target.after {
+                dependents[previous].push((i, true));
+            }
+        }
+        let interactive: Vec<bool> = scheduled
+            .iter()
+            .map(|s| {
+                self.get_scheduled_yake(s, &execution.yakes)
+                    .get_target_by_name(&s.target_name)
+                    .unwrap()
+                    .is_interactive()
+            })
+            .collect();
+
+        let scheduler = Mutex::new(Scheduler {
+            pending: scheduled
+                .iter()
+                .map(|s| s.dependencies.len() + s.after.len())
+                .collect(),
+            ready: (0..scheduled.len())
+                .filter(|&i| {
+                    run_targets
+                        && scheduled[i].dependencies.is_empty()
+                        && scheduled[i].after.is_empty()
+                })
+                .collect(),
+            running: 0,
+            exclusive: false,
+            failures: Vec::new(),
+            blocked: BTreeSet::new(),
+            results: vec![None; scheduled.len()],
+        });
+        let changed = Condvar::new();
+        let workers = execution.options.jobs.max(1).min(scheduled.len());
+        let keep_going = options.keep_going;
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    let mut state = scheduler.lock().unwrap();
+                    loop {
+                        let next = if (keep_going || state.failures.is_empty())
+                            && !state.exclusive
+                            && !interrupted()
+                        {
+                            let running = state.running;
+                            state
+                                .ready
+                                .iter()
+                                .cloned()
+                                .find(|&i| !interactive[i] || running == 0)
+                        } else {
+                            None
+                        };
+
+                        let i = match next {
+                            Some(i) => i,
+                            None => {
+                                let finished = state.ready.is_empty()
+                                    || (!keep_going && !state.failures.is_empty())
+                                    || interrupted();
+                                if state.running == 0 && finished {
+                                    break;
+                                }
+                                state = changed.wait(state).unwrap();
+                                continue;
+                            }
+                        };
+
+                        state.ready.remove(&i);
+                        state.running += 1;
+                        state.exclusive = interactive[i];
+                        drop(state);
+
+                        let target_name = &scheduled[i].target_name;
+                        if interactive[i] {
+                            pause_progress(true);
+                        }
+                        progress_started(target_name);
+                        let target_started = Instant::now();
+                        let mut target_result = self.get_target_result(
+                            &scheduled[i],
+                            execution,
+                            TargetStatus::Succeeded,
+                        );
+                        let result = self.run_scheduled_target(i, execution, &mut target_result);
+                        target_result.duration = target_started.elapsed().as_secs_f64();
+                        progress_finished(
+                            target_name,
+                            match result {
+                                Ok(Some(_)) => None,
+                                Ok(None) => Some(true),
+                                Err(_) => Some(false),
+                            },
+                        );
+                        if interactive[i] {
+                            pause_progress(false);
+                        }
+
+                        state = scheduler.lock().unwrap();
+                        state.running -= 1;
+                        state.exclusive = false;
+                        match result {
+                            Ok(skipped) => {
+                                if let Some(reason) = skipped {
+                                    report_skipped(
+                                        &execution.options.logger(),
+                                        &scheduled[i].target_name,
+                                        &reason,
+                                    );
+                                    target_result.status = TargetStatus::Skipped;
+                                    target_result.reason = Some(reason);
+                                }
+                                target_result.exit_code = Some(0);
+                                state.results[i] = Some(target_result);
+                                state.finish(i, true, &dependents);
+                            }
+                            Err(e) => {
+                                target_result.status = TargetStatus::Failed;
+                                target_result.exit_code = Some(e.exit_code());
+                                target_result.error = Some(e.to_string());
+                                state.results[i] = Some(target_result);
+                                state.failures.push((i, e));
+                                state.finish(i, false, &dependents);
+                            }
+                        }
+                        changed.notify_all();
+                    }
+                });
+            }
+        });
+
+        let scheduler = scheduler.into_inner().unwrap();
+        let mut failures = scheduler.failures;
+        failures.sort_by_key(|(i, _)| *i);
+        let targets = scheduler
+            .results
+            .into_iter()
+            .enumerate()
+            .map(|(i, result)| {
+                result.unwrap_or_else(|| {
+                    self.get_target_result(&scheduled[i], execution, TargetStatus::NotRun)
+                })
+            })
+            .collect();
+        ExecutionReport {
+            success: failures.is_empty(),
+            exit_code: failures_exit_code(&failures),
+            duration: started.elapsed().as_secs_f64(),
+            targets,
+            interrupted: false,
+            failures,
+        }
+    }
+
+    /// Result of a scheduled target with the given status, without details.
+    fn get_target_result(
+        &self,
+        scheduled: &ScheduledTarget,
+        execution: &Execution,
+        status: TargetStatus,
+    ) -> TargetResult {
+        TargetResult {
+            target: self.get_scheduled_name(scheduled),
+            file: self
+                .get_scheduled_yake(scheduled, &execution.yakes)
+                .get_source(),
+            status,
+            reason: None,
+            duration: 0.0,
+            exit_code: None,
+            error: None,
+            output: String::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Runs the commands of a scheduled target, unless they are skipped, the condition
+    /// of the target does not hold, it's outputs are up to date or it's fingerprint did
+    /// not change. Returns the reason if the target is skipped. Dependencies of a
+    /// skipped target run regardless. The commands and captured output of the target
+    /// are added to `result`.
+    fn run_scheduled_target(
+        &self,
+        index: usize,
+        execution: &Execution,
+        result: &mut TargetResult,
+    ) -> Result<Option<String>, YakeError> {
+        let scheduled = &execution.scheduled[index];
+        // the output of `set` commands is passed on to the targets depending on them,
+        // skipped targets included
+        let waited_for: Vec<usize> = scheduled
+            .dependencies
+            .iter()
+            .chain(&scheduled.after)
+            .cloned()
+            .collect();
+        let captured = execution.get_captured(&waited_for);
+        execution
+            .captured
+            .lock()
+            .unwrap()
+            .insert(index, captured.clone());
+        if scheduled.skip {
+            return Ok(Some("deps-only".to_string()));
+        }
+        let yake = self.get_scheduled_yake(scheduled, &execution.yakes);
+        let target = yake.get_target_by_name(&scheduled.target_name).unwrap();
+        let capturing;
+        let yake = if captured.is_empty() && !target.sets_variables() {
+            yake
+        } else {
+            capturing = yake.with_captured(captured);
+            &capturing
+        };
+        let target_dir = yake.get_target_base_dir(&scheduled.target_name);
+        if let Some(ref when) = target.when {
+            let envs = yake.get_target_env_vars(&scheduled.target_name)?;
+            if !Condition::parse(when)?.evaluate(&envs, &target_dir) {
+                return Ok(Some(format!("when: {}", when)));
+            }
+        }
+        if target.is_service() {
+            if let Some(pid) = running_pid(&yake.get_base_dir(), &scheduled.target_name) {
+                return Ok(Some(format!("running, pid {}", pid)));
+            }
+        }
+        let cached = !(execution.options.force || target.is_always_run());
+        if let (true, Some(outputs)) = (cached, &target.outputs) {
+            let inputs = target.inputs.clone().unwrap_or_default();
+            if is_up_to_date(&target_dir, &inputs, outputs)? {
+                return Ok(Some("up to date".to_string()));
+            }
+        }
+
+        let fingerprint = match target.inputs {
+            Some(_) => Some(yake.get_target_fingerprint(&scheduled.target_name, &target)?),
+            None => None,
+        };
+        let key = yake.get_fingerprint_key(&scheduled.target_name);
+        if let (true, Some(fingerprint)) = (cached, &fingerprint) {
+            let outputs = target.outputs.clone().unwrap_or_default();
+            if read_fingerprint(&yake.get_base_dir(), &key)?.as_ref() == Some(fingerprint)
+                && outputs_exist(&target_dir, &outputs)?
+            {
+                return Ok(Some("unchanged".to_string()));
+            }
+        }
+
+        // the output of targets which may run concurrently is tagged with their names
+        let tag = match execution.options.jobs {
+            0 | 1 => None,
+            _ if execution.scheduled.len() < 2 => None,
+            _ => {
+                let width = execution
+                    .scheduled
+                    .iter()
+                    .map(|scheduled| scheduled.target_name.len())
+                    .max()
+                    .unwrap_or(0);
+                Some(target_tag(&scheduled.target_name, index, width))
+            }
+        };
+        let run = yake.run_target(
+            &scheduled.target_name,
+            &target,
+            execution.options,
+            tag,
+            result,
+        );
+        execution
+            .captured
+            .lock()
+            .unwrap()
+            .insert(index, yake.captured.get());
+        run?;
+        if let Some(fingerprint) = fingerprint {
+            if !execution.options.dry_run {
+                write_fingerprint(&yake.get_base_dir(), &key, &fingerprint)?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Copy of the yake with env variables set by `set` commands.
+    fn with_captured(&self, captured: HashMap<String, String>) -> Yake {
+        let yake = self.clone();
+        *yake.captured.0.lock().unwrap() = captured;
+        yake
+    }
+
+    /// Name of a scheduled target in reports. Targets of cross-file dependencies are
+    /// qualified with the path of their Yakefile relative to the one of this yake, e.g.
+    /// `../shared/Yakefile:docker.login`.
+    fn get_scheduled_name(&self, scheduled: &ScheduledTarget) -> String {
+        match scheduled.yake {
+            Some(ref path) => {
+                let base_dir = self.get_base_dir();
+                let base_dir = fs::canonicalize(&base_dir).unwrap_or(base_dir);
+                format!(
+                    "{}:{}",
+                    relative_path(path, &base_dir).display(),
+                    scheduled.target_name
+                )
+            }
+            None => scheduled.target_name.clone(),
+        }
+    }
+
+    /// The yake a scheduled target belongs to, either this one or a yake loaded for a
+    /// cross-file dependency.
+    fn get_scheduled_yake<'a>(
+        &'a self,
+        scheduled: &ScheduledTarget,
+        yakes: &'a ExternalYakes,
+    ) -> &'a Yake {
+        match scheduled.yake {
+            Some(ref path) => &yakes[path],
+            None => self,
+        }
+    }
+
+    /// Runs the commands of a single target, without it's dependencies. The commands
+    /// that ran are added to `target_result`, along with their output with JSON output.
+    /// The output is prefixed with `tag` if given, except for interactive targets.
+    fn run_target(
+        &self,
+        target_name: &str,
+        target: &YakeTarget,
+        options: &ExecutionOptions,
+        tag: Option<String>,
+        target_result: &mut TargetResult,
+    ) -> Result<(), YakeError> {
+        if options.dry_run {
+            return Ok(self.print_target(target_name, target)?);
+        }
+        if !target.has_commands() {
+            return Ok(());
+        }
+        let before = self.expand_commands(target_name, &target.before)?;
+        let exec = self.get_target_commands(target_name, target)?;
+        let after = self.expand_commands(target_name, &target.after)?;
+        let (exec_offset, after_offset) = (before.len(), before.len() + exec.len());
+        let secrets = self.get_target_secrets(target_name)?;
+
+        let commands = Mutex::new(Vec::new());
+        let result = if target.is_interactive() {
+            let run = |hook: Vec<YakeCommand>, offset: usize| {
+                self.run_interactive_target(target_name, target, hook, offset, options, &commands)
+            };
+            run_with_hooks(
+                || run(before, 0),
+                || run(exec, exec_offset),
+                || run(after, after_offset),
+            )
+        } else {
+            let silent = target.is_silent() && !options.no_silent;
+            let reporter = TargetReporter::new(
+                target_name,
+                silent,
+                options.ci_folding,
+                options.logger(),
+                secrets.clone(),
+                tag,
+            );
+            let run = |hook: Vec<YakeCommand>, offset: usize| {
+                self.run_sequential_target(target_name, target, hook, offset, &reporter, &commands)
+            };
+            let result = run_with_hooks(
+                || run(before, 0),
+                || {
+                    if target.is_service() {
+                        self.start_service(target_name, exec, exec_offset, &reporter, &commands)
+                    } else if target.is_parallel() {
+                        self.run_parallel_target(
+                            target_name,
+                            target,
+                            options.jobs,
+                            exec,
+                            exec_offset,
+                            &reporter,
+                            &commands,
+                        )
+                    } else {
+                        run(exec, exec_offset)
+                    }
+                },
+                || run(after, after_offset),
+            );
+            target_result.output.push_str(&reporter.finish(&result));
+            result
+        };
+
+        let mut commands = commands.into_inner().unwrap();
+        commands.sort_by_key(|(i, _)| *i);
+        target_result
+            .commands
+            .extend(commands.into_iter().map(|(_, mut command)| {
+                command.command = mask_secrets(&command.command, &secrets);
+                command
+            }));
+        result.map_err(|e| match e {
+            YakeError::Failed { message, exit_code } => YakeError::Failed {
+                message: mask_secrets(&message, &secrets),
+                exit_code,
+            },
+            e => e,
+        })
+    }
+
+    /// Prints the env and the commands a target would run, for dry runs. Commands of
+    /// parallel targets are numbered, their stdin is shown as heredoc or redirect.
+    fn print_target(&self, target_name: &str, target: &YakeTarget) -> Result<(), String> {
+        if !target.has_commands() {
+            return Ok(());
+        }
+        let before = self.expand_commands(target_name, &target.before)?;
+        let commands = self.get_target_commands(target_name, target)?;
+        let after = self.expand_commands(target_name, &target.after)?;
+        let envs = self.get_target_env_vars(target_name)?;
+        let target_dir = self.get_target_base_dir(target_name);
+        for command in before.iter().chain(&commands).chain(&after) {
+            command.get_stdin(&target_dir, &envs)?;
+        }
+        let dir = self.get_target_dir(target_name)?;
+        let secrets = self.get_target_secrets(target_name)?;
+
+        println!(
+            "{} {}:",
+            "↪ Would execute".bold().blue(),
+            target_name.bold().green()
+        );
+        let mut names: Vec<&String> = envs.keys().collect();
+        names.sort();
+        for name in names {
+            println!(
+                "{}  export {}={}",
+                "┆".bold().green(),
+                name,
+                mask_value(&envs[name], &secrets)
+            );
+        }
+        if dir != self.get_base_dir() {
+            println!(
+                "{}  cd {}",
+                "┆".bold().green(),
+                shell_quote(&dir.to_string_lossy())
+            );
+        }
+        if let Some(container) = self.get_target_container(target_name) {
+            println!("{}  container: {}", "┆".bold().green(), container.image);
+        }
+        if let Some(remote) = self.get_target_remote(target_name) {
+            println!("{}  remote: {}", "┆".bold().green(), remote.host);
+        }
+        let print = |prefix: &str, command: &YakeCommand| {
+            let set = command
+                .get_output_var()
+                .map(|name| format!("set: {} = ", name))
+                .unwrap_or_default();
+            let (redirect, stdin) = match *command {
+                YakeCommand::Detailed(YakeCommandDetails {
+                    stdin: Some(ref stdin),
+                    ..
+                }) => (" <<'EOF'".to_string(), Some(expand_env_vars(stdin, &envs))),
+                YakeCommand::Detailed(YakeCommandDetails {
+                    stdin_file: Some(ref stdin_file),
+                    ..
+                }) => (format!(" < {}", shell_quote(stdin_file)), None),
+                _ => (String::new(), None),
+            };
+            println!(
+                "{}  {}{}{}{}",
+                "┆".bold().green(),
+                prefix,
+                set,
+                mask_secrets(command.get_command(), &secrets),
+                redirect
+            );
+            if let Some(stdin) = stdin {
+                for line in mask_secrets(&stdin, &secrets).lines().chain(Some("EOF")) {
+                    println!("{}  {}", "┆".bold().green(), line);
+                }
+            }
+        };
+        for command in &before {
+            print("before: ", command);
+        }
+        for (i, command) in commands.iter().enumerate() {
+            let prefix = if target.is_parallel() {
+                format!("[{}] ", i + 1)
+            } else {
+                String::new()
+            };
+            print(&prefix, command);
+        }
+        for command in &after {
+            print("after: ", command);
+        }
+        Ok(())
+    }
+
+    /// Runs commands of a target one after another, stopping at the first failing
+    /// command or once yake was interrupted. Commands are recorded from `offset` on.
+    fn run_sequential_target(
+        &self,
+        target_name: &str,
+        target: &YakeTarget,
+        target_commands: Vec<YakeCommand>,
+        offset: usize,
+        reporter: &TargetReporter,
+        commands: &CommandResults,
+    ) -> Result<(), YakeError> {
+        let interrupts_before = interrupts();
+        for (i, command) in target_commands.into_iter().enumerate() {
+            if interrupts() > interrupts_before {
+                return Err(YakeError::Failed {
+                    message: format!("Interrupted before command \"{}\"", command.get_command()),
+                    exit_code: INTERRUPT_EXIT_CODE,
+                });
+            }
+            let result = record_command(commands, offset + i, &command, || {
+                command.run_with_retries(
+                    target,
+                    || {
+                        report_command(reporter, &command, "");
+                        self.report_invocation(
+                            target_name,
+                            &command,
+                            "",
+                            reporter.verbosity(),
+                            |level, text| reporter.message(level, text),
+                        )?;
+                        // the output of `set` commands is not shown, but captured
+                        let output = Mutex::new(String::new());
+                        let status =
+                            self.run_command(target_name, &command, |stream, line| {
+                                match (command.get_output_var(), stream) {
+                                    (Some(_), Stream::Stdout) => {
+                                        output.lock().unwrap().push_str(line)
+                                    }
+                                    _ => report_line(reporter, stream, line, ""),
+                                }
+                            })?;
+                        command.check_exit_status(&status, target)?;
+                        if let Some(name) = command.get_output_var() {
+                            let output = output.into_inner().unwrap();
+                            self.captured
+                                .insert(name, output.trim_end_matches('\n').to_string());
+                        }
+                        Ok(())
+                    },
+                    |retry, retries, delay| {
+                        report_retry(reporter, &command, "", retry, retries, delay)
+                    },
+                )
+            });
+            match result {
+                Err(ref e) if command.ignores_errors() && !interrupted() => {
+                    report_ignored(reporter, &command, "", e)
+                }
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the command of a service in the background, in a process group of it's
+    /// own, so that it keeps running after yake ended and can be stopped along with
+    /// all processes it started. It's output is appended to the log file of the
+    /// service. Commands are recorded from `offset` on.
+    fn start_service(
+        &self,
+        target_name: &str,
+        target_commands: Vec<YakeCommand>,
+        offset: usize,
+        reporter: &TargetReporter,
+        commands: &CommandResults,
+    ) -> Result<(), YakeError> {
+        let base_dir = self.get_base_dir();
+        for (i, command) in target_commands.into_iter().enumerate() {
+            record_command(commands, offset + i, &command, || {
+                self.report_invocation(
+                    target_name,
+                    &command,
+                    "",
+                    reporter.verbosity(),
+                    |level, text| reporter.message(level, text),
+                )?;
+                let envs = self.get_target_env_vars(target_name)?;
+                let log = open_log_file(&base_dir, target_name)?;
+                let output = log
+                    .try_clone()
+                    .map_err(|e| format!("Unable to open the log file: {}", e))?;
+                // services get no input but the env on remote hosts
+                let stdin = self.get_remote_stdin(target_name)?;
+                let mut process = self.create_process(target_name, &command, envs)?;
+                new_process_group(&mut process);
+                let mut child = process
+                    .stdin(if stdin.is_some() {
+                        Stdio::piped()
+                    } else {
+                        Stdio::null()
+                    })
+                    .stdout(output)
+                    .stderr(log)
+                    .spawn()
+                    .map_err(|e| {
+                        format!(
+                            "failed to execute command \"{}\": {}",
+                            command.get_command(),
+                            e
+                        )
+                    })?;
+                if let (Some(pipe), Some(content)) = (child.stdin.take(), stdin) {
+                    write_stdin(pipe, &content)
+                        .map_err(|e| format!("Unable to write the env to ssh: {}", e))?;
+                }
+                write_pid(&base_dir, target_name, child.id())?;
+                let log_path = log_file(&base_dir, target_name);
+                reporter.message(
+                    Verbosity::Normal,
+                    &format!(
+                        "{} {} (pid {}), logging to {}",
+                        "↪ Started".bold().blue(),
+                        target_name.bold().green(),
+                        child.id(),
+                        log_path
+                            .strip_prefix(&base_dir)
+                            .unwrap_or(&log_path)
+                            .display()
+                    ),
+                );
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Runs the commands of a parallel target concurrently on up to `max_parallel`
+    /// threads, or `jobs` if the target has no `max_parallel`. Each line of output is
+    /// prefixed with the number of the command it belongs to, as output of the commands
+    /// interleaves, unless it is grouped. All commands run even if some of them fail,
+    /// the failures are reported together. Once yake was interrupted no more commands
+    /// are started. Commands are recorded from `offset` on.
+    #[allow(clippy::too_many_arguments)]
+    fn run_parallel_target(
+        &self,
+        target_name: &str,
+        target: &YakeTarget,
+        jobs: usize,
+        commands: Vec<YakeCommand>,
+        offset: usize,
+        target_reporter: &TargetReporter,
+        command_results: &CommandResults,
+    ) -> Result<(), YakeError> {
+        let jobs = match jobs {
+            0 => commands.len(),
+            jobs => jobs,
+        };
+        let workers = target
+            .max_parallel
+            .unwrap_or(jobs)
+            .max(1)
+            .min(commands.len());
+        let next = Mutex::new(commands.iter().enumerate());
+        let failures = Mutex::new(Vec::new());
+        let interrupts_before = interrupts();
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    if interrupts() > interrupts_before {
+                        break;
+                    }
+                    let (i, command) = match next.lock().unwrap().next() {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let prefix = format!("[{}] ", i + 1);
+                    let held = if target.groups_output() {
+                        Some(target_reporter.hold())
+                    } else {
+                        None
+                    };
+                    let reporter = held.as_ref().unwrap_or(target_reporter);
+                    let result = record_command(command_results, offset + i, command, || {
+                        command.run_with_retries(
+                            target,
+                            || {
+                                report_command(reporter, command, &prefix);
+                                self.report_invocation(
+                                    target_name,
+                                    command,
+                                    &prefix,
+                                    reporter.verbosity(),
+                                    |level, text| reporter.message(level, text),
+                                )?;
+                                self.run_command(target_name, command, |stream, line| {
+                                    report_line(reporter, stream, line, &prefix)
+                                })
+                                .and_then(|status| command.check_exit_status(&status, target))
+                            },
+                            |retry, retries, delay| {
+                                report_retry(reporter, command, &prefix, retry, retries, delay)
+                            },
+                        )
+                    });
+                    match result {
+                        Err(ref e) if command.ignores_errors() && !interrupted() => {
+                            report_ignored(reporter, command, &prefix, e)
+                        }
+                        Err(e) => failures.lock().unwrap().push((i, e)),
+                        Ok(()) => {}
+                    }
+                    if let Some(held) = held {
+                        target_reporter.release(held);
+                    }
+                });
+            }
+        });
+
+        join_failures(failures.into_inner().unwrap())
+    }
+
+    /// Runs commands of an interactive target with the terminal attached. Neither the
+    /// output nor interrupts are handled by yake while the commands run. With JSON
+    /// output, everything the commands and yake write to stdout goes to stderr, to keep
+    /// stdout for the JSON document. Commands are recorded from `offset` on.
+    fn run_interactive_target(
+        &self,
+        target_name: &str,
+        target: &YakeTarget,
+        target_commands: Vec<YakeCommand>,
+        offset: usize,
+        options: &ExecutionOptions,
+        commands: &CommandResults,
+    ) -> Result<(), YakeError> {
+        let logger = options.logger();
+        let secrets = self.get_target_secrets(target_name)?;
+        let notify = |text: String| logger.log(Verbosity::Normal, &mask_secrets(&text, &secrets));
+        for (i, command) in target_commands.into_iter().enumerate() {
+            notify(format!(
+                "{} {}",
+                "↪ Interactive".bold().blue(),
+                command.get_command().bold().green()
+            ));
+            let envs = self.get_target_env_vars(target_name)?;
+            let result = record_command(commands, offset + i, &command, || {
+                command.run_with_retries(
+                    target,
+                    || {
+                        self.report_invocation(
+                            target_name,
+                            &command,
+                            "",
+                            options.verbosity,
+                            |level, text| logger.log(level, text),
+                        )?;
+                        let mut process =
+                            self.create_process(target_name, &command, envs.clone())?;
+                        reset_interrupts(&mut process);
+                        if options.output == OutputFormat::Json {
+                            process.stdout(io::stderr());
+                        }
+
+                        let _guard = InterruptGuard::new();
+                        let status = process.status().map_err(|e| {
+                            format!(
+                                "failed to execute command \"{}\": {}",
+                                command.get_command(),
+                                e
+                            )
+                        })?;
+                        command.check_exit_status(&status, target)
+                    },
+                    |retry, retries, delay| {
+                        notify(format!(
+                            "{} {} in {} ({} of {})",
+                            "↪ Retrying".bold().yellow(),
+                            command.get_command().bold().green(),
+                            format_duration(delay),
+                            retry,
+                            retries
+                        ))
+                    },
+                )
+            });
+            match result {
+                Err(ref e) if command.ignores_errors() => notify(format!(
+                    "{} {}: {}",
+                    "↪ Ignoring failure of".bold().yellow(),
+                    command.get_command().bold().green(),
+                    e
+                )),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Commands of a target with `{{VAR}}` placeholders resolved. Placeholders refer to
+    /// env variables of the target, the `meta.doc` and `meta.version` values,
+    /// `ARGS`, the arguments given after `--` quoted for the shell, and the built-in
+    /// values like `git.sha`, see `builtin_value`.
+    /// Targets in script mode have a single command with all lines.
+    fn get_target_commands(
+        &self,
+        target_name: &str,
+        target: &YakeTarget,
+    ) -> Result<Vec<YakeCommand>, String> {
+        let commands = self.expand_commands(target_name, &target.exec)?;
+        if !(target.is_script_mode() || target.is_service()) || commands.is_empty() {
+            return Ok(commands);
+        }
+        let lines: Vec<&str> = commands.iter().map(YakeCommand::get_command).collect();
+        Ok(vec![YakeCommand::Plain(lines.join("\n"))])
+    }
+
+    /// Commands of `exec`, `before` or `after` of a target with placeholders resolved,
+    /// see `get_target_commands`, in their inline `stdin` as well. Commands with
+    /// variants for operating systems are replaced by the one for this system, or left
+    /// out if there is none.
+    pub fn expand_commands(
+        &self,
+        target_name: &str,
+        commands: &Option<Vec<YakeCommand>>,
+    ) -> Result<Vec<YakeCommand>, String> {
+        let mut values = self.get_target_env_vars(target_name)?;
+        values.insert("meta.doc".to_string(), self.meta.doc.clone());
+        values.insert("meta.version".to_string(), self.meta.version.clone());
+        let args: Vec<String> = self.args.iter().map(|arg| shell_quote(arg)).collect();
+        values.insert("ARGS".to_string(), args.join(" "));
+        let dir = self.get_target_base_dir(target_name);
+
+        commands
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter(|command| command.runs_on_this_os())
+            .map(|command| {
+                let expand = |text: &str| {
+                    expand_builtins(text, &dir, &values, &self.builtins)
+                        .and_then(|text| expand_placeholders(&text, &values))
+                        .map_err(|e| format!("{} in command \"{}\"", e, command.get_command()))
+                };
+                let mut expanded = command.with_command(expand(command.get_command())?);
+                if let YakeCommand::Detailed(ref mut details) = expanded {
+                    if let Some(ref mut stdin) = details.stdin {
+                        *stdin = expand(stdin)?;
+                    }
+                }
+                Ok(expanded)
+            })
+            .collect()
+    }
+
+    /// Fingerprint of a target with `inputs`, over it's input files, commands, shell,
+    /// working directory and env.
+    fn get_target_fingerprint(
+        &self,
+        target_name: &str,
+        target: &YakeTarget,
+    ) -> Result<String, String> {
+        let mut commands = self.get_target_shell(target_name);
+        commands.extend(self.get_target_shell_opts(target_name));
+        commands.extend(self.get_target_cwd(target_name).map(|(cwd, _)| cwd));
+        commands.extend(
+            self.get_target_container(target_name)
+                .map(|container| format!("{:?}", container)),
+        );
+        commands.extend(
+            self.get_target_remote(target_name)
+                .map(|remote| format!("{:?}", remote)),
+        );
+        for command in self
+            .expand_commands(target_name, &target.before)?
+            .into_iter()
+            .chain(self.get_target_commands(target_name, target)?)
+            .chain(self.expand_commands(target_name, &target.after)?)
+        {
+            commands.push(command.get_command().to_string());
+        }
+        let envs = self.get_target_env_vars(target_name)?;
+        let files = expand_patterns(
+            &self.get_target_base_dir(target_name),
+            target.inputs.as_deref().unwrap_or_default(),
+        )?;
+        fingerprint(&commands, &envs, &files)
+    }
+
+    /// Key of a target within the fingerprints file, Yakefiles in the same directory
+    /// share it.
+    fn get_fingerprint_key(&self, target_name: &str) -> String {
+        let file_name = self
+            .get_source()
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        format!("{}:{}", file_name, target_name)
+    }
+
+    /// Shell running the commands of a target. Targets without a shell use the one of
+    /// their closest parent target, then the one of the meta data.
+    pub fn get_target_shell(&self, target_name: &str) -> Vec<String> {
+        let parts: Vec<&str> = target_name.split('.').collect();
+        (1..=parts.len())
+            .rev()
+            .filter_map(|i| self.get_target_by_name(&parts[..i].join(".")))
+            .find_map(|target| target.shell)
+            .or_else(|| self.meta.shell.clone())
+            .unwrap_or_else(|| {
+                if self.get_target_container(target_name).is_some()
+                    || self.get_target_remote(target_name).is_some()
+                {
+                    vec!["sh".to_string(), "-c".to_string()]
+                } else {
+                    default_shell()
+                }
+            })
+    }
+
+    /// Shell options of a target, the ones of the target or of it's closest parent
+    /// target, then the ones of the meta data.
+    pub fn get_target_shell_opts(&self, target_name: &str) -> Option<String> {
+        let parts: Vec<&str> = target_name.split('.').collect();
+        (1..=parts.len())
+            .rev()
+            .filter_map(|i| self.get_target_by_name(&parts[..i].join(".")))
+            .find_map(|target| target.shell_opts)
+            .or_else(|| self.meta.shell_opts.clone())
+    }
+
+    /// Container running the commands of a target, the one of the target or of it's
+    /// closest parent target.
+    pub fn get_target_container(&self, target_name: &str) -> Option<YakeContainer> {
+        let parts: Vec<&str> = target_name.split('.').collect();
+        (1..=parts.len())
+            .rev()
+            .filter_map(|i| self.get_target_by_name(&parts[..i].join(".")))
+            .find_map(|target| target.container)
+    }
+
+    /// Host running the commands of a target, the one of the target or of it's closest
+    /// parent target.
+    pub fn get_target_remote(&self, target_name: &str) -> Option<YakeRemote> {
+        let parts: Vec<&str> = target_name.split('.').collect();
+        (1..=parts.len())
+            .rev()
+            .filter_map(|i| self.get_target_by_name(&parts[..i].join(".")))
+            .find_map(|target| target.remote)
+    }
+
+    /// Program and arguments running a command of a target, the shell of the target
+    /// given the command, preceded by the shell options, within `docker run` for
+    /// targets with a container or `ssh` for targets with a remote host. Env variables
+    /// are passed on to the container by name only, so their values do not show up in
+    /// the arguments.
+    fn get_invocation(
+        &self,
+        target_name: &str,
+        command: &YakeCommand,
+    ) -> Result<Vec<String>, String> {
+        let mut shell = self.get_target_shell(target_name);
+        shell.push(match self.get_target_shell_opts(target_name) {
+            Some(opts) => format!("{}\n{}", opts, command.get_command()),
+            None => command.get_command().to_string(),
+        });
+        let container = match (
+            self.get_target_container(target_name),
+            self.get_target_remote(target_name),
+        ) {
+            (Some(_), Some(_)) => {
+                return Err(format!(
+                    "Target {} must not run both in a container and on a remote host",
+                    target_name
+                ))
+            }
+            (Some(container), None) => container,
+            (None, Some(remote)) => return self.get_remote_invocation(target_name, remote, shell),
+            (None, None) => return Ok(shell),
+        };
+
+        let dir = self.get_target_dir(target_name)?;
+        let dir = fs::canonicalize(&dir).unwrap_or(dir);
+        let workdir = container
+            .workdir
+            .unwrap_or_else(|| DEFAULT_CONTAINER_WORKDIR.to_string());
+        let mut invocation: Vec<String> = ["docker", "run", "--rm", "--interactive"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        if self
+            .get_target_by_name(target_name)
+            .is_some_and(|target| target.is_interactive())
+        {
+            invocation.push("--tty".to_string());
+        }
+        invocation.push("--volume".to_string());
+        invocation.push(format!("{}:{}", dir.display(), workdir));
+        invocation.push("--workdir".to_string());
+        invocation.push(workdir);
+        let base_dir = self.get_target_base_dir(target_name);
+        for volume in container.volumes.unwrap_or_default() {
+            invocation.push("--volume".to_string());
+            invocation.push(resolve_volume(&base_dir, &volume));
+        }
+        let mut names: Vec<String> = self.get_target_env_vars(target_name)?.into_keys().collect();
+        names.sort();
+        for name in names {
+            invocation.push("--env".to_string());
+            invocation.push(name);
+        }
+        invocation.extend(container.args.unwrap_or_default());
+        invocation.push(container.image);
+        invocation.extend(shell);
+        Ok(invocation)
+    }
+
+    /// Working directory of a target, the closest one of it and it's parent targets.
+    /// Returned as given and relative to the Yakefile of the target defining it.
+    fn get_target_cwd(&self, target_name: &str) -> Option<(String, PathBuf)> {
+        let parts: Vec<&str> = target_name.split('.').collect();
+        (1..=parts.len()).rev().find_map(|i| {
+            let name = parts[..i].join(".");
+            let cwd = self.get_target_by_name(&name)?.cwd?;
+            let dir = self.get_target_base_dir(&name).join(&cwd);
+            Some((cwd, dir))
+        })
+    }
+
+    /// Directory the commands of a target run in. Fails if the `cwd` of the target does
+    /// not exist.
+    pub fn get_target_dir(&self, target_name: &str) -> Result<PathBuf, String> {
+        let (cwd, dir) = match self.get_target_cwd(target_name) {
+            Some(cwd) => cwd,
+            None => return Ok(self.get_target_base_dir(target_name)),
+        };
+        if !dir.is_dir() {
+            return Err(format!(
+                "Working directory \"{}\" of target {} does not exist",
+                cwd, target_name
+            ));
+        }
+        Ok(dir)
+    }
+
+    /// Arguments of `ssh` running the shell invocation of a command on a remote host,
+    /// after exporting the env of the target and changing to the directory on the host.
+    /// The script exporting the env is written to stdin ahead of the input of the
+    /// command, see `get_process_stdin`, so that the values do not show up in the
+    /// arguments of processes on either host. Interactive targets keep stdin for the
+    /// terminal and get the env in the arguments, so they must not have secrets.
+    fn get_remote_invocation(
+        &self,
+        target_name: &str,
+        remote: YakeRemote,
+        shell: Vec<String>,
+    ) -> Result<Vec<String>, String> {
+        let interactive = self
+            .get_target_by_name(target_name)
+            .is_some_and(|target| target.is_interactive());
+        let mut invocation = vec!["ssh".to_string()];
+        if interactive {
+            invocation.push("-t".to_string());
+        } else {
+            // a password prompt would wait for input no one gives
+            invocation.extend(
+                ["-T", "-o", "BatchMode=yes"]
+                    .iter()
+                    .map(|arg| arg.to_string()),
+            );
+        }
+        if let Some(port) = remote.port {
+            invocation.push("-p".to_string());
+            invocation.push(port.to_string());
+        }
+        if let Some(key) = remote.key {
+            let key = if key.starts_with('~') {
+                key
+            } else {
+                let path = self.get_target_base_dir(target_name).join(key);
+                path.display().to_string()
+            };
+            invocation.push("-i".to_string());
+            invocation.push(key);
+        }
+        invocation.push(match remote.user {
+            Some(user) => format!("{}@{}", user, remote.host),
+            None => remote.host,
+        });
+
+        let exports = self.get_remote_exports(target_name)?;
+        let mut script = Vec::new();
+        if interactive {
+            let secrets = self.get_target_secrets(target_name)?;
+            let envs = self.get_target_env_vars(target_name)?;
+            if envs.values().any(|value| secrets.contains(value)) {
+                return Err(format!(
+                    "Interactive target {} must not pass secrets to a remote host",
+                    target_name
+                ));
+            }
+            script.extend(exports);
+        } else if !exports.is_empty() {
+            // dd reads byte by byte, leaving the input of the command on stdin
+            script.push(format!(
+                "eval \"$(dd bs=1 count={} 2>/dev/null)\"",
+                exports.join("\n").len()
+            ));
+        }
+        if let Some(dir) = remote.dir {
+            script.push(format!("cd {}", shell_quote(&dir)));
+        }
+        let shell: Vec<String> = shell.iter().map(|arg| shell_quote(arg)).collect();
+        script.push(shell.join(" "));
+        invocation.push(script.join(" && "));
+        Ok(invocation)
+    }
+
+    /// Commands exporting the env of a target on a remote host, sorted by name.
+    fn get_remote_exports(&self, target_name: &str) -> Result<Vec<String>, String> {
+        let envs = self.get_target_env_vars(target_name)?;
+        let mut names: Vec<&String> = envs.keys().collect();
+        names.sort();
+        Ok(names
+            .into_iter()
+            .map(|name| format!("export {}={}", name, shell_quote(&envs[name])))
+            .collect())
+    }
+
+    /// Script exporting the env of a target on a remote host which is written to the
+    /// stdin of `ssh`, see `get_remote_invocation`. `None` for other targets and
+    /// interactive ones.
+    fn get_remote_stdin(&self, target_name: &str) -> Result<Option<Vec<u8>>, String> {
+        let interactive = self
+            .get_target_by_name(target_name)
+            .is_some_and(|target| target.is_interactive());
+        if interactive || self.get_target_remote(target_name).is_none() {
+            return Ok(None);
+        }
+        let exports = self.get_remote_exports(target_name)?;
+        Ok(Some(exports.join("\n").into_bytes()).filter(|script| !script.is_empty()))
+    }
+
+    /// Content written to the stdin of the process of a command, the script exporting
+    /// the env on a remote host followed by the stdin of the command.
+    fn get_process_stdin(
+        &self,
+        target_name: &str,
+        command: &YakeCommand,
+        envs: &HashMap<String, String>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let stdin = command.get_stdin(&self.get_target_base_dir(target_name), envs)?;
+        Ok(match (self.get_remote_stdin(target_name)?, stdin) {
+            (Some(mut script), Some(stdin)) => {
+                script.extend(stdin);
+                Some(script)
+            }
+            (script, stdin) => script.or(stdin),
+        })
+    }
+
+    /// Reports how a command is run, the working directory and shell invocation with
+    /// verbose output and the env of the command in addition with debug output.
+    fn report_invocation<F>(
+        &self,
+        target_name: &str,
+        command: &YakeCommand,
+        prefix: &str,
+        verbosity: Verbosity,
+        report: F,
+    ) -> Result<(), String>
+    where
+        F: Fn(Verbosity, &str),
+    {
+        if verbosity < Verbosity::Verbose {
+            return Ok(());
+        }
+        // the env is part of the invocation for interactive targets on remote hosts,
+        // quoted
+        let secrets = self.get_target_secrets(target_name)?;
+        let mut quoted_secrets: Vec<String> = secrets.iter().map(|s| shell_quote(s)).collect();
+        quoted_secrets.extend(secrets.iter().cloned());
+        let invocation: Vec<String> = self
+            .get_invocation(target_name, command)?
+            .iter()
+            .map(|arg| shell_quote(&mask_secrets(arg, &quoted_secrets)))
+            .collect();
+        let line = |name: &str, value: &str| format!("  {}{} {}", prefix, name.dimmed(), value);
+
+        report(
+            Verbosity::Verbose,
+            &line(
+                "cwd:",
+                &self.get_target_dir(target_name)?.display().to_string(),
+            ),
+        );
+        report(Verbosity::Verbose, &line("shell:", &invocation.join(" ")));
+        if verbosity >= Verbosity::Debug {
+            let envs = self.get_target_env_vars(target_name)?;
+            let mut names: Vec<&String> = envs.keys().collect();
+            names.sort();
+            for name in names {
+                let value = format!("{}={}", name, mask_value(&envs[name], &secrets));
+                report(Verbosity::Debug, &line("env:", &value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the shell process for a command of a target. Containers and remote hosts
+    /// never inherit the env of yake, so `docker` and `ssh` always do.
+    fn create_process(
+        &self,
+        target_name: &str,
+        command: &YakeCommand,
+        envs: HashMap<String, String>,
+    ) -> Result<Command, String> {
+        let invocation = self.get_invocation(target_name, command)?;
+        let mut process = Command::new(&invocation[0]);
+        if !self.meta.inherit_env.unwrap_or(true)
+            && self.get_target_container(target_name).is_none()
+            && self.get_target_remote(target_name).is_none()
+        {
+            process.env_clear();
+        }
+        process
+            .args(&invocation[1..])
+            .current_dir(self.get_target_dir(target_name)?)
+            .envs(envs);
+        Ok(process)
+    }
+
+    /// Runs a single command of a target, passing each line of it's output to `on_line`
+    /// as soon as it is written.
+    fn run_command<F>(
+        &self,
+        target_name: &str,
+        command: &YakeCommand,
+        on_line: F,
+    ) -> Result<ExitStatus, YakeError>
+    where
+        F: Fn(Stream, &str) + Sync,
+    {
+        let envs = self.get_target_env_vars(target_name)?;
+        let stdin = self.get_process_stdin(target_name, command, &envs)?;
+        let timeout = match self
+            .get_target_by_name(target_name)
+            .and_then(|target| command.get_timeout(&target).map(str::to_string))
+        {
+            Some(timeout) => Some((parse_duration(&timeout)?, timeout)),
+            None => None,
+        };
+
+        // commands run in a process group of their own, interrupts are forwarded to
+        // the whole group and yake waits for it
+        let mut process = self.create_process(target_name, command, envs)?;
+        new_process_group(&mut process);
+        let interrupts_before = interrupts();
+        let mut child = process
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                format!(
+                    "failed to execute command \"{}\": {}",
+                    command.get_command(),
+                    e
+                )
+            })?;
+
+        // write stdin from a separate thread, the child might block on a full stdout
+        // pipe before it has consumed all of it's input.
+        let writer = match (child.stdin.take(), stdin) {
+            (Some(pipe), Some(content)) => Some(thread::spawn(move || write_stdin(pipe, &content))),
+            _ => None,
+        };
+
+        // read stdout and stderr concurrently, either of them might fill up and block
+        // the child otherwise.
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let on_line = &on_line;
+        let child_id = child.id();
+        let _forward = ForwardInterrupts::new(child_id, interrupts_before);
+        let timed_out = AtomicBool::new(false);
+        let (finished, wait_finished) = mpsc::channel::<()>();
+        let status = thread::scope(|scope| {
+            // kills the command once the timeout passed, unless it finished before.
+            // Processes started by the command might keep the output pipes open, so the
+            // whole process group is killed.
+            if let Some((duration, _)) = timeout {
+                let timed_out = &timed_out;
+                scope.spawn(move || {
+                    if wait_finished.recv_timeout(duration) == Err(RecvTimeoutError::Timeout) {
+                        timed_out.store(true, Ordering::SeqCst);
+                        kill_process_group(child_id);
+                    }
+                });
+            }
+            scope.spawn(move || read_lines(stderr, |line| on_line(Stream::Stderr, line)));
+            read_lines(stdout, |line| on_line(Stream::Stdout, line));
+            let status = child.wait();
+            drop(finished);
+            status
+        })
+        .map_err(|e| {
+            format!(
+                "failed to execute command \"{}\": {}",
+                command.get_command(),
+                e
+            )
+        })?;
+
+        if let Some((_, timeout)) = timeout {
+            if timed_out.load(Ordering::SeqCst) {
+                return Err(YakeError::Failed {
+                    message: format!(
+                        "Command \"{}\" timed out after {}",
+                        command.get_command(),
+                        timeout
+                    ),
+                    exit_code: TIMEOUT_EXIT_CODE,
+                });
+            }
+        }
+        if interrupts() > interrupts_before {
+            return Err(YakeError::Failed {
+                message: format!("Command \"{}\" was interrupted", command.get_command()),
+                exit_code: INTERRUPT_EXIT_CODE,
+            });
+        }
+
+        if let Some(writer) = writer {
+            writer.join().expect("stdin writer panicked").map_err(|e| {
+                format!(
+                    "failed to write stdin of \"{}\": {}",
+                    command.get_command(),
+                    e
+                )
+            })?;
+        }
+
+        Ok(status)
+    }
+}
+
+/// Implementation for a YakeParam.
+impl YakeParam {
+    /// Gets the name of the parameter.
+    pub fn get_name(&self) -> &str {
+        match *self {
+            YakeParam::Plain(ref name) => name,
+            YakeParam::Detailed(ref details) => &details.name,
+        }
+    }
+
+    /// Gets the value of the parameter if it is not given.
+    fn get_default(&self) -> Option<&str> {
+        match *self {
+            YakeParam::Plain(_) => None,
+            YakeParam::Detailed(ref details) => details.default.as_deref(),
+        }
+    }
+
+    /// Checks whether the parameter has to be given.
+    fn is_required(&self) -> bool {
+        match *self {
+            YakeParam::Plain(_) => true,
+            YakeParam::Detailed(ref details) => {
+                details.required.unwrap_or(details.default.is_none())
+            }
+        }
+    }
+}
+
+/// Implementation for a YakeRequiredEnv.
+impl YakeRequiredEnv {
+    /// Gets the name of the env variable.
+    pub fn get_name(&self) -> &str {
+        match *self {
+            YakeRequiredEnv::Plain(ref name) => name,
+            YakeRequiredEnv::Detailed(ref details) => &details.name,
+        }
+    }
+
+    /// Gets the value suggested when the user is asked for the variable.
+    fn get_default(&self) -> Option<&str> {
+        match *self {
+            YakeRequiredEnv::Plain(_) => None,
+            YakeRequiredEnv::Detailed(ref details) => details.default.as_deref(),
+        }
+    }
+
+    /// Checks whether the value of the variable is a secret.
+    fn is_secret(&self) -> bool {
+        match *self {
+            YakeRequiredEnv::Plain(_) => false,
+            YakeRequiredEnv::Detailed(ref details) => details.secret.unwrap_or(false),
+        }
+    }
+}
+
+/// Implementation for a YakeCommand.
+impl YakeCommand {
+    /// Gets the command line to execute.
+    pub fn get_command(&self) -> &str {
+        match *self {
+            YakeCommand::Plain(ref cmd) => cmd,
+            YakeCommand::Detailed(ref details) => &details.cmd,
+            YakeCommand::Set(ref set) => set
+                .set
+                .split_once('=')
+                .map_or(&set.set, |(_, cmd)| cmd.trim()),
+            YakeCommand::Os(ref os) => os.get_command().unwrap_or_default(),
+            YakeCommand::Use(_) => "",
+        }
+    }
+
+    /// Checks whether the command runs on the operating system yake runs on, which is
+    /// only not the case for a `YakeOsCommand` without a variant for it.
+    pub fn runs_on_this_os(&self) -> bool {
+        match *self {
+            YakeCommand::Os(ref os) => os.get_command().is_some(),
+            _ => true,
+        }
+    }
+
+    /// Name of the env variable the output of a `set` command is set as.
+    pub fn get_output_var(&self) -> Option<&str> {
+        match *self {
+            YakeCommand::Set(ref set) => set.set.split_once('=').map(|(name, _)| name.trim()),
+            _ => None,
+        }
+    }
+
+    /// Copy of the command with another command line.
+    fn with_command(&self, cmd: String) -> YakeCommand {
+        match *self {
+            YakeCommand::Plain(_) | YakeCommand::Os(_) | YakeCommand::Use(_) => {
+                YakeCommand::Plain(cmd)
+            }
+            YakeCommand::Detailed(ref details) => YakeCommand::Detailed(YakeCommandDetails {
+                cmd,
+                ..details.clone()
+            }),
+            YakeCommand::Set(_) => YakeCommand::Set(YakeSetCommand {
+                set: format!("{} = {}", self.get_output_var().unwrap_or_default(), cmd),
+            }),
+        }
+    }
+
+    /// Checks the command options for conflicts with each other and with the options
+    /// of the target.
+    pub fn validate(&self, target: &YakeTarget) -> Result<(), String> {
+        if let YakeCommand::Use(ref use_command) = *self {
+            return Err(format!(
+                "Command \"use: {}\" must be expanded before running it",
+                use_command.snippet
+            ));
+        }
+        if let YakeCommand::Set(ref set) = *self {
+            let name = self.get_output_var().unwrap_or_default();
+            let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+            if name.is_empty()
+                || name.starts_with(|c: char| c.is_ascii_digit())
+                || !name.chars().all(is_name_char)
+            {
+                return Err(format!(
+                    "Command \"set: {}\" must be written as \"set: NAME = command\"",
+                    set.set
+                ));
+            }
+            if target.is_parallel() || target.is_interactive() {
+                return Err(format!(
+                    "Command \"set: {}\" must not be a command of a parallel or interactive target",
+                    set.set
+                ));
+            }
+        }
+        if let YakeCommand::Os(ref os) = *self {
+            if *os == YakeOsCommand::default() {
+                return Err(
+                    "Command variants must give at least one of linux, macos, windows or default"
+                        .to_string(),
+                );
+            }
+        }
+        if let YakeCommand::Detailed(ref details) = *self {
+            if details.stdin.is_some() && details.stdin_file.is_some() {
+                return Err(format!(
+                    "Command \"{}\" must not define both stdin and stdin_file",
+                    details.cmd
+                ));
+            }
+            if target.is_interactive() && (details.stdin.is_some() || details.stdin_file.is_some())
+            {
+                return Err(format!(
+                    "Command \"{}\" of an interactive target must not define stdin or stdin_file",
+                    details.cmd
+                ));
+            }
+            if let Some(ref timeout) = details.timeout {
+                if target.is_interactive() {
+                    return Err(format!(
+                        "Command \"{}\" of an interactive target must not have a timeout",
+                        details.cmd
+                    ));
+                }
+                parse_duration(timeout)?;
+            }
+            if let Some(ref retry_delay) = details.retry_delay {
+                parse_duration(retry_delay)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the exit status against the expected exit codes of the command or, if
+    /// not given, the ones of it's target. Only 0 is expected by default.
+    fn check_exit_status(&self, status: &ExitStatus, target: &YakeTarget) -> Result<(), YakeError> {
+        let expected: &[i32] = match *self {
+            YakeCommand::Detailed(YakeCommandDetails {
+                expected_exit_codes: Some(ref codes),
+                ..
+            }) => codes,
+            _ => match target.expected_exit_codes {
+                Some(ref codes) => codes,
+                None => &[0],
+            },
+        };
+
+        let message = match status.code() {
+            Some(code) if expected.contains(&code) => return Ok(()),
+            Some(code) => format!(
+                "Command \"{}\" exited with code {}, expected one of {:?}",
+                self.get_command(),
+                code,
+                expected
+            ),
+            None => format!(
+                "Command \"{}\" was terminated by a signal, expected one of exit codes {:?}",
+                self.get_command(),
+                expected
+            ),
+        };
+        Err(YakeError::Failed {
+            message,
+            exit_code: command_exit_code(exit_code(status)),
+        })
+    }
+
+    /// Gets the timeout of the command or, if not given, the one of it's target.
+    fn get_timeout<'a>(&'a self, target: &'a YakeTarget) -> Option<&'a str> {
+        match *self {
+            YakeCommand::Detailed(YakeCommandDetails {
+                timeout: Some(ref timeout),
+                ..
+            }) => Some(timeout),
+            _ => target.timeout.as_deref(),
+        }
+    }
+
+    /// Checks whether a failure of the command is reported, but does not fail the
+    /// target.
+    pub fn ignores_errors(&self) -> bool {
+        match *self {
+            YakeCommand::Detailed(ref details) => details.ignore_errors.unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Runs the command by calling `run` until it succeeds or the `retries` of the
+    /// command or it's target are used up. The delay between the attempts starts at
+    /// `retry_delay` and doubles with each retry. `on_retry` is called before each
+    /// retry with the number of the retry, the number of retries and the delay.
+    fn run_with_retries<F, R>(
+        &self,
+        target: &YakeTarget,
+        mut run: F,
+        on_retry: R,
+    ) -> Result<(), YakeError>
+    where
+        F: FnMut() -> Result<(), YakeError>,
+        R: Fn(u32, u32, Duration),
+    {
+        let details = match *self {
+            YakeCommand::Detailed(ref details) => Some(details),
+            _ => None,
+        };
+        let retries = details
+            .and_then(|details| details.retries)
+            .or(target.retries)
+            .unwrap_or(0);
+        let mut delay = match details
+            .and_then(|details| details.retry_delay.as_ref())
+            .or(target.retry_delay.as_ref())
+        {
+            Some(delay) => parse_duration(delay)?,
+            None => DEFAULT_RETRY_DELAY,
+        };
+
+        let mut retry = 0;
+        loop {
+            match run() {
+                Err(_) if retry < retries && !interrupted() => {
+                    retry += 1;
+                    on_retry(retry, retries, delay);
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Gets the content for the command's stdin, if any. Inline content gets the
+    /// environment variables expanded, files are read relative to `base_dir`.
+    fn get_stdin(
+        &self,
+        base_dir: &Path,
+        envs: &HashMap<String, String>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let details = match *self {
+            YakeCommand::Detailed(ref details) => details,
+            _ => return Ok(None),
+        };
+
+        if let Some(ref stdin) = details.stdin {
+            return Ok(Some(expand_env_vars(stdin, envs).into_bytes()));
+        }
+
+        match details.stdin_file {
+            Some(ref stdin_file) => {
+                let path = base_dir.join(stdin_file);
+                fs::read(&path)
+                    .map(Some)
+                    .map_err(|e| format!("Unable to read stdin_file {}: {}", path.display(), e))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reports the start of a command.
+fn report_command(reporter: &TargetReporter, command: &YakeCommand, prefix: &str) {
+    reporter.message(
+        Verbosity::Normal,
+        &format!(
+            "{} {}{}:",
+            "↪ Executing".bold().blue(),
+            prefix,
+            command.get_command().bold().green()
+        ),
+    );
+}
+
+/// Reports that a failed command is run again after a delay.
+fn report_retry(
+    reporter: &TargetReporter,
+    command: &YakeCommand,
+    prefix: &str,
+    retry: u32,
+    retries: u32,
+    delay: Duration,
+) {
+    reporter.message(
+        Verbosity::Normal,
+        &format!(
+            "{} {}{} in {} ({} of {})",
+            "↪ Retrying".bold().yellow(),
+            prefix,
+            command.get_command().bold().green(),
+            format_duration(delay),
+            retry,
+            retries
+        ),
+    );
+}
+
+/// Runs a command by calling `run` and records it's duration and exit code.
+fn record_command<F>(
+    commands: &CommandResults,
+    i: usize,
+    command: &YakeCommand,
+    run: F,
+) -> Result<(), YakeError>
+where
+    F: FnOnce() -> Result<(), YakeError>,
+{
+    let started = Instant::now();
+    let result = run();
+    let command_result = CommandResult {
+        command: command.get_command().to_string(),
+        duration: started.elapsed().as_secs_f64(),
+        exit_code: match result {
+            Ok(()) => 0,
+            Err(ref e) => e.exit_code(),
+        },
+    };
+    commands.lock().unwrap().push((i, command_result));
+    result
+}
+
+/// Reports the failure of a command whose errors are ignored.
+fn report_ignored(
+    reporter: &TargetReporter,
+    command: &YakeCommand,
+    prefix: &str,
+    error: &YakeError,
+) {
+    reporter.message(
+        Verbosity::Normal,
+        &format!(
+            "{} {}{}: {}",
+            "↪ Ignoring failure of".bold().yellow(),
+            prefix,
+            command.get_command().bold().green(),
+            error
+        ),
+    );
+}
+
+/// Shell running commands if none is configured, depending on the platform.
+fn default_shell() -> Vec<String> {
+    let shell: &[&str] = if cfg!(windows) {
+        &["powershell", "-NoProfile", "-Command"]
+    } else {
+        &["bash", "-c"]
+    };
+    shell.iter().map(|s| s.to_string()).collect()
+}
+
+/// Target of the given full name among some targets and their sub targets.
+fn get_target_mut<'a>(
+    targets: &'a mut HashMap<String, YakeTarget>,
+    target_name: &str,
+) -> Option<&'a mut YakeTarget> {
+    match target_name.split_once('.') {
+        Some((parent, rest)) => get_target_mut(targets.get_mut(parent)?.targets.as_mut()?, rest),
+        None => targets.get_mut(target_name),
+    }
+}
+
+/// Replaces fields of a target with the given ones, env variables are added to the
+/// ones of the target instead.
+fn override_target_fields(target: &mut YakeTarget, fields: &Mapping) -> Result<(), String> {
+    let position = target.position;
+    let mut value = serde_yaml::to_value(&*target).map_err(|e| e.to_string())?;
+    let current_fields = value.as_mapping_mut().unwrap();
+    for (key, field) in fields {
+        let name = key.as_str().unwrap_or_default();
+        // sub targets are overridden by their full name
+        if name == "targets" {
+            return Err(format!("Unknown field {}", name));
+        }
+        match (current_fields.get_mut(key), field) {
+            (Some(Value::Mapping(env)), Value::Mapping(added)) if name == "env" => {
+                for (variable, value) in added {
+                    env.insert(variable.clone(), value.clone());
+                }
+            }
+            (Some(current), _) => *current = field.clone(),
+            (None, _) => return Err(format!("Unknown field {}", name)),
+        }
+    }
+    *target = serde_yaml::from_value(value).map_err(|e| e.to_string())?;
+    target.position = position;
+    Ok(())
+}
+
+/// Replaces the `use` commands of some targets and their sub targets by the commands
+/// of their snippets.
+fn expand_snippet_targets(
+    targets: &mut HashMap<String, YakeTarget>,
+    prefix: Option<&str>,
+    snippets: &HashMap<String, Vec<YakeCommand>>,
+) -> Result<(), String> {
+    for (name, target) in targets.iter_mut() {
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        for commands in target
+            .before
+            .iter_mut()
+            .chain(target.exec.iter_mut())
+            .chain(target.after.iter_mut())
+        {
+            *commands = expand_commands(commands, snippets, &mut Vec::new())
+                .map_err(|e| format!("Invalid target {}: {}", name, e))?;
+        }
+        if let Some(ref mut children) = target.targets {
+            expand_snippet_targets(children, Some(&name), snippets)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the `use` commands among some commands by the commands of their
+/// snippets, `using` holds the snippets using them for detecting cycles.
+fn expand_commands(
+    commands: &[YakeCommand],
+    snippets: &HashMap<String, Vec<YakeCommand>>,
+    using: &mut Vec<String>,
+) -> Result<Vec<YakeCommand>, String> {
+    let mut expanded = Vec::new();
+    for command in commands {
+        let name = match *command {
+            YakeCommand::Use(ref use_command) => &use_command.snippet,
+            _ => {
+                expanded.push(command.clone());
+                continue;
+            }
+        };
+        if let Some(pos) = using.iter().position(|used| used == name) {
+            let mut cycle = using[pos..].to_vec();
+            cycle.push(name.clone());
+            return Err(format!("Cyclic use of snippets: {}", cycle.join(" -> ")));
+        }
+        let snippet = snippets
+            .get(name)
+            .ok_or_else(|| format!("Unknown snippet {}", name))?;
+        using.push(name.clone());
+        expanded.extend(expand_commands(snippet, snippets, using)?);
+        using.pop();
+    }
+    Ok(expanded)
+}
+
+/// Expands the targets with a matrix among some targets and their sub targets.
+fn expand_matrix_targets(
+    targets: &mut HashMap<String, YakeTarget>,
+    prefix: Option<&str>,
+) -> Result<(), String> {
+    for (name, target) in targets.iter_mut() {
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        if let Some(ref mut children) = target.targets {
+            expand_matrix_targets(children, Some(&name))?;
+        }
+        if let Some(matrix) = target.matrix.take() {
+            *target = expand_matrix(&name, std::mem::take(target), &matrix)
+                .map_err(|e| format!("Invalid matrix of target {}: {}", name, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Turns a target with a matrix into a group with a target for each combination of
+/// the matrix values. The group keeps the options inherited by sub targets, e.g. `env`
+/// or `cwd`, the expanded targets get the others. Dots within the values are replaced
+/// in the names of the targets.
+fn expand_matrix(
+    name: &str,
+    target: YakeTarget,
+    matrix: &YakeMatrix,
+) -> Result<YakeTarget, String> {
+    if target.meta.target_type == YakeTargetType::Group {
+        return Err("only callable targets can have a matrix".to_string());
+    }
+    if matrix.0.is_empty() {
+        return Err("the matrix has no variables".to_string());
+    }
+    if let Some((variable, _)) = matrix.0.iter().find(|(_, values)| values.is_empty()) {
+        return Err(format!("{} has no values", variable));
+    }
+
+    let mut children = HashMap::new();
+    for (position, combination) in matrix.combinations().into_iter().enumerate() {
+        let values: Vec<String> = combination
+            .iter()
+            .map(|(_, value)| value.replace('.', "_"))
+            .collect();
+        let child_name = values.join("-");
+        let assignments: Vec<String> = combination
+            .iter()
+            .map(|(variable, value)| format!("{}={}", variable, value))
+            .collect();
+        let child = YakeTarget {
+            meta: YakeTargetMeta {
+                doc: format!("{} ({})", target.meta.doc, assignments.join(", ")),
+                depends: None,
+                ..target.meta.clone()
+            },
+            env: Some(combination.into_iter().collect()),
+            env_file: None,
+            secrets: None,
+            shell: None,
+            shell_opts: None,
+            cwd: None,
+            container: None,
+            remote: None,
+            position,
+            ..target.clone()
+        };
+        if children.insert(child_name.clone(), child).is_some() {
+            return Err(format!(
+                "{}.{} would be expanded more than once",
+                name, child_name
+            ));
+        }
+    }
+
+    Ok(YakeTarget {
+        meta: YakeTargetMeta {
+            target_type: YakeTargetType::Group,
+            ..target.meta
+        },
+        targets: Some(children),
+        env: target.env,
+        env_file: target.env_file,
+        secrets: target.secrets,
+        shell: target.shell,
+        shell_opts: target.shell_opts,
+        cwd: target.cwd,
+        container: target.container,
+        remote: target.remote,
+        position: target.position,
+        ..Default::default()
+    })
+}
+
+/// Resolves the host path of a volume relative to a directory if it starts with `.`,
+/// `docker run` only accepts absolute paths.
+fn resolve_volume(dir: &Path, volume: &str) -> String {
+    match volume.split_once(':') {
+        Some((host, container)) if host.starts_with('.') => {
+            let path = dir.join(host);
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            format!("{}:{}", path.display(), container)
+        }
+        _ => volume.to_string(),
+    }
+}
+
+/// Checks whether a target of a sub yake or one of the groups it is in is marked with
+/// `override`.
+fn overrides(targets: &HashMap<String, YakeTarget>, name: &str) -> bool {
+    let mut name = name;
+    loop {
+        if targets
+            .get(name)
+            .is_some_and(|target| target.meta.overrides.unwrap_or(false))
+        {
+            return true;
+        }
+        match name.rsplit_once('.') {
+            Some((group, _)) => name = group,
+            None => return false,
+        }
+    }
+}
+
+/// Names of the direct children of a group within a flattened map of targets, sorted
+/// by name.
+fn get_child_names(targets: &HashMap<String, YakeTarget>, group_name: &str) -> Vec<String> {
+    let prefix = format!("{}.", group_name);
+    let mut names: Vec<String> = targets
+        .keys()
+        .filter(|name| {
+            name.strip_prefix(&prefix)
+                .is_some_and(|child| !child.contains('.'))
+        })
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+/// Directory of a Yakefile, the current directory if it has none.
+fn get_dir(source: Option<&Path>) -> PathBuf {
+    source
+        .and_then(|source| source.parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Path relative to a directory, going up with `..` out of the parts of the directory
+/// it is not within. Both are expected to be canonical.
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    dir.components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect()
+}
+
+/// Value of an env variable quoted for the shell, or the mask if it is a secret.
+fn mask_value(value: &str, secrets: &[String]) -> String {
+    if secrets.iter().any(|secret| secret == value) {
+        SECRET_MASK.to_string()
+    } else {
+        shell_quote(value)
+    }
+}
+
+/// Target running the commands of a global hook.
+fn get_hook_target(commands: Vec<YakeCommand>) -> YakeTarget {
+    YakeTarget {
+        exec: Some(commands),
+        ..Default::default()
+    }
+}
+
+/// Runs the `before` hooks of a target, then `exec` if they succeeded, and the `after`
+/// hooks regardless. The first failure is returned.
+fn run_with_hooks<B, E, A>(before: B, exec: E, after: A) -> Result<(), YakeError>
+where
+    B: FnOnce() -> Result<(), YakeError>,
+    E: FnOnce() -> Result<(), YakeError>,
+    A: FnOnce() -> Result<(), YakeError>,
+{
+    let result = before().and_then(|()| exec());
+    let after_result = after();
+    result.and(after_result)
+}
+
+/// Prefixes the dependencies of a target of a sub yake, and those of it's children,
+/// which refer to targets of the sub yake with the namespace of it. Other dependencies
+/// refer to targets of the yake the sub yake is added to.
+fn add_namespace(target: &mut YakeTarget, namespace: &str, sub_yake: &Yake) {
+    if let Some(ref mut depends) = target.meta.depends {
+        for dependency in depends.iter_mut() {
+            if sub_yake.get_target_by_name(dependency).is_some() {
+                *dependency = format!("{}.{}", namespace, dependency);
+            }
+        }
+    }
+    for child in target
+        .targets
+        .iter_mut()
+        .flat_map(|targets| targets.values_mut())
+    {
+        add_namespace(child, namespace, sub_yake);
+    }
+}
+
+/// Finds a cycle in the dependencies between targets, given by name. Returns the names
+/// along the cycle, starting and ending with the same target.
+fn find_dependency_cycle(dependencies: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+    let mut names: Vec<&String> = dependencies.keys().collect();
+    names.sort();
+
+    let mut visited = HashSet::new();
+    names
+        .into_iter()
+        .find_map(|name| visit_dependencies(name, dependencies, &mut visited, &mut Vec::new()))
+}
+
+/// Visits the dependencies of a target depth first, `stack` holds the targets on the
+/// path to it.
+fn visit_dependencies<'a>(
+    name: &'a str,
+    dependencies: &'a HashMap<String, Vec<String>>,
+    visited: &mut HashSet<&'a str>,
+    stack: &mut Vec<&'a str>,
+) -> Option<Vec<String>> {
+    if let Some(start) = stack.iter().position(|n| *n == name) {
+        let mut cycle: Vec<String> = stack[start..].iter().map(|n| n.to_string()).collect();
+        cycle.push(name.to_string());
+        return Some(cycle);
+    }
+    if !visited.insert(name) {
+        return None;
+    }
+
+    stack.push(name);
+    for dependency in dependencies.get(name).into_iter().flatten() {
+        if let Some(cycle) = visit_dependencies(dependency, dependencies, visited, stack) {
+            return Some(cycle);
+        }
+    }
+    stack.pop();
+    None
+}
+
+/// Exit code of yake for the failures of the targets of an execution: the one of the
+/// failed target if a single target failed, `FAILED_EXIT_CODE` if several did. Failed
+/// commands never exit with the codes yake reserves, see `command_exit_code`.
+fn failures_exit_code(failures: &[(usize, YakeError)]) -> i32 {
+    match *failures {
+        [] => 0,
+        [(_, ref e)] => e.exit_code(),
+        _ => FAILED_EXIT_CODE,
+    }
+}
+
+/// Combines failures of targets or commands which ran concurrently into a single error,
+/// ordered by their index. The exit code is the one of the first failure.
+fn join_failures(mut failures: Vec<(usize, YakeError)>) -> Result<(), YakeError> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    failures.sort_by_key(|(i, _)| *i);
+    let messages: Vec<String> = failures.iter().map(|(_, e)| e.to_string()).collect();
+    Err(YakeError::Failed {
+        message: messages.join("\n"),
+        exit_code: failures[0].1.exit_code(),
+    })
+}
+
+/// Reports a line of output of a command, prefixed with `┆` and the given prefix.
+/// Captured lines only get the prefix.
+fn report_line(reporter: &TargetReporter, stream: Stream, line: &str, prefix: &str) {
+    let marker = match stream {
+        Stream::Stdout => "┆".bold().green(),
+        Stream::Stderr => "┆".bold().red(),
+    };
+    let formatted = format!(
+        "{}  {}{}\n",
+        marker,
+        prefix,
+        line.lines().next().unwrap_or_default()
+    );
+    reporter.write_line(stream, &format!("{}{}", prefix, line), &formatted);
+}
+
+/// Reads lines from a pipe until it is closed. Lines are passed on including their line
+/// break, the last line might not have one.
+fn read_lines<R: Read, F: FnMut(&str)>(pipe: R, mut on_line: F) {
+    let mut reader = BufReader::new(pipe);
+    let mut line = Vec::new();
+    while let Ok(len) = reader.read_until(b'\n', &mut line) {
+        if len == 0 {
+            break;
+        }
+        on_line(&String::from_utf8_lossy(&line));
+        line.clear();
+    }
+}
+
+/// Writes content to the stdin of a child process and closes it afterwards. A child
+/// which exits before consuming all of it is not an error.
+fn write_stdin(mut stdin: ChildStdin, content: &[u8]) -> io::Result<()> {
+    match stdin.write_all(content) {
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+/// Implementation for a YakeTarget.
+impl YakeTarget {
+    /// Checks the options of the target and it's commands for conflicts.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_parallel() && self.is_interactive() {
+            return Err("A parallel target must not be interactive".to_string());
+        }
+        if self.is_silent() && self.is_interactive() {
+            return Err("A silent target must not be interactive".to_string());
+        }
+        if self.is_service() {
+            if self.exec.is_none() {
+                return Err("A service needs exec commands to run".to_string());
+            }
+            if self.after.is_some() {
+                return Err("A service must not have after commands".to_string());
+            }
+            if self.is_parallel() || self.is_interactive() {
+                return Err("A service must be neither parallel nor interactive".to_string());
+            }
+            if self.timeout.is_some() || self.retries.is_some() {
+                return Err("A service must not have a timeout or retries".to_string());
+            }
+        }
+        if self.is_script_mode() || self.is_service() {
+            if self.is_parallel() {
+                return Err("A target in script mode must not be parallel".to_string());
+            }
+            let plain = |command: &YakeCommand| {
+                matches!(*command, YakeCommand::Plain(_) | YakeCommand::Os(_))
+            };
+            if !self.exec.iter().flatten().all(plain) {
+                return Err(
+                    "The exec commands of a service or a target in script mode must be plain command lines"
+                        .to_string(),
+                );
+            }
+        }
+        if self.groups_output() && !self.is_parallel() {
+            return Err("Only parallel targets can group the output of commands".to_string());
+        }
+        if self.max_parallel == Some(0) {
+            return Err("max_parallel must be at least 1".to_string());
+        }
+        if self.shell.as_ref().is_some_and(|shell| shell.is_empty()) {
+            return Err("shell must not be empty".to_string());
+        }
+        if self
+            .container
+            .as_ref()
+            .is_some_and(|container| container.image.is_empty())
+        {
+            return Err("The image of a container must not be empty".to_string());
+        }
+        if let Some(ref remote) = self.remote {
+            if remote.host.is_empty() {
+                return Err("The host of a remote must not be empty".to_string());
+            }
+            if self.container.is_some() {
+                return Err("A target must not have both a container and a remote".to_string());
+            }
+        }
+        if let Some(ref timeout) = self.timeout {
+            if self.is_interactive() {
+                return Err("An interactive target must not have a timeout".to_string());
+            }
+            parse_duration(timeout)?;
+        }
+        if let Some(ref retry_delay) = self.retry_delay {
+            parse_duration(retry_delay)?;
+        }
+        if let Some(ref when) = self.when {
+            Condition::parse(when)?;
+        }
+        validate_patterns(self.inputs.as_deref().unwrap_or_default())?;
+        validate_patterns(self.outputs.as_deref().unwrap_or_default())?;
+        for command in self.get_all_commands() {
+            command.validate(self)?;
+        }
+        Ok(())
+    }
+
+    /// Checks whether the target has `set` commands, which set env variables.
+    fn sets_variables(&self) -> bool {
+        self.get_all_commands()
+            .any(|command| command.get_output_var().is_some())
+    }
+
+    /// Checks whether the target has commands to run, in `exec` or it's hooks.
+    pub fn has_commands(&self) -> bool {
+        self.exec.is_some() || self.before.is_some() || self.after.is_some()
+    }
+
+    /// Commands of `before`, `exec` and `after`, in the order they run.
+    fn get_all_commands(&self) -> impl Iterator<Item = &YakeCommand> {
+        self.before
+            .iter()
+            .chain(self.exec.iter())
+            .chain(self.after.iter())
+            .flatten()
+    }
+
+    /// Checks whether the target only runs as a dependency of other targets.
+    pub fn is_internal(&self) -> bool {
+        self.meta.internal.unwrap_or(false)
+    }
+
+    /// Checks whether the output of the target is only shown on failure.
+    pub fn is_silent(&self) -> bool {
+        self.silent.unwrap_or(false)
+    }
+
+    /// Checks whether the target runs regardless of it's inputs and outputs.
+    pub fn is_always_run(&self) -> bool {
+        self.always_run.unwrap_or(false)
+    }
+
+    /// Checks whether the target runs it's commands concurrently.
+    pub fn is_parallel(&self) -> bool {
+        self.parallel.unwrap_or(false)
+    }
+
+    /// Checks whether the output of each parallel command is shown in one piece.
+    pub fn groups_output(&self) -> bool {
+        self.group_output.unwrap_or(false)
+    }
+
+    /// Checks whether the target runs all it's commands in a single shell.
+    pub fn is_script_mode(&self) -> bool {
+        self.script_mode.unwrap_or(false)
+    }
+
+    /// Checks whether the target is a service running in the background.
+    pub fn is_service(&self) -> bool {
+        self.meta.target_type == YakeTargetType::Service
+    }
+
+    /// Checks whether the target runs it's commands attached to the terminal.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive.unwrap_or(false)
+    }
+
+    /// Get a map of subordinate targets, sub groups and their targets included.
+    pub fn get_sub_targets(&self, prefix: Option<String>) -> HashMap<String, YakeTarget> {
+        let mut targets = HashMap::new();
+        if let Some(ref x) = self.targets {
+            for (target_name, target) in x {
+                let name = match prefix {
+                    Some(ref x) => format!("{}.{}", x, target_name),
+                    None => target_name.to_string(),
+                };
+                if target.meta.target_type == YakeTargetType::Group {
+                    targets.extend(target.get_sub_targets(Some(name.clone())));
+                }
+                targets.insert(name, target.clone());
+            }
+        }
+        targets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use yaml::load_yml_from_subdirs;
+
+    fn get_yake_targets() -> HashMap<String, YakeTarget> {
+        let mut env = HashMap::new();
+        env.insert("WEBAPP_PORT".to_string(), "6543".to_string());
+        env.insert("POSTGRES_PORT".to_string(), "5432".to_string());
+        let callable_target = YakeTarget {
+            targets: None,
+            meta: YakeTargetMeta {
+                doc: "Huhu".to_string(),
+                target_type: YakeTargetType::Callable,
+                depends: Some(vec!["base".to_string()]),
+                deprecated: None,
+                internal: None,
+                overrides: None,
+            },
+            env: Some(env),
+            exec: None,
+            ..Default::default()
+        };
+
+        let mut env_sub = HashMap::new();
+        env_sub.insert("BASE".to_string(), "OVERWRITE".to_string());
+        env_sub.insert("DOCKER_PORT".to_string(), "1234".to_string());
+        env_sub.insert("POSTGRES_PORT".to_string(), "54322".to_string());
+        let sub_target = YakeTarget {
+            targets: None,
+            meta: YakeTargetMeta {
+                doc: "Subtarget".to_string(),
+                target_type: YakeTargetType::Callable,
+                depends: Some(vec!["base".to_string()]),
+                deprecated: None,
+                internal: None,
+                overrides: None,
+            },
+            env: Some(env_sub),
+            exec: None,
+            ..Default::default()
+        };
+
+        let group_target = YakeTarget {
+            targets: Some([("sub".to_string(), sub_target)].iter().cloned().collect()),
+            meta: YakeTargetMeta {
+                doc: "Grouptarget".to_string(),
+                target_type: YakeTargetType::Group,
+                depends: None,
+                deprecated: None,
+                internal: None,
+                overrides: None,
+            },
+            env: None,
+            exec: None,
+            ..Default::default()
+        };
+
+        [
+            (
+                "base".to_string(),
+                YakeTarget {
+                    targets: None,
+                    meta: YakeTargetMeta {
+                        doc: "Base".to_string(),
+                        target_type: YakeTargetType::Callable,
+                        depends: None,
+                        deprecated: None,
+                        internal: None,
+                        overrides: None,
+                    },
+                    env: None,
+                    exec: None,
+                    ..Default::default()
+                },
+            ),
+            ("test".to_string(), callable_target),
+            ("group".to_string(), group_target),
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    }
+
+    fn get_yake_dependencies(
+        targets: &HashMap<String, YakeTarget>,
+    ) -> HashMap<String, Vec<YakeTarget>> {
+        let mut dependencies = HashMap::new();
+        dependencies.insert(
+            "test".to_string(),
+            vec![targets.get(&"base".to_string()).unwrap().clone()],
+        );
+        dependencies
+    }
+
+    fn get_yake() -> Yake {
+        let targets = get_yake_targets();
+        let dependencies = get_yake_dependencies(&targets);
+        let mut env_root = HashMap::new();
+        env_root.insert("BASE".to_string(), "BASEVAL".to_string());
+
+        Yake {
+            targets,
+            dependencies,
+            env: Some(env_root),
+            secrets: None,
+            meta: YakeMeta {
+                doc: "Bla".to_string(),
+                version: "0.1.0".to_string(),
+                include_recursively: None,
+                include_depth: None,
+                include_exclude: None,
+                includes: None,
+                include_strategy: None,
+                default_target: None,
+                shell: None,
+                shell_opts: None,
+                inherit_env: None,
+            },
+            before_all: None,
+            after_all: None,
+            snippets: None,
+            extends: None,
+            profiles: None,
+            all_targets: HashMap::new(),
+            source: None,
+            merged_sources: HashMap::new(),
+            args: Vec::new(),
+            params: HashMap::new(),
+            env_overrides: HashMap::new(),
+            update_includes: false,
+            captured: CapturedVars::default(),
+            builtins: BuiltinValues::default(),
+        }
+    }
+
+    #[test]
+    fn test_get_all_targets() {
+        let yake = get_yake();
+
+        let all_targets = yake.get_all_targets();
+        assert_eq!(all_targets.len(), 4);
+    }
+
+    #[test]
+    fn test_get_all_dependencies() {
+        let yake = get_yake();
+        let dependencies = yake.get_all_dependencies().unwrap();
+        assert_eq!(dependencies.len(), 4);
+        assert_eq!(dependencies.get("test").unwrap().len(), 1);
+        assert_eq!(dependencies.get("base").unwrap().len(), 0);
+        assert_eq!(dependencies.get("group.sub").unwrap().len(), 1);
+        assert_eq!(dependencies.get("group").unwrap()[0].meta.doc, "Subtarget");
+    }
+
+    #[test]
+    fn test_get_target_by_name() {
+        let yake = get_yake();
+        assert!(yake.get_target_by_name("group.sub").is_some());
+        assert!(yake.get_target_by_name("base").is_some());
+        assert!(yake.get_target_by_name("sub").is_none());
+    }
+
+    #[test]
+    fn test_has_target_name() {
+        let yake = get_yake();
+        assert!(yake.has_target_name("group.sub").is_ok());
+        assert!(yake.has_target_name("sub").is_err());
+        match yake.has_target_name("sub") {
+            Err(YakeError::UnknownTarget { target, available }) => {
+                assert_eq!(target, "sub");
+                assert_eq!(available.len(), 3);
+            }
+            result => panic!("expected an unknown target, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_get_target_names() {
+        let yake = get_yake();
+        let names = yake.get_target_names();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"group.sub".to_string()));
+        assert!(names.contains(&"base".to_string()));
+        assert!(names.contains(&"test".to_string()));
+    }
+
+    #[test]
+    fn test_get_dependencies_by_name() {
+        let yake = get_yake();
+        let dependencies = yake.get_dependencies_by_name("group.sub").unwrap();
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].meta.doc, "Base".to_string());
+    }
+
+    #[test]
+    fn test_dependency_cycle() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          build:
+            meta:
+              doc: "Part of a cycle"
+              type: callable
+              depends:
+                - codegen
+            exec:
+              - echo "build"
+          codegen:
+            meta:
+              doc: "Part of a cycle"
+              type: callable
+              depends:
+                - schema
+          schema:
+            meta:
+              doc: "Closes the cycle"
+              type: callable
+              depends:
+                - build
+          lint:
+            meta:
+              doc: "Depends on itself"
+              type: callable
+              depends:
+                - lint
+        "###;
+
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        assert_eq!(
+            yake.get_all_dependencies().unwrap_err(),
+            "Dependency cycle detected: build -> codegen -> schema -> build"
+        );
+        assert_eq!(
+            yake.execute("codegen", &ExecutionOptions::default())
+                .unwrap_err()
+                .to_string(),
+            "Dependency cycle detected: build -> codegen -> schema -> build"
+        );
+
+        yake.targets.get_mut("build").unwrap().meta.depends = None;
+        assert_eq!(
+            yake.get_all_dependencies().unwrap_err(),
+            "Dependency cycle detected: lint -> lint"
+        );
+    }
+
+    #[test]
+    fn test_unknown_dependency() {
+        let mut yake = get_yake();
+        yake.targets.get_mut("base").unwrap().meta.depends = Some(vec!["missing".to_string()]);
+
+        assert_eq!(
+            yake.execute("base", &ExecutionOptions::default()),
+            Err(YakeError::Invalid(
+                "Unknown dependency: missing of target: base".to_string()
+            ))
+        );
+        assert_eq!(
+            yake.execute("missing", &ExecutionOptions::default())
+                .unwrap_err()
+                .exit_code(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_get_env_vars() {
+        let yake = get_yake();
+
+        let envs = yake.get_target_env_vars("base").unwrap_or_default();
+        assert_eq!(envs.len(), 1);
+        assert_eq!(envs.get("BASE").unwrap(), "BASEVAL");
+
+        let envs = yake.get_target_env_vars("test").unwrap_or_default();
+        assert_eq!(envs.len(), 3);
+        assert_eq!(envs.get("BASE").unwrap(), "BASEVAL");
+        assert_eq!(envs.get("WEBAPP_PORT").unwrap(), "6543");
+        assert_eq!(envs.get("POSTGRES_PORT").unwrap(), "5432");
+
+        let envs = yake.get_target_env_vars("group").unwrap_or_default();
+        assert_eq!(envs.len(), 1);
+        assert_eq!(envs.get("BASE").unwrap(), "BASEVAL");
+
+        let envs = yake.get_target_env_vars("group.sub").unwrap_or_default();
+        assert_eq!(envs.len(), 3);
+        assert_eq!(envs.get("BASE").unwrap(), "OVERWRITE");
+        assert_eq!(envs.get("DOCKER_PORT").unwrap(), "1234");
+        assert_eq!(envs.get("POSTGRES_PORT").unwrap(), "54322");
+    }
+
+    #[test]
+    fn test_get_env_vars_extends_path() {
+        let mut env = HashMap::new();
+        env.insert("WEBAPP_PORT".to_string(), "6543".to_string());
+        env.insert("PATH".to_string(), "$HOME/bin:$PATH".to_string());
+        let mut yake = get_yake();
+        yake.env = Some(env);
+
+        let envs = yake.get_target_env_vars("base").unwrap();
+        assert_eq!(
+            envs.get("PATH").unwrap(),
+            &format!(
+                "{}/bin:{}",
+                std::env::var("HOME").unwrap(),
+                std::env::var("PATH").unwrap()
+            )
+        );
+        assert_eq!(envs.get("WEBAPP_PORT").unwrap(), "6543");
+    }
+
+    #[test]
+    fn test_deserialize_yake_target_type() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          PATH: $HOME/bin:$PATH
+        targets:
+          base:
+            meta:
+              doc: "Test command"
+              type: callable
+            exec:
+              - echo "i'm base"
+          group:
+            meta:
+              doc: "Test command"
+              type: group
+        "###;
+
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        assert_eq!(
+            yake.targets.get("base").unwrap().meta.target_type,
+            YakeTargetType::Callable
+        );
+        assert_eq!(
+            yake.targets.get("group").unwrap().meta.target_type,
+            YakeTargetType::Group
+        );
+    }
+
+    #[test]
+    fn test_add_sub_yakes() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          PATH: $HOME/bin:$PATH
+        targets:
+          base:
+            meta:
+              doc: "Test command"
+              type: callable
+            exec:
+              - echo "i'm base"
+          group:
+            meta:
+              doc: "Test command"
+              type: group
+        "###;
+
+        let subyml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          PATH: $HOME/bin:$PATH
+        targets:
+          base:
+            meta:
+              doc: "Test command of the sub yake"
+              type: callable
+            exec:
+              - echo "i'm the base of the sub yake"
+          sub_base:
+            meta:
+              doc: "Sub: Test command"
+              type: callable
+              depends:
+                - base
+                - group
+            exec:
+              - echo "i'm sub base"
+        "###;
+
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        assert_eq!(
+            yake.targets.get("base").unwrap().meta.target_type,
+            YakeTargetType::Callable
+        );
+        assert_eq!(
+            yake.targets.get("group").unwrap().meta.target_type,
+            YakeTargetType::Group
+        );
+
+        let mut sub_yake: Yake = serde_yaml::from_str(subyml).expect("Unable to parse");
+        sub_yake.source = Some(PathBuf::from("sub/Yakefile"));
+        assert_eq!(
+            sub_yake.targets.get("base").unwrap().meta.target_type,
+            YakeTargetType::Callable
+        );
+        assert_eq!(
+            sub_yake.targets.get("sub_base").unwrap().meta.target_type,
+            YakeTargetType::Callable
+        );
+
+        yake.add_sub_yake(sub_yake).unwrap();
+        assert_eq!(yake.targets.get("base").unwrap().meta.doc, "Test command");
+        assert_eq!(
+            yake.targets.get("sub").unwrap().meta.target_type,
+            YakeTargetType::Group
+        );
+        assert_eq!(
+            yake.get_target_by_name("sub.base").unwrap().meta.doc,
+            "Test command of the sub yake"
+        );
+        let sub_base = yake.get_target_by_name("sub.sub_base").unwrap();
+        assert_eq!(
+            sub_base.meta.depends,
+            Some(vec!["sub.base".to_string(), "group".to_string()])
+        );
+        assert_eq!(
+            sub_base.env.unwrap().get("PATH"),
+            Some(&"$HOME/bin:$PATH".to_string())
+        );
+    }
+
+    fn get_cross_file_yake() -> Yake {
+        read_yml_from_file(Path::new("tests/fixtures/cross_file/root/Yakefile"))
+            .expect("Unable to load fixture")
+    }
+
+    #[test]
+    fn test_parse_external_reference() {
+        let reference = ExternalReference::parse("../shared/Yakefile:docker.login").unwrap();
+        assert_eq!(reference.path, PathBuf::from("../shared/Yakefile"));
+        assert_eq!(reference.target, "docker.login");
+
+        assert!(ExternalReference::parse("docker.login").is_none());
+        assert!(ExternalReference::parse(":docker.login").is_none());
+        assert!(ExternalReference::parse("../shared/Yakefile:").is_none());
+    }
+
+    #[test]
+    fn test_execute_cross_file_dependency() {
+        let yake = get_cross_file_yake();
+        assert!(yake.execute("deploy", &ExecutionOptions::default()).is_ok());
+
+        let reference = ExternalReference::parse("../shared/Yakefile:docker.login").unwrap();
+        let options = ExecutionOptions::default();
+        let mut execution = get_execution(&yake, &options);
+        let (key, shared) = yake
+            .load_external_yake(&reference, "deploy", &mut execution)
+            .unwrap();
+        assert_eq!(key, Some(shared.get_canonical_source()));
+        assert_eq!(
+            shared.get_base_dir(),
+            PathBuf::from("tests/fixtures/cross_file/root/../shared")
+        );
+        let envs = shared.get_target_env_vars("docker.login").unwrap();
+        assert_eq!(envs.get("ORIGIN").unwrap(), "shared");
+
+        // the referenced yakefile is only loaded once
+        yake.load_external_yake(&reference, "deploy", &mut execution)
+            .unwrap();
+        assert_eq!(execution.yakes.len(), 1);
+
+        // references back to the executed yakefile get it under the key None
+        let reference = ExternalReference::parse("../root/Yakefile:cycle").unwrap();
+        let (key, root) = shared
+            .load_external_yake(&reference, "cycle", &mut execution)
+            .unwrap();
+        assert_eq!(key, None);
+        assert_eq!(root.get_source(), yake.get_source());
+    }
+
+    #[test]
+    fn test_execute_cross_file_missing_file() {
+        let yake = get_cross_file_yake();
+        let err = yake
+            .execute("missing_file", &ExecutionOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("root/../nowhere/Yakefile"));
+        assert!(err.contains("root/Yakefile:missing_file"));
+    }
+
+    #[test]
+    fn test_execute_cross_file_missing_target() {
+        let yake = get_cross_file_yake();
+        let err = yake
+            .execute("missing_target", &ExecutionOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("shared/Yakefile:docker.logout"));
+        assert!(err.contains("root/Yakefile:missing_target"));
+    }
+
+    #[test]
+    fn test_execute_cross_file_cycle() {
+        let yake = get_cross_file_yake();
+        let err = yake
+            .execute("cycle", &ExecutionOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Dependency cycle detected"));
+        assert!(err.contains("root/Yakefile:cycle -> "));
+        assert!(err.contains("shared/Yakefile:cycle -> "));
+    }
+
+    /// Runs a command and collects it's stdout.
+    fn run_command_stdout(
+        yake: &Yake,
+        target_name: &str,
+        command: &YakeCommand,
+    ) -> Result<String, YakeError> {
+        let stdout = Mutex::new(String::new());
+        yake.run_command(target_name, command, |stream, line| {
+            if stream == Stream::Stdout {
+                stdout.lock().unwrap().push_str(line);
+            }
+        })?;
+        Ok(stdout.into_inner().unwrap())
+    }
+
+    #[test]
+    fn test_run_command_streams_output() {
+        let yake = get_yake();
+        let command =
+            YakeCommand::Plain("echo first; echo oops >&2; sleep 1; echo second".to_string());
+        let started = Instant::now();
+        let lines = Mutex::new(Vec::new());
+
+        let status = yake
+            .run_command("base", &command, |stream, line| {
+                lines
+                    .lock()
+                    .unwrap()
+                    .push((stream, line.to_string(), started.elapsed()));
+            })
+            .unwrap();
+        assert!(status.success());
+
+        let lines = lines.into_inner().unwrap();
+        let texts: Vec<(Stream, &str)> = lines.iter().map(|l| (l.0, l.1.as_str())).collect();
+        assert_eq!(texts.len(), 3);
+        assert!(texts.contains(&(Stream::Stderr, "oops\n")));
+        assert_eq!(texts.last().unwrap(), &(Stream::Stdout, "second\n"));
+        // the first line is passed on before the command finished
+        assert!(lines[0].2 + Duration::from_millis(500) < lines[2].2);
+    }
+
+    #[test]
+    fn test_run_command_with_stdin() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          NAME: yake
+        targets:
+          base:
+            meta:
+              doc: "Test command"
+              type: callable
+            exec:
+              - echo "plain"
+              - cmd: cat
+                stdin: |
+                  hello $NAME
+                  select $1;
+              - cmd: cat
+                stdin: "{{NAME}} {{meta.version}}"
+        "###;
+
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let commands = yake.targets.get("base").unwrap().exec.clone().unwrap();
+        assert_eq!(
+            commands[0],
+            YakeCommand::Plain("echo \"plain\"".to_string())
+        );
+        assert_eq!(commands[1].get_command(), "cat");
+
+        let stdout = run_command_stdout(&yake, "base", &commands[1]).unwrap();
+        assert_eq!(stdout, "hello yake\nselect $1;\n");
+
+        // placeholders are resolved along with the ones of the command
+        let target = yake.targets.get("base").unwrap();
+        let commands = yake.get_target_commands("base", target).unwrap();
+        let stdout = run_command_stdout(&yake, "base", &commands[2]).unwrap();
+        assert_eq!(stdout, "yake 0.1.0");
+    }
+
+    #[test]
+    fn test_run_command_with_stdin_file() {
+        let yake = get_yake();
+        let command = YakeCommand::Detailed(YakeCommandDetails {
+            cmd: "head -n 1".to_string(),
+            stdin: None,
+            stdin_file: Some("foo/Yakefile".to_string()),
+            ..Default::default()
+        });
+
+        let stdout = run_command_stdout(&yake, "base", &command).unwrap();
+        assert_eq!(stdout, "meta:\n");
+
+        let command = YakeCommand::Detailed(YakeCommandDetails {
+            cmd: "cat".to_string(),
+            stdin: None,
+            stdin_file: Some("does/not/exist".to_string()),
+            ..Default::default()
+        });
+        assert!(run_command_stdout(&yake, "base", &command).is_err());
+    }
+
+    #[test]
+    fn test_run_command_with_stdin_closed_early() {
+        let yake = get_yake();
+        let command = YakeCommand::Detailed(YakeCommandDetails {
+            cmd: "true".to_string(),
+            stdin: Some("x".repeat(1024 * 1024)),
+            ..Default::default()
+        });
+
+        assert!(run_command_stdout(&yake, "base", &command).is_ok());
+    }
+
+    #[test]
+    fn test_validate_stdin_conflict() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          base:
+            meta:
+              doc: "Test command"
+              type: callable
+            exec:
+              - cmd: cat
+                stdin: "inline"
+                stdin_file: input.txt
+        "###;
+
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let err = yake.validate().unwrap_err();
+        assert!(err.contains("base"));
+        assert!(err.contains("stdin_file"));
+    }
+
+    fn get_exit_code_yake() -> Yake {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          accepted:
+            meta:
+              doc: "Non-zero exit code which is fine"
+              type: callable
+            exec:
+              - cmd: exit 1
+                expected_exit_codes: [0, 1]
+          rejected:
+            meta:
+              doc: "Exit code which is not accepted"
+              type: callable
+            expected_exit_codes: [0, 1]
+            exec:
+              - exit 2
+              - echo "never reached"
+          plain:
+            meta:
+              doc: "Only exit code 0 is expected by default"
+              type: callable
+            exec:
+              - exit 3
+              - echo "never reached"
+        "###;
+
+        serde_yaml::from_str(yml).expect("Unable to parse")
+    }
+
+    #[test]
+    fn test_expected_exit_codes_accepted() {
+        let yake = get_exit_code_yake();
+        assert!(yake
+            .execute("accepted", &ExecutionOptions::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_expected_exit_codes_rejected() {
+        let yake = get_exit_code_yake();
+        let err = yake
+            .execute("rejected", &ExecutionOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Command \"exit 2\" exited with code 2, expected one of [0, 1]"
+        );
+    }
+
+    #[test]
+    fn test_expected_exit_codes_default() {
+        let yake = get_exit_code_yake();
+        assert_eq!(
+            yake.execute("plain", &ExecutionOptions::default()),
+            Err(YakeError::Failed {
+                message: "Command \"exit 3\" exited with code 3, expected one of [0]".to_string(),
+                exit_code: 3,
+            })
+        );
+
+        // exit codes yake reserves for timeouts and interrupts are not passed on
+        let mut yake = get_exit_code_yake();
+        for code in [TIMEOUT_EXIT_CODE, INTERRUPT_EXIT_CODE] {
+            yake.targets.get_mut("plain").unwrap().exec =
+                Some(vec![YakeCommand::Plain(format!("exit {}", code))]);
+            assert_eq!(
+                yake.execute("plain", &ExecutionOptions::default())
+                    .unwrap_err()
+                    .exit_code(),
+                FAILED_EXIT_CODE
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_interactive_stdin() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          repl:
+            meta:
+              doc: "Test command"
+              type: callable
+            interactive: true
+            exec:
+              - cmd: cat
+                stdin: "inline"
+        "###;
+
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let err = yake.validate().unwrap_err();
+        assert!(err.contains("interactive target"));
+    }
+
+    fn get_parallel_yake() -> Yake {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          parallel:
+            meta:
+              doc: "Commands running at the same time"
+              type: callable
+            parallel: true
+            exec:
+              - sleep 0.3
+              - sleep 0.5
+              - sleep 0.4
+          limited:
+            meta:
+              doc: "Commands running one at a time"
+              type: callable
+            parallel: true
+            max_parallel: 1
+            exec:
+              - sleep 0.2
+              - sleep 0.2
+              - sleep 0.2
+          failing:
+            meta:
+              doc: "Failing commands running at the same time"
+              type: callable
+            parallel: true
+            expected_exit_codes: [0]
+            exec:
+              - exit 1
+              - echo "ok"
+              - exit 2
+        "###;
+
+        serde_yaml::from_str(yml).expect("Unable to parse")
+    }
+
+    #[test]
+    fn test_execute_parallel() {
+        let yake = get_parallel_yake();
+        let start = Instant::now();
+        assert!(yake
+            .execute("parallel", &ExecutionOptions::default())
+            .is_ok());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(500));
+        assert!(elapsed < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_execute_parallel_max_parallel() {
+        let yake = get_parallel_yake();
+        let start = Instant::now();
+        assert!(yake
+            .execute("limited", &ExecutionOptions::default())
+            .is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[test]
+    fn test_execute_parallel_collects_failures() {
+        let yake = get_parallel_yake();
+        let err = yake
+            .execute("failing", &ExecutionOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Command \"exit 1\" exited with code 1, expected one of [0]\n\
+             Command \"exit 2\" exited with code 2, expected one of [0]"
+        );
+    }
+
+    #[test]
+    fn test_validate_group_output() {
+        let mut yake = get_parallel_yake();
+        yake.targets.get_mut("parallel").unwrap().group_output = Some(true);
+        assert!(yake.validate().is_ok());
+
+        yake.targets.get_mut("parallel").unwrap().parallel = None;
+        assert_eq!(
+            yake.validate().unwrap_err(),
+            "Invalid target parallel: Only parallel targets can group the output of commands"
+        );
+    }
+
+    #[test]
+    fn test_validate_parallel_interactive() {
+        let mut yake = get_parallel_yake();
+        yake.targets.get_mut("parallel").unwrap().interactive = Some(true);
+        let err = yake.validate().unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid target parallel: A parallel target must not be interactive"
+        );
+    }
+
+    fn get_which_yake() -> Yake {
+        let mut yake = read_yml_from_file(Path::new("tests/fixtures/which/Yakefile"))
+            .expect("Unable to load fixture");
+        for sub_yake in load_yml_from_subdirs(Path::new("tests/fixtures/which")).unwrap() {
+            yake.add_sub_yake(sub_yake).unwrap();
+        }
+        yake
+    }
+
+    #[test]
+    fn test_get_target_location() {
+        let yake = get_which_yake();
+
+        let location = yake.get_target_location("services.lint").unwrap();
+        assert_eq!(
+            location.definition,
+            TargetDefinition {
+                file: PathBuf::from("tests/fixtures/which/Yakefile"),
+                line: Some(17),
+            }
+        );
+        assert!(location.overrides.is_empty());
+
+        let location = yake.get_target_location("api.test").unwrap();
+        assert_eq!(
+            location.definition,
+            TargetDefinition {
+                file: PathBuf::from("tests/fixtures/which/api/Yakefile"),
+                line: Some(12),
+            }
+        );
+        assert!(location.overrides.is_empty());
+    }
+
+    #[test]
+    fn test_get_target_location_overridden() {
+        let yake = get_which_yake();
+
+        let location = yake.get_target_location("api.build").unwrap();
+        assert_eq!(
+            location.definition,
+            TargetDefinition {
+                file: PathBuf::from("tests/fixtures/which/api/Yakefile"),
+                line: Some(5),
+            }
+        );
+        assert_eq!(
+            location.overrides,
+            vec![TargetDefinition {
+                file: PathBuf::from("tests/fixtures/which/Yakefile"),
+                line: Some(28),
+            }]
+        );
+        assert!(yake
+            .get_target_location("build")
+            .unwrap()
+            .overrides
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_target_location_unknown() {
+        let yake = get_which_yake();
+        assert!(yake.get_target_location("lint").is_err());
+    }
+
+    #[test]
+    fn test_get_env_vars_references() {
+        let mut yake = get_yake();
+        yake.env
+            .as_mut()
+            .unwrap()
+            .insert("BASE_URL".to_string(), "https://${BASE}".to_string());
+        yake.targets
+            .get_mut("test")
+            .unwrap()
+            .env
+            .as_mut()
+            .unwrap()
+            .insert("HEALTH_URL".to_string(), "${BASE_URL}/health".to_string());
+
+        let envs = yake.get_target_env_vars("test").unwrap();
+        assert_eq!(envs.get("HEALTH_URL").unwrap(), "https://BASEVAL/health");
+
+        let envs = yake.get_target_env_vars("group.sub").unwrap();
+        assert_eq!(envs.get("BASE_URL").unwrap(), "https://OVERWRITE");
+    }
+
+    #[test]
+    fn test_get_env_vars_references_cycle() {
+        let mut yake = get_yake();
+        yake.env
+            .as_mut()
+            .unwrap()
+            .insert("BASE".to_string(), "${BASE}".to_string());
+
+        assert_eq!(
+            yake.get_target_env_vars("base").unwrap_err(),
+            "Cyclic reference in env variables: BASE -> BASE"
+        );
+    }
+
+    #[test]
+    fn test_env_vars_with_special_characters() {
+        let mut yake = get_yake();
+        let value = "it's \"quoted\" $HOME ${BASE}\nsecond line";
+        yake.env
+            .as_mut()
+            .unwrap()
+            .insert("SPECIAL".to_string(), value.to_string());
+
+        let command = YakeCommand::Plain("printf %s \"$SPECIAL\"".to_string());
+        let stdout = run_command_stdout(&yake, "base", &command).unwrap();
+        assert_eq!(stdout, "it's \"quoted\" $HOME BASEVAL\nsecond line");
+    }
+
+    #[test]
+    fn test_env_vars_with_null_byte() {
+        let mut yake = get_yake();
+        yake.env
+            .as_mut()
+            .unwrap()
+            .insert("BROKEN".to_string(), "a\0b".to_string());
+
+        assert_eq!(
+            yake.get_target_env_vars("base").unwrap_err(),
+            "Env variable BROKEN contains a null byte"
+        );
+        let command = YakeCommand::Plain("true".to_string());
+        assert!(run_command_stdout(&yake, "base", &command).is_err());
+    }
+
+    fn get_execution<'a>(yake: &'a Yake, options: &'a ExecutionOptions) -> Execution<'a> {
+        Execution {
+            root: yake,
+            options,
+            yakes: HashMap::new(),
+            stack: Vec::new(),
+            scheduled: Vec::new(),
+            scheduled_indices: HashMap::new(),
+            captured: Mutex::new(HashMap::new()),
+            given_env: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_schedule_target() {
+        let yake = get_yake();
+        let options = ExecutionOptions::default();
+        let mut execution = get_execution(&yake, &options);
+
+        let test = yake.schedule_target(&None, "test", &mut execution).unwrap();
+        let sub = yake
+            .schedule_target(&None, "group.sub", &mut execution)
+            .unwrap();
+        let names: Vec<&str> = execution
+            .scheduled
+            .iter()
+            .map(|s| s.target_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["base", "test", "group.sub"]);
+        assert_eq!(execution.scheduled[test].dependencies, vec![0]);
+        assert_eq!(execution.scheduled[sub].dependencies, vec![0]);
+    }
+
+    #[test]
+    fn test_schedule_transitive_dependencies() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          deploy:
+            meta:
+              doc: "Depends on build only"
+              type: callable
+              depends:
+                - build
+                - lint
+          build:
+            meta:
+              doc: "Depends on codegen"
+              type: callable
+              depends:
+                - codegen
+          lint:
+            meta:
+              doc: "Depends on codegen as well"
+              type: callable
+              depends:
+                - codegen
+          codegen:
+            meta:
+              doc: "Generates code"
+              type: callable
+        "###;
+
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let options = ExecutionOptions::default();
+        let mut execution = get_execution(&yake, &options);
+
+        yake.schedule_target(&None, "deploy", &mut execution)
+            .unwrap();
+        let names: Vec<&str> = execution
+            .scheduled
+            .iter()
+            .map(|s| s.target_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["codegen", "build", "lint", "deploy"]);
+        assert_eq!(execution.scheduled[2].dependencies, vec![0]);
+    }
+
+    #[test]
+    fn test_schedule_cross_file_target() {
+        let yake = get_cross_file_yake();
+        let options = ExecutionOptions::default();
+        let mut execution = get_execution(&yake, &options);
+
+        let deploy = yake
+            .schedule_target(&None, "deploy", &mut execution)
+            .unwrap();
+        assert_eq!(deploy, execution.scheduled.len() - 1);
+        let login = &execution.scheduled[execution.scheduled[deploy].dependencies[0]];
+        assert_eq!(login.target_name, "docker.login");
+        assert!(login.yake.as_ref().unwrap().ends_with("shared/Yakefile"));
+    }
+
+    #[test]
+    fn test_execute_with_jobs() {
+        let yake = get_cross_file_yake();
+        let options = ExecutionOptions {
+            jobs: 4,
+            ..Default::default()
+        };
+        assert!(yake.execute("deploy", &options).is_ok());
+        assert!(yake.execute("missing_target", &options).is_err());
+    }
+
+    fn get_shell_yake() -> Yake {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+          shell: [sh, -c]
+        targets:
+          plain:
+            meta:
+              doc: "Uses the shell of the meta data"
+              type: callable
+            exec:
+              - echo "from sh"
+          scripts:
+            meta:
+              doc: "Python targets"
+              type: group
+            shell: [python3, -c]
+            targets:
+              hello:
+                meta:
+                  doc: "Inherits the shell of the group"
+                  type: callable
+                exec:
+                  - print("hello from python")
+              bash:
+                meta:
+                  doc: "Overrides the shell of the group"
+                  type: callable
+                shell: [bash, -e, -c]
+                exec:
+                  - echo "from bash"
+        "###;
+
+        serde_yaml::from_str(yml).expect("Unable to parse")
+    }
+
+    #[test]
+    fn test_get_target_shell() {
+        let yake = get_shell_yake();
+        assert_eq!(yake.get_target_shell("plain"), vec!["sh", "-c"]);
+        assert_eq!(
+            yake.get_target_shell("scripts.hello"),
+            vec!["python3", "-c"]
+        );
+        assert_eq!(
+            yake.get_target_shell("scripts.bash"),
+            vec!["bash", "-e", "-c"]
+        );
+
+        assert_eq!(get_yake().get_target_shell("base"), default_shell());
+    }
+
+    #[test]
+    fn test_run_command_with_shell() {
+        let yake = get_shell_yake();
+        let command = YakeCommand::Plain("print(\"hello from python\")".to_string());
+        assert_eq!(
+            run_command_stdout(&yake, "scripts.hello", &command).unwrap(),
+            "hello from python\n"
+        );
+    }
+
+    #[test]
+    fn test_validate_empty_shell() {
+        let mut yake = get_shell_yake();
+        yake.targets.get_mut("plain").unwrap().shell = Some(Vec::new());
+        assert_eq!(
+            yake.validate().unwrap_err(),
+            "Invalid target plain: shell must not be empty"
+        );
+
+        let mut yake = get_shell_yake();
+        yake.meta.shell = Some(Vec::new());
+        assert!(yake.validate().is_err());
+    }
+
+    #[test]
+    fn test_get_param_values() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          deploy:
+            meta:
+              doc: "Has parameters"
+              type: callable
+            params:
+              - stage
+              - name: region
+                default: eu
+              - name: tag
+                required: false
+        "###;
+
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        assert!(yake.get_param_values("deploy").is_err());
+
+        yake.params.insert("stage".to_string(), "prod".to_string());
+        let values = yake.get_param_values("deploy").unwrap();
+        assert_eq!(values.get("stage").unwrap(), "prod");
+        assert_eq!(values.get("region").unwrap(), "eu");
+        assert!(!values.contains_key("tag"));
+
+        let envs = yake.get_target_env_vars("deploy").unwrap();
+        assert_eq!(envs.get("stage").unwrap(), "prod");
+    }
+
+    #[test]
+    fn test_inherit_env() {
+        let mut yake = get_yake();
+        let command = YakeCommand::Plain("echo \"${CARGO_MANIFEST_DIR:-unset} $BASE\"".to_string());
+        assert_eq!(
+            run_command_stdout(&yake, "base", &command).unwrap(),
+            format!("{} BASEVAL\n", env!("CARGO_MANIFEST_DIR"))
+        );
+
+        yake.meta.inherit_env = Some(false);
+        assert_eq!(
+            run_command_stdout(&yake, "base", &command).unwrap(),
+            "unset BASEVAL\n"
+        );
+    }
+
+    fn get_timeout_yake() -> Yake {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          hanging:
+            meta:
+              doc: "Commands which do not finish in time"
+              type: callable
+            timeout: 300ms
+            exec:
+              - echo started; sleep 10 & wait
+              - cmd: sleep 10
+                timeout: 1h
+          quick:
+            meta:
+              doc: "A command which finishes in time"
+              type: callable
+            timeout: 10s
+            exec:
+              - echo done
+        "###;
+        serde_yaml::from_str(yml).expect("Unable to parse")
+    }
+
+    #[test]
+    fn test_run_command_timeout() {
+        let yake = get_timeout_yake();
+        let commands = yake.targets["hanging"].exec.clone().unwrap();
+        let started = Instant::now();
+        let err = run_command_stdout(&yake, "hanging", &commands[0]).unwrap_err();
+        // the background process started by the command is killed as well
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            err,
+            YakeError::Failed {
+                message: "Command \"echo started; sleep 10 & wait\" timed out after 300ms"
+                    .to_string(),
+                exit_code: TIMEOUT_EXIT_CODE,
+            }
+        );
+
+        let quick = yake.targets["quick"].exec.clone().unwrap();
+        assert_eq!(
+            run_command_stdout(&yake, "quick", &quick[0]).unwrap(),
+            "done\n"
+        );
+    }
+
+    #[test]
+    fn test_get_timeout() {
+        let yake = get_timeout_yake();
+        let target = &yake.targets["hanging"];
+        let commands = target.exec.clone().unwrap();
+        assert_eq!(commands[0].get_timeout(target), Some("300ms"));
+        assert_eq!(commands[1].get_timeout(target), Some("1h"));
+        assert_eq!(commands[0].get_timeout(&YakeTarget::default()), None);
+    }
+
+    #[test]
+    fn test_validate_timeout() {
+        let mut yake = get_timeout_yake();
+        yake.targets.get_mut("quick").unwrap().timeout = Some("10 seconds".to_string());
+        assert_eq!(
+            yake.validate().unwrap_err(),
+            "Invalid target quick: Invalid duration \"10 seconds\""
+        );
+
+        let mut yake = get_timeout_yake();
+        yake.targets.get_mut("quick").unwrap().interactive = Some(true);
+        assert_eq!(
+            yake.validate().unwrap_err(),
+            "Invalid target quick: An interactive target must not have a timeout"
+        );
+    }
+
+    #[test]
+    fn test_run_with_retries() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          push:
+            meta:
+              doc: "Flaky commands"
+              type: callable
+            retries: 3
+            retry_delay: 10ms
+            exec:
+              - docker push app
+              - cmd: curl example.com
+                retries: 1
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let target = &yake.targets["push"];
+        let commands = target.exec.clone().unwrap();
+        let failure = || Err(YakeError::from("failed".to_string()));
+
+        // succeeds on the third attempt
+        let attempts = Mutex::new(0);
+        let retries = Mutex::new(Vec::new());
+        let result = commands[0].run_with_retries(
+            target,
+            || {
+                *attempts.lock().unwrap() += 1;
+                if *attempts.lock().unwrap() < 3 {
+                    failure()
+                } else {
+                    Ok(())
+                }
+            },
+            |retry, count, delay| retries.lock().unwrap().push((retry, count, delay)),
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            retries.into_inner().unwrap(),
+            vec![
+                (1, 3, Duration::from_millis(10)),
+                (2, 3, Duration::from_millis(20))
+            ]
+        );
+
+        let attempts = Mutex::new(0);
+        let result = commands[1].run_with_retries(
+            target,
+            || {
+                *attempts.lock().unwrap() += 1;
+                failure()
+            },
+            |_, _, _| {},
+        );
+        assert_eq!(result.unwrap_err().to_string(), "failed");
+        assert_eq!(attempts.into_inner().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_ignore_errors() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          clean:
+            meta:
+              doc: "Cleanup which may fail"
+              type: callable
+            exec:
+              - cmd: exit 1
+                ignore_errors: true
+              - echo "cleaned"
+          clean-all:
+            meta:
+              doc: "Cleanup which may fail in parallel"
+              type: callable
+            parallel: true
+            exec:
+              - cmd: exit 1
+                ignore_errors: true
+              - exit 2
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        assert!(yake.execute("clean", &ExecutionOptions::default()).is_ok());
+        assert_eq!(
+            yake.execute("clean-all", &ExecutionOptions::default()),
+            Err(YakeError::Failed {
+                message: "Command \"exit 2\" exited with code 2, expected one of [0]".to_string(),
+                // not mistaken for an invalid Yakefile
+                exit_code: FAILED_EXIT_CODE,
+            })
+        );
+    }
+
+    #[test]
+    fn test_execute_with_report() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          setup:
+            meta:
+              doc: "Only on CI"
+              type: callable
+            when: env.YAKE_UNSET_VARIABLE
+            exec:
+              - echo "setting up"
+          build:
+            meta:
+              doc: "Builds the app"
+              type: callable
+              depends:
+                - setup
+            parallel: true
+            exec:
+              - echo "building"
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let options = ExecutionOptions {
+            output: OutputFormat::Json,
+            ..ExecutionOptions::default()
+        };
+        let report = yake
+            .execute_with_report(&["build".to_string()], &options)
+            .unwrap();
+
+        assert!(report.success);
+        assert_eq!(report.exit_code, 0);
+        let statuses: Vec<(&str, TargetStatus)> = report
+            .targets
+            .iter()
+            .map(|t| (t.target.as_str(), t.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("setup", TargetStatus::Skipped),
+                ("build", TargetStatus::Succeeded)
+            ]
+        );
+        assert_eq!(
+            report.targets[0].reason.as_deref(),
+            Some("when: env.YAKE_UNSET_VARIABLE")
+        );
+        assert_eq!(report.targets[1].output, "[1] building\n");
+        assert!(report.targets[0].commands.is_empty());
+        let commands: Vec<(&str, i32)> = report.targets[1]
+            .commands
+            .iter()
+            .map(|c| (c.command.as_str(), c.exit_code))
+            .collect();
+        assert_eq!(commands, vec![("echo \"building\"", 0)]);
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn test_report_invocation() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          GREETING: hello world
+        targets:
+          greet:
+            meta:
+              doc: "Greets"
+              type: callable
+            env:
+              NAME: yake
+            exec:
+              - echo "$GREETING $NAME"
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let command = YakeCommand::Plain("echo \"$GREETING $NAME\"".to_string());
+        let lines = Mutex::new(Vec::new());
+        let report = |level: Verbosity, text: &str| {
+            lines.lock().unwrap().push((level, text.to_string()));
+        };
+
+        yake.report_invocation("greet", &command, "", Verbosity::Normal, report)
+            .unwrap();
+        assert!(lines.lock().unwrap().is_empty());
+
+        yake.report_invocation("greet", &command, "[1] ", Verbosity::Debug, report)
+            .unwrap();
+        let lines = lines.into_inner().unwrap();
+        assert_eq!(lines[0].0, Verbosity::Verbose);
+        assert!(lines[1].1.ends_with("-c 'echo \"$GREETING $NAME\"'"));
+        assert!(lines[1].1.starts_with("  [1] "));
+        let env: Vec<&(Verbosity, String)> = lines
+            .iter()
+            .filter(|(level, _)| *level == Verbosity::Debug)
+            .collect();
+        assert_eq!(env.len(), 2);
+        assert!(env[0].1.ends_with("GREETING='hello world'"));
+        assert!(env[1].1.ends_with("NAME=yake"));
+    }
+
+    #[test]
+    fn test_get_target_base_dir() {
+        let yake = get_which_yake();
+
+        assert_eq!(
+            yake.get_target_base_dir("api.build"),
+            PathBuf::from("tests/fixtures/which/api")
+        );
+        assert_eq!(
+            yake.get_target_dir("api.test").unwrap(),
+            PathBuf::from("tests/fixtures/which/api")
+        );
+        assert_eq!(
+            yake.get_target_base_dir("api"),
+            PathBuf::from("tests/fixtures/which")
+        );
+        assert_eq!(
+            yake.get_target_base_dir("services.lint"),
+            PathBuf::from("tests/fixtures/which")
+        );
+    }
+
+    #[test]
+    fn test_container_invocation() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          TOKEN: secret
+        targets:
+          build:
+            meta:
+              doc: "Builds in a container"
+              type: group
+            container:
+              image: rust:1.80
+              volumes:
+                - ./target:/cache
+                - cargo:/usr/local/cargo
+              args: [--network, host]
+            targets:
+              app:
+                meta:
+                  doc: "Builds the app"
+                  type: callable
+                env:
+                  MODE: release
+                exec:
+                  - cargo build
+          local:
+            meta:
+              doc: "Runs on the host"
+              type: callable
+            exec:
+              - cargo build
+        "###;
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        yake.source = Some(PathBuf::from("/project/Yakefile"));
+        let command = YakeCommand::Plain("cargo build".to_string());
+
+        assert_eq!(
+            yake.get_invocation("build.app", &command).unwrap(),
+            vec![
+                "docker",
+                "run",
+                "--rm",
+                "--interactive",
+                "--volume",
+                "/project:/workspace",
+                "--workdir",
+                "/workspace",
+                "--volume",
+                "/project/./target:/cache",
+                "--volume",
+                "cargo:/usr/local/cargo",
+                "--env",
+                "MODE",
+                "--env",
+                "TOKEN",
+                "--network",
+                "host",
+                "rust:1.80",
+                "sh",
+                "-c",
+                "cargo build"
+            ]
+        );
+        let mut local = default_shell();
+        local.push("cargo build".to_string());
+        assert_eq!(yake.get_invocation("local", &command).unwrap(), local);
+
+        let invalid = YakeTarget {
+            container: Some(YakeContainer::default()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_remote_invocation() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        env:
+          TOKEN: "it's secret"
+        targets:
+          deploy:
+            meta:
+              doc: "Deploys on the server"
+              type: callable
+            secrets:
+              - TOKEN
+            remote:
+              host: example.com
+              user: deploy
+              port: 2222
+              key: keys/id_ed25519
+              dir: /srv/app
+            exec:
+              - ./deploy.sh
+          console:
+            meta:
+              doc: "Opens a console on the server"
+              type: callable
+            interactive: true
+            remote:
+              host: example.com
+            exec:
+              - ./console.sh
+          both:
+            meta:
+              doc: "Invalid"
+              type: callable
+            container:
+              image: alpine
+            remote:
+              host: example.com
+        "###;
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        yake.source = Some(PathBuf::from("/project/Yakefile"));
+        let command = YakeCommand::Plain("./deploy.sh".to_string());
+        let script = String::from_utf8(yake.get_remote_stdin("deploy").unwrap().unwrap()).unwrap();
+
+        assert_eq!(
+            yake.get_invocation("deploy", &command).unwrap(),
+            vec![
+                "ssh",
+                "-T",
+                "-o",
+                "BatchMode=yes",
+                "-p",
+                "2222",
+                "-i",
+                "/project/keys/id_ed25519",
+                "deploy@example.com",
+                &format!(
+                    "eval \"$(dd bs=1 count={} 2>/dev/null)\" && cd /srv/app && sh -c ./deploy.sh",
+                    script.len()
+                )
+            ]
+        );
+        // the env is written to stdin instead, ahead of the input of the command
+        assert!(script.contains("export TOKEN='it'\\''s secret'"));
+        let envs = yake.get_target_env_vars("deploy").unwrap();
+        let stdin = YakeCommand::Detailed(YakeCommandDetails {
+            cmd: "./deploy.sh".to_string(),
+            stdin: Some("input".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            yake.get_process_stdin("deploy", &stdin, &envs).unwrap(),
+            Some(format!("{}input", script).into_bytes())
+        );
+
+        // interactive targets get the env in the arguments, so they must not have
+        // secrets
+        let console = YakeCommand::Plain("./console.sh".to_string());
+        let invocation = yake.get_invocation("console", &console).unwrap();
+        assert!(invocation[3].starts_with("export TOKEN='it'\\''s secret' && "));
+        assert_eq!(yake.get_remote_stdin("console").unwrap(), None);
+        yake.targets.get_mut("console").unwrap().secrets = Some(vec!["TOKEN".to_string()]);
+        assert_eq!(
+            yake.get_invocation("console", &console).unwrap_err(),
+            "Interactive target console must not pass secrets to a remote host"
+        );
+
+        let lines = Mutex::new(Vec::new());
+        let report = |_: Verbosity, text: &str| lines.lock().unwrap().push(text.to_string());
+        yake.report_invocation("deploy", &command, "", Verbosity::Verbose, report)
+            .unwrap();
+        let lines = lines.into_inner().unwrap();
+        assert!(!lines[1].contains("it'\\''s secret"));
+        assert!(!lines[1].contains("TOKEN"));
+
+        assert!(yake.get_invocation("both", &command).is_err());
+        assert!(yake.get_target_by_name("both").unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_expand_matrices() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          test:
+            meta:
+              doc: "Tests"
+              type: callable
+              depends:
+                - lint
+            cwd: tests
+            matrix:
+              python: ["3.11", "3.12"]
+              db: [postgres]
+            exec:
+              - tox -e py{{python}}-$db
+          lint:
+            meta:
+              doc: "Lints"
+              type: callable
+        "###;
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let matrix = yake.targets["test"].matrix.clone().unwrap();
+        assert_eq!(
+            matrix.combinations(),
+            vec![
+                vec![
+                    ("python".to_string(), "3.11".to_string()),
+                    ("db".to_string(), "postgres".to_string())
+                ],
+                vec![
+                    ("python".to_string(), "3.12".to_string()),
+                    ("db".to_string(), "postgres".to_string())
+                ],
+            ]
+        );
+
+        yake.expand_matrices().unwrap();
+        let group = yake.get_target_by_name("test").unwrap();
+        assert_eq!(group.meta.target_type, YakeTargetType::Group);
+        assert_eq!(group.meta.depends, Some(vec!["lint".to_string()]));
+        assert_eq!(group.cwd, Some("tests".to_string()));
+        let target = yake.get_target_by_name("test.3_11-postgres").unwrap();
+        assert_eq!(target.meta.doc, "Tests (python=3.11, db=postgres)");
+        assert_eq!(target.meta.depends, None);
+        assert_eq!(target.cwd, None);
+        assert_eq!(
+            yake.get_target_commands("test.3_12-postgres", &target)
+                .unwrap(),
+            vec![YakeCommand::Plain("tox -e py3.12-$db".to_string())]
+        );
+
+        let mut invalid = yake.clone();
+        invalid.targets.get_mut("lint").unwrap().matrix =
+            Some(YakeMatrix(vec![("os".to_string(), Vec::new())]));
+        assert_eq!(
+            invalid.expand_matrices().unwrap_err(),
+            "Invalid matrix of target lint: os has no values"
+        );
+    }
+
+    #[test]
+    fn test_expand_snippets() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        snippets:
+          login:
+            - docker login
+          push:
+            - use: login
+            - cmd: docker push app
+              expected_exit_codes: [0, 1]
+        before_all:
+          - use: login
+        targets:
+          images:
+            meta:
+              doc: "Images"
+              type: group
+            targets:
+              app:
+                meta:
+                  doc: "App"
+                  type: callable
+                exec:
+                  - docker build .
+                  - use: push
+        "###;
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        yake.expand_snippets().unwrap();
+        assert_eq!(
+            yake.before_all,
+            Some(vec![YakeCommand::Plain("docker login".to_string())])
         );
-        return dependencies;
-    }
+        let exec = yake.get_target_by_name("images.app").unwrap().exec.unwrap();
+        assert_eq!(
+            exec.iter().map(|c| c.get_command()).collect::<Vec<_>>(),
+            vec!["docker build .", "docker login", "docker push app"]
+        );
+        assert!(yake.validate().is_ok());
 
-    fn get_yake() -> Yake {
-        let targets = get_yake_targets();
-        let dependencies = get_yake_dependencies(&targets);
-        let mut env_root = HashMap::new();
-        env_root.insert("BASE".to_string(), "BASEVAL".to_string());
+        let mut unknown = yake.clone();
+        unknown.targets.get_mut("images").unwrap().exec =
+            Some(vec![YakeCommand::Use(YakeUseCommand {
+                snippet: "deploy".to_string(),
+            })]);
+        assert_eq!(
+            unknown.expand_snippets().unwrap_err(),
+            "Invalid target images: Unknown snippet deploy"
+        );
 
-        Yake {
-            targets,
-            dependencies,
-            env: Some(env_root),
-            meta: YakeMeta {
-                doc: "Bla".to_string(),
-                version: "1.0.0".to_string(),
-                include_recursively: None,
-            },
-            all_targets: HashMap::new(),
-        }
+        let mut cyclic = yake.clone();
+        let use_push = YakeCommand::Use(YakeUseCommand {
+            snippet: "push".to_string(),
+        });
+        cyclic
+            .snippets
+            .as_mut()
+            .unwrap()
+            .insert("login".to_string(), vec![use_push.clone()]);
+        cyclic.after_all = Some(vec![use_push.clone()]);
+        assert_eq!(
+            cyclic.expand_snippets().unwrap_err(),
+            "Invalid after_all: Cyclic use of snippets: push -> login -> push"
+        );
+        assert_eq!(
+            use_push.validate(&YakeTarget::default()),
+            Err("Command \"use: push\" must be expanded before running it".to_string())
+        );
     }
 
     #[test]
-    fn test_get_all_targets() {
-        let yake = get_yake();
+    fn test_validate_set_commands() {
+        let set = |line: &str| {
+            YakeCommand::Set(YakeSetCommand {
+                set: line.to_string(),
+            })
+        };
+        let target = YakeTarget::default();
+        let command = set("VERSION = git describe --tags");
 
-        let all_targets = yake.get_all_targets();
-        assert_eq!(all_targets.len(), 4);
-    }
+        assert_eq!(command.get_output_var(), Some("VERSION"));
+        assert_eq!(command.get_command(), "git describe --tags");
+        assert_eq!(
+            command.with_command("echo 1".to_string()),
+            set("VERSION = echo 1")
+        );
+        assert!(command.validate(&target).is_ok());
+        assert!(set("git describe").validate(&target).is_err());
+        assert!(set("1X = git describe").validate(&target).is_err());
+        let parallel = YakeTarget {
+            parallel: Some(true),
+            ..Default::default()
+        };
+        assert!(command.validate(&parallel).is_err());
 
-    #[test]
-    fn test_get_all_dependencies() {
-        let yake = get_yake();
-        let dependencies = yake.get_all_dependencies();
-        assert_eq!(dependencies.len(), 3);
-        assert_eq!(dependencies.get("test").unwrap().len(), 1);
-        assert_eq!(dependencies.get("base").unwrap().len(), 0);
-        assert_eq!(dependencies.get("group.sub").unwrap().len(), 1);
+        let commands: Vec<YakeCommand> =
+            serde_yaml::from_str("- echo\n- set: A = echo a\n- cmd: echo b\n").unwrap();
+        assert_eq!(commands[1], set("A = echo a"));
     }
 
     #[test]
-    fn test_get_target_by_name() {
-        let yake = get_yake();
-        assert_eq!(yake.get_target_by_name("group.sub").is_some(), true);
-        assert_eq!(yake.get_target_by_name("base").is_some(), true);
-        assert_eq!(yake.get_target_by_name("sub").is_none(), true);
-    }
+    fn test_validate_script_mode() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          script:
+            meta:
+              doc: "A script"
+              type: callable
+            script_mode: true
+            before:
+              - echo before
+            exec:
+              - cd build
+              - make
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let target = yake.get_target_by_name("script").unwrap();
+        let commands = yake.get_target_commands("script", &target).unwrap();
 
-    #[test]
-    fn test_has_target_name() {
-        let yake = get_yake();
-        assert_eq!(yake.has_target_name("group.sub").is_ok(), true);
-        assert_eq!(yake.has_target_name("sub").is_err(), true);
-        assert_eq!(yake.has_target_name("sub").err().unwrap().len(), 3);
-    }
+        assert!(target.validate().is_ok());
+        assert_eq!(
+            commands,
+            vec![YakeCommand::Plain("cd build\nmake".to_string())]
+        );
 
-    #[test]
-    fn test_get_target_names() {
-        let yake = get_yake();
-        let names = yake.get_target_names();
-        assert_eq!(names.len(), 3);
-        assert_eq!(names.contains(&"group.sub".to_string()), true);
-        assert_eq!(names.contains(&"base".to_string()), true);
-        assert_eq!(names.contains(&"test".to_string()), true);
+        let parallel = YakeTarget {
+            parallel: Some(true),
+            ..target.clone()
+        };
+        assert!(parallel.validate().is_err());
+        let detailed = YakeTarget {
+            exec: serde_yaml::from_str("- cmd: make\n  timeout: 1m\n").unwrap(),
+            ..target
+        };
+        assert!(detailed.validate().is_err());
     }
 
     #[test]
-    fn test_get_dependencies_by_name() {
-        let yake = get_yake();
-        let dependencies = yake.get_dependencies_by_name("group.sub");
-        assert_eq!(dependencies.len(), 1);
-        assert_eq!(dependencies[0].meta.doc, "Base".to_string());
+    fn test_shell_opts() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+          shell_opts: set -eu
+        targets:
+          lenient:
+            meta:
+              doc: "Uses the options of the meta data"
+              type: callable
+          strict:
+            meta:
+              doc: "Strict targets"
+              type: group
+            shell_opts: set -euo pipefail
+            targets:
+              pipe:
+                meta:
+                  doc: "Inherits the options of the group"
+                  type: callable
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+
+        assert_eq!(
+            yake.get_target_shell_opts("lenient"),
+            Some("set -eu".to_string())
+        );
+        assert_eq!(
+            yake.get_target_shell_opts("strict.pipe"),
+            Some("set -euo pipefail".to_string())
+        );
+        assert_eq!(get_yake().get_target_shell_opts("base"), None);
+
+        let command = YakeCommand::Plain("false | true".to_string());
+        assert_eq!(
+            yake.get_invocation("strict.pipe", &command).unwrap(),
+            vec!["bash", "-c", "set -euo pipefail\nfalse | true"]
+        );
+        let succeeds = |target_name: &str| {
+            yake.run_command(target_name, &command, |_, _| {})
+                .unwrap()
+                .success()
+        };
+        assert!(!succeeds("strict.pipe"));
+        assert!(succeeds("lenient"));
     }
 
     #[test]
-    fn test_get_env_vars() {
-        let yake = get_yake();
+    fn test_validate_service() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          db:
+            meta:
+              doc: "A database"
+              type: service
+            exec:
+              - cd data
+              - exec postgres
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let target = yake.get_target_by_name("db").unwrap();
 
-        let envs = yake.get_target_env_vars("base").unwrap_or_default();
-        assert_eq!(envs.len(), 1);
-        assert_eq!(envs.get("BASE").unwrap(), "BASEVAL");
+        assert!(target.validate().is_ok());
+        assert_eq!(yake.get_service_names(), vec!["db"]);
+        assert_eq!(yake.get_target_names(), vec!["db"]);
+        assert_eq!(
+            yake.get_target_commands("db", &target).unwrap(),
+            vec![YakeCommand::Plain("cd data\nexec postgres".to_string())]
+        );
 
-        let envs = yake.get_target_env_vars("test").unwrap_or_default();
-        assert_eq!(envs.len(), 3);
-        assert_eq!(envs.get("BASE").unwrap(), "BASEVAL");
-        assert_eq!(envs.get("WEBAPP_PORT").unwrap(), "6543");
-        assert_eq!(envs.get("POSTGRES_PORT").unwrap(), "5432");
+        let after = YakeTarget {
+            after: Some(vec![YakeCommand::Plain("echo".to_string())]),
+            ..target.clone()
+        };
+        assert!(after.validate().is_err());
+        let timeout = YakeTarget {
+            timeout: Some("1m".to_string()),
+            ..target.clone()
+        };
+        assert!(timeout.validate().is_err());
+        let empty = YakeTarget {
+            exec: None,
+            ..target
+        };
+        assert!(empty.validate().is_err());
+    }
 
-        let envs = yake.get_target_env_vars("group").unwrap_or_default();
-        assert_eq!(envs.len(), 1);
-        assert_eq!(envs.get("BASE").unwrap(), "BASEVAL");
+    #[test]
+    fn test_apply_profile() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        profiles:
+          prod:
+            targets:
+              group.sub:
+                timeout: 5m
+                env:
+                  STAGE: prod
+          broken:
+            targets:
+              group.sub:
+                timout: 5m
+          invalid:
+            targets:
+              group.sub:
+                parallel: "yes"
+        targets:
+          group:
+            meta:
+              doc: "A group"
+              type: group
+            targets:
+              sub:
+                meta:
+                  doc: "A sub target"
+                  type: callable
+                env:
+                  NAME: sub
+                exec:
+                  - echo "sub"
+        "###;
+        let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
 
-        let envs = yake.get_target_env_vars("group.sub").unwrap_or_default();
-        assert_eq!(envs.len(), 3);
-        assert_eq!(envs.get("BASE").unwrap(), "OVERWRITE");
-        assert_eq!(envs.get("DOCKER_PORT").unwrap(), "1234");
-        assert_eq!(envs.get("POSTGRES_PORT").unwrap(), "54322");
+        let mut prod = yake.clone();
+        prod.apply_profile("prod").unwrap();
+        let sub = prod.get_target_by_name("group.sub").unwrap();
+        assert_eq!(sub.timeout, Some("5m".to_string()));
+        assert_eq!(sub.env.unwrap().len(), 2);
+
+        assert_eq!(
+            yake.clone().apply_profile("staging").unwrap_err(),
+            "Unknown profile: staging, the Yakefile defines broken, invalid, prod"
+        );
+        assert_eq!(
+            yake.clone().apply_profile("broken").unwrap_err(),
+            "Invalid target group.sub of profile broken: Unknown field timout"
+        );
+        assert!(yake
+            .clone()
+            .apply_profile("invalid")
+            .unwrap_err()
+            .starts_with("Invalid target group.sub of profile invalid: "));
     }
 
     #[test]
-    #[should_panic]
-    fn test_get_env_vars_bad() {
-        let mut env = HashMap::new();
-        env.insert("WEBAPP_PORT".to_string(), "6543".to_string());
-        env.insert("PATH".to_string(), "$HOME/bin:$PATH".to_string());
-        let mut yake = get_yake();
-        yake.env = Some(env);
+    fn test_os_commands() {
+        let yml = r###"
+        - linux: make
+          macos: gmake
+        - windows: nmake
+        - default: make
+        - {}
+        "###;
+        let commands: Vec<YakeCommand> = serde_yaml::from_str(yml).expect("Unable to parse");
+        let runs: Vec<bool> = commands.iter().map(YakeCommand::runs_on_this_os).collect();
+        assert_eq!(
+            runs,
+            vec![
+                ["linux", "macos"].contains(&env::consts::OS),
+                env::consts::OS == "windows",
+                true,
+                false
+            ]
+        );
+        assert_eq!(commands[2].get_command(), "make");
 
-        let _ = yake.get_target_env_vars("base");
+        let target = YakeTarget::default();
+        assert!(commands[0].validate(&target).is_ok());
+        assert_eq!(
+            commands[3].validate(&target).unwrap_err(),
+            "Command variants must give at least one of linux, macos, windows or default"
+        );
     }
 
     #[test]
-    fn test_deserialize_yake_target_type() {
+    fn test_add_sub_yake_conflict() {
         let yml = r###"
         meta:
           doc: "Some docs"
-          version: 1.0.0
-        env:
-          PATH: $HOME/bin:$PATH
+          version: 0.1.0
         targets:
-          base:
+          sub:
             meta:
-              doc: "Test command"
+              doc: "Completed by the sub yake"
+              type: group
+            targets:
+              build:
+                meta:
+                  doc: "Root build"
+                  type: callable
+              db:
+                meta:
+                  doc: "Root db"
+                  type: group
+                targets:
+                  migrate:
+                    meta:
+                      doc: "Root migrate"
+                      type: callable
+        "###;
+        let subyml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          build:
+            meta:
+              doc: "Sub build"
               type: callable
-            exec:
-              - echo "i'm base"
-          group:
+          db:
             meta:
-              doc: "Test command"
+              doc: "Sub db"
               type: group
+              override: true
+            targets:
+              migrate:
+                meta:
+                  doc: "Sub migrate"
+                  type: callable
         "###;
 
-        let yake: Yake = serde_yaml::from_str(&yml).expect("Unable to parse");
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let mut sub_yake: Yake = serde_yaml::from_str(subyml).expect("Unable to parse");
+        sub_yake.source = Some(PathBuf::from("sub/Yakefile"));
         assert_eq!(
-            yake.targets.get("base").unwrap().meta.target_type,
-            YakeTargetType::Callable
+            yake.add_sub_yake(sub_yake.clone()).unwrap_err(),
+            "Target sub.build is defined by Yakefile and sub/Yakefile, mark it with `override: true` to replace it"
         );
         assert_eq!(
-            yake.targets.get("group").unwrap().meta.target_type,
-            YakeTargetType::Group
+            yake.get_target_by_name("sub.build").unwrap().meta.doc,
+            "Root build"
+        );
+
+        sub_yake.targets.get_mut("build").unwrap().meta.overrides = Some(true);
+        yake.add_sub_yake(sub_yake).unwrap();
+        assert_eq!(
+            yake.get_target_by_name("sub.build").unwrap().meta.doc,
+            "Sub build"
+        );
+        assert_eq!(
+            yake.get_target_by_name("sub.db.migrate").unwrap().meta.doc,
+            "Sub migrate"
         );
     }
 
     #[test]
-    fn test_add_sub_yakes() {
+    fn test_add_sub_yake_include_strategy() {
         let yml = r###"
         meta:
           doc: "Some docs"
-          version: 1.0.0
+          version: 0.1.0
         env:
-          PATH: $HOME/bin:$PATH
+          STAGE: dev
         targets:
-          base:
+          sub:
             meta:
-              doc: "Test command"
-              type: callable
-            exec:
-              - echo "i'm base"
-          group:
-            meta:
-              doc: "Test command"
+              doc: "Completed by the sub yake"
               type: group
+            targets:
+              build:
+                meta:
+                  doc: "Root build"
+                  type: callable
         "###;
-
         let subyml = r###"
         meta:
           doc: "Some docs"
-          version: 1.0.0
+          version: 0.1.0
         env:
-          PATH: $HOME/bin:$PATH
+          STAGE: test
         targets:
-          base:
-            meta:
-              doc: "Test command overwritten"
-              type: callable
-            exec:
-              - echo "i'm base, but overwritten by a sub yake"
-          sub_base:
+          build:
             meta:
-              doc: "Sub: Test command"
+              doc: "Sub build"
               type: callable
-            exec:
-              - echo "i'm sub base"
+              override: true
         "###;
 
-        let mut yake: Yake = serde_yaml::from_str(&yml).expect("Unable to parse");
-        assert_eq!(
-            yake.targets.get("base").unwrap().meta.target_type,
-            YakeTargetType::Callable
-        );
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+        let mut sub_yake: Yake = serde_yaml::from_str(subyml).expect("Unable to parse");
+        sub_yake.source = Some(PathBuf::from("sub/Yakefile"));
         assert_eq!(
-            yake.targets.get("group").unwrap().meta.target_type,
-            YakeTargetType::Group
+            yake.add_sub_yake(sub_yake.clone()).unwrap_err(),
+            "Env variable STAGE is set to different values by Yakefile and sub/Yakefile"
         );
 
-        let sub_yake: Yake = serde_yaml::from_str(subyml).expect("Unable to parse");
+        sub_yake.targets.get_mut("build").unwrap().meta.overrides = None;
+        yake.meta.include_strategy = Some(IncludeStrategy::Override);
+        yake.add_sub_yake(sub_yake).unwrap();
+        let build = yake.get_target_by_name("sub.build").unwrap();
+        assert_eq!(build.meta.doc, "Sub build");
+        assert_eq!(build.env.unwrap().get("STAGE"), Some(&"test".to_string()));
+    }
+
+    #[test]
+    fn test_failures_exit_code() {
+        let failed = |exit_code| YakeError::Failed {
+            message: "failed".to_string(),
+            exit_code,
+        };
+        assert_eq!(failures_exit_code(&[]), 0);
+        assert_eq!(failures_exit_code(&[(0, failed(4))]), 4);
         assert_eq!(
-            sub_yake.targets.get("base").unwrap().meta.target_type,
-            YakeTargetType::Callable
+            failures_exit_code(&[(0, failed(TIMEOUT_EXIT_CODE))]),
+            TIMEOUT_EXIT_CODE
         );
         assert_eq!(
-            sub_yake.targets.get("sub_base").unwrap().meta.target_type,
-            YakeTargetType::Callable
+            failures_exit_code(&[(0, failed(4)), (2, failed(4))]),
+            FAILED_EXIT_CODE
         );
+    }
+
+    #[test]
+    fn test_get_group_children() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          checks:
+            meta:
+              doc: "Runs all checks"
+              type: group
+            targets:
+              unit:
+                meta:
+                  doc: "Unit tests"
+                  type: callable
+              lint:
+                meta:
+                  doc: "Lints"
+                  type: callable
+              audit:
+                meta:
+                  doc: "Audits dependencies"
+                  type: callable
+        "###;
+        let subyml = r###"
+        meta:
+          doc: "More checks"
+          version: 0.1.0
+        targets:
+          zeta:
+            meta:
+              doc: "Checks zeta"
+              type: callable
+          alpha:
+            meta:
+              doc: "Checks alpha"
+              type: callable
+        "###;
 
-        yake.add_sub_yake(sub_yake);
+        let mut yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
         assert_eq!(
-            yake.targets.get("base").unwrap().meta.target_type,
-            YakeTargetType::Callable
+            yake.get_group_children("checks"),
+            vec!["checks.unit", "checks.lint", "checks.audit"]
         );
+
+        // children of an included Yakefile follow the ones the group has already
+        let mut sub_yake: Yake = serde_yaml::from_str(subyml).expect("Unable to parse");
+        sub_yake.source = Some(PathBuf::from("checks/Yakefile"));
+        yake.add_sub_yake(sub_yake).unwrap();
         assert_eq!(
-            yake.targets.get("base").unwrap().meta.doc,
-            "Test command overwritten"
+            yake.get_group_children("checks"),
+            vec![
+                "checks.unit",
+                "checks.lint",
+                "checks.audit",
+                "checks.zeta",
+                "checks.alpha"
+            ]
         );
+
+        // the order is kept when the targets are serialized, e.g. for extends
+        let value = serde_yaml::to_value(&yake).unwrap();
+        let yake = Yake::deserialize(value).unwrap();
         assert_eq!(
-            yake.targets.get("sub_base").unwrap().meta.target_type,
-            YakeTargetType::Callable
+            yake.get_group_children("checks")[3..],
+            ["checks.zeta", "checks.alpha"]
         );
     }
+
+    #[test]
+    fn test_schedule_declaration_order() {
+        let yml = r###"
+        meta:
+          doc: "Some docs"
+          version: 0.1.0
+        targets:
+          release:
+            meta:
+              doc: "Depends on targets declared out of alphabetical order"
+              type: callable
+              depends:
+                - package
+                - docs
+                - checks
+          package:
+            meta:
+              doc: "Depends on compile and assets"
+              type: callable
+              depends:
+                - compile
+                - assets
+          docs:
+            meta:
+              doc: "Depends on assets"
+              type: callable
+              depends:
+                - assets
+          compile:
+            meta:
+              doc: "Compiles"
+              type: callable
+          assets:
+            meta:
+              doc: "Bundles assets"
+              type: callable
+          checks:
+            meta:
+              doc: "Runs all checks"
+              type: group
+            targets:
+              unit:
+                meta:
+                  doc: "Unit tests"
+                  type: callable
+              lint:
+                meta:
+                  doc: "Lints"
+                  type: callable
+              audit:
+                meta:
+                  doc: "Audits dependencies"
+                  type: callable
+        "###;
+
+        // each parse gets differently seeded maps
+        for _ in 0..10 {
+            let yake: Yake = serde_yaml::from_str(yml).expect("Unable to parse");
+            let options = ExecutionOptions::default();
+            let mut execution = get_execution(&yake, &options);
+            yake.schedule_target(&None, "release", &mut execution)
+                .unwrap();
+            let names: Vec<&str> = execution
+                .scheduled
+                .iter()
+                .map(|s| s.target_name.as_str())
+                .collect();
+            assert_eq!(
+                names,
+                vec![
+                    "compile",
+                    "assets",
+                    "package",
+                    "docs",
+                    "checks.unit",
+                    "checks.lint",
+                    "checks.audit",
+                    "checks",
+                    "release"
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_execute_parallel_bounded_by_jobs() {
+        let mut yake = get_parallel_yake();
+        yake.targets.get_mut("limited").unwrap().max_parallel = None;
+        let options = ExecutionOptions {
+            jobs: 2,
+            ..ExecutionOptions::default()
+        };
+        let start = Instant::now();
+        assert!(yake.execute("limited", &options).is_ok());
+        let elapsed = start.elapsed();
+        // two commands run in the first wave, the third one in the second
+        assert!(elapsed >= Duration::from_millis(400));
+        assert!(elapsed < Duration::from_millis(600));
+    }
 }
@@ -1,37 +1,829 @@
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
-use serde_yaml;
+use gitignore::GitIgnore;
+use glob::{glob, MatchOptions, Pattern};
+use remote::{fetch_remote_include, is_remote_include, split_pin};
+use reporter::Logger;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use serde_yaml::{self, Mapping, Value};
+use toml;
 use walkdir::{DirEntry, WalkDir};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
 
-use yake::Yake;
+use error::YakeError;
+use yake::{
+    deserialize_targets, Yake, YakeCommandDetails, YakeContainer, YakeParamDetails, YakeRemote,
+    YakeRequiredEnvDetails, YakeTarget,
+};
 
-pub fn load_yml_from_file(filename: &str) -> Yake {
-    let mut f = File::open(filename).expect("File not found.");
+/// Names of Yakefiles, in the order they are looked for in a directory. The format is
+/// detected by the extension, see `parse_yml_from_file`.
+const YAKEFILE_NAMES: [&str; 3] = ["Yakefile", "Yakefile.toml", "Yakefile.json"];
+
+/// Loads a Yakefile given on the command line or found by yake.
+pub fn load_yml_from_file(path: &Path) -> Result<Yake, YakeError> {
+    read_yml_from_file(path).map_err(|message| YakeError::Load {
+        path: path.to_path_buf(),
+        message,
+    })
+}
+
+/// Reads and parses a Yakefile. The path is remembered as the source of the yake.
+pub fn read_yml_from_file(path: &Path) -> Result<Yake, String> {
+    let yake = parse_yml_from_file(path)?;
+    yake.validate()?;
+    Ok(yake)
+}
+
+/// Reads and parses a Yakefile without checking it's targets for invalid options.
+/// Yakefiles ending with `.toml` are parsed as TOML, those ending with `.json` as
+/// JSON and all others as YAML, see `parse_yml_documents`. A Yakefile with `extends`
+/// is merged over the Yakefile it extends, see `merge_values`, targets with `extends`
+/// over the target they extend.
+pub fn parse_yml_from_file(path: &Path) -> Result<Yake, String> {
+    let mut yake = parse_extended_yakefile(path, &mut Vec::new())?;
+    yake.source = Some(path.to_path_buf());
+    yake.expand_snippets()?;
+    yake.expand_matrices()?;
+
+    Ok(yake)
+}
+
+/// Parses a Yakefile merged over the Yakefiles it extends, `extending` holds the
+/// Yakefiles extending it for detecting cycles.
+fn parse_extended_yakefile(path: &Path, extending: &mut Vec<PathBuf>) -> Result<Yake, String> {
+    let mut f = File::open(path).map_err(|e| format!("File not found: {}", e))?;
     let mut contents = String::new();
 
     f.read_to_string(&mut contents)
-        .expect("Error while reading file.");
+        .map_err(|e| format!("Error while reading file: {}", e))?;
+
+    let format = path.extension().and_then(|extension| extension.to_str());
+    let value = parse_value(&contents, format).ok();
+    let base = match value.as_ref().and_then(|value| value.get("extends")) {
+        Some(Value::String(extends)) => Some(parse_base_yakefile(path, extends, extending)?),
+        Some(_) => {
+            return Err("Unable to parse: extends must be the path of a Yakefile".to_string())
+        }
+        // targets extending other targets are resolved on the value, which loses the
+        // locations of parse errors, so it is only parsed as value if needed
+        None if value.as_ref().is_some_and(extends_targets) => Some(Value::Null),
+        None => None,
+    };
+
+    let parsed = match (format, base) {
+        (Some("toml"), None) => toml::from_str(&contents).map_err(|e| {
+            let location = e.line_col().map(|(line, column)| (line + 1, column + 1));
+            with_snippet(e.to_string(), &contents, location)
+        }),
+        (Some("json"), None) => serde_json::from_str(&contents)
+            .map_err(|e| with_snippet(e.to_string(), &contents, Some((e.line(), e.column())))),
+        (Some("toml"), Some(base)) | (Some("json"), Some(base)) => parse_value(&contents, format)
+            .and_then(|value| resolve_target_extends(merge_values(base, value)))
+            .and_then(|value| Yake::deserialize(value).map_err(|e| e.to_string())),
+        (_, base) => parse_yml_documents(&contents, base),
+    };
+    parsed.map_err(|e| format!("Unable to parse: {}", e))
+}
+
+/// Parses the Yakefile extended by another one, relative to it, as a value for
+/// merging the other one over it.
+fn parse_base_yakefile(
+    path: &Path,
+    extends: &str,
+    extending: &mut Vec<PathBuf>,
+) -> Result<Value, String> {
+    let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(pos) = extending
+        .iter()
+        .position(|extended| *extended == canonical_path)
+    {
+        let mut cycle: Vec<String> = extending[pos..]
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        cycle.push(canonical_path.display().to_string());
+        return Err(format!("Cyclic extends: {}", cycle.join(" -> ")));
+    }
+    extending.push(canonical_path);
+
+    let base_path = path.parent().unwrap_or(Path::new("")).join(extends);
+    let base = parse_extended_yakefile(&base_path, extending).map_err(|e| {
+        if e.starts_with("Cyclic extends") {
+            e
+        } else {
+            format!("Unable to load {}: {}", base_path.display(), e)
+        }
+    })?;
+    extending.pop();
+    serde_yaml::to_value(&base).map_err(|e| e.to_string())
+}
+
+/// Parses a Yakefile into a value, the first document of a YAML Yakefile.
+fn parse_value(contents: &str, format: Option<&str>) -> Result<Value, String> {
+    match format {
+        Some("toml") => toml::from_str(contents).map_err(|e| e.to_string()),
+        Some("json") => serde_json::from_str(contents).map_err(|e| e.to_string()),
+        _ => match serde_yaml::Deserializer::from_str(contents).next() {
+            Some(document) => Value::deserialize(document).map_err(|e| e.to_string()),
+            None => Ok(Value::Null),
+        },
+    }
+}
+
+/// Merges a Yakefile over the Yakefile it extends: mappings like the env, the targets
+/// and their fields are merged key by key, all other values like lists of commands
+/// replace the ones of the base.
+fn merge_values(base: Value, value: Value) -> Value {
+    match (base, value) {
+        (Value::Mapping(mut base), Value::Mapping(value)) => {
+            for (key, value) in value {
+                let merged = match base.remove(&key) {
+                    Some(base) => merge_values(base, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Mapping(base)
+        }
+        (base, Value::Null) => base,
+        (_, value) => value,
+    }
+}
+
+/// Checks whether a Yakefile value has targets extending other targets.
+fn extends_targets(value: &Value) -> bool {
+    match value.get("targets") {
+        Some(Value::Mapping(targets)) => targets.iter().any(|(_, target)| {
+            target
+                .get("extends")
+                .is_some_and(|extends| !extends.is_null())
+                || extends_targets(target)
+        }),
+        _ => false,
+    }
+}
+
+/// Checks whether a target or one of it's sub targets extends another target.
+fn extends_target(target: &YakeTarget) -> bool {
+    target.extends.is_some()
+        || target
+            .targets
+            .iter()
+            .flatten()
+            .any(|(_, target)| extends_target(target))
+}
+
+/// Resolves the targets of a Yakefile value extending other targets, given by their
+/// full names like `build.docker`. Such a target is merged over the target it extends,
+/// see `merge_values`, which is resolved before. The sub targets and the `internal`
+/// and `override` meta data are not inherited.
+fn resolve_target_extends(mut value: Value) -> Result<Value, String> {
+    if let Some(Value::Mapping(targets)) = value.get_mut("targets") {
+        let mut flat = HashMap::new();
+        collect_targets(targets, "", &mut flat);
+        let mut resolved = HashMap::new();
+        for name in flat.keys() {
+            resolve_target(name, &flat, &mut resolved, &mut Vec::new())?;
+        }
+        replace_targets(targets, "", &resolved);
+    }
+    Ok(value)
+}
+
+/// Collects the targets of a mapping and their sub targets by their full names,
+/// without their sub targets.
+fn collect_targets(targets: &Mapping, prefix: &str, flat: &mut HashMap<String, Value>) {
+    for (name, target) in targets {
+        let name = match name.as_str() {
+            Some(name) => format!("{}{}", prefix, name),
+            None => continue,
+        };
+        let mut target = target.clone();
+        if let Value::Mapping(ref mut fields) = target {
+            if let Some(Value::Mapping(children)) = fields.remove(&key("targets")) {
+                collect_targets(&children, &format!("{}.", name), flat);
+            }
+        }
+        flat.insert(name, target);
+    }
+}
+
+/// Resolves a target extending another one, `extending` holds the targets extending
+/// it for detecting cycles. Resolved targets are kept in `resolved`.
+fn resolve_target(
+    name: &str,
+    flat: &HashMap<String, Value>,
+    resolved: &mut HashMap<String, Value>,
+    extending: &mut Vec<String>,
+) -> Result<Value, String> {
+    if let Some(target) = resolved.get(name) {
+        return Ok(target.clone());
+    }
+    let mut target = flat[name].clone();
+    let base_name = match target.get("extends") {
+        Some(Value::String(base_name)) => base_name.clone(),
+        Some(Value::Null) | None => return Ok(target),
+        Some(_) => {
+            return Err(format!(
+                "Target {}: extends must be the name of a target",
+                name
+            ))
+        }
+    };
+    if let Some(pos) = extending.iter().position(|extended| extended == name) {
+        let mut cycle = extending[pos..].to_vec();
+        cycle.push(name.to_string());
+        return Err(format!("Cyclic extends of targets: {}", cycle.join(" -> ")));
+    }
+    if !flat.contains_key(&base_name) {
+        return Err(format!(
+            "Target {} extends unknown target {}",
+            name, base_name
+        ));
+    }
+
+    extending.push(name.to_string());
+    let mut base = resolve_target(&base_name, flat, resolved, extending)?;
+    extending.pop();
+    if let Some(Value::Mapping(meta)) = base.get_mut("meta") {
+        meta.remove(&key("internal"));
+        meta.remove(&key("override"));
+    }
+    if let Value::Mapping(ref mut fields) = target {
+        fields.remove(&key("extends"));
+    }
+    let target = merge_values(base, target);
+    resolved.insert(name.to_string(), target.clone());
+    Ok(target)
+}
 
-    serde_yaml::from_str(&contents).expect("Unable to parse")
+/// Replaces the targets of a mapping and their sub targets by their resolved values,
+/// keeping their sub targets.
+fn replace_targets(targets: &mut Mapping, prefix: &str, resolved: &HashMap<String, Value>) {
+    for (name, target) in targets.iter_mut() {
+        let name = match name.as_str() {
+            Some(name) => format!("{}{}", prefix, name),
+            None => continue,
+        };
+        let children = match *target {
+            Value::Mapping(ref mut fields) => fields.remove(&key("targets")),
+            _ => None,
+        };
+        if let Some(resolved) = resolved.get(&name) {
+            *target = resolved.clone();
+        }
+        if let Some(mut children) = children {
+            if let Value::Mapping(ref mut children) = children {
+                replace_targets(children, &format!("{}.", name), resolved);
+            }
+            if let Value::Mapping(ref mut fields) = *target {
+                fields.insert(key("targets"), children);
+            }
+        }
+    }
+}
+
+/// Key of a field in a mapping.
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+/// A document following the first one of a Yakefile.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct YakefileSection {
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default, deserialize_with = "deserialize_targets")]
+    targets: HashMap<String, YakeTarget>,
+}
+
+/// Parses a YAML Yakefile, which may be a stream of documents separated by `---`.
+/// The first document is the Yakefile, merged over the `base` Yakefile it extends if
+/// given and with it's targets extending other targets resolved, see
+/// `resolve_target_extends`. The following ones only add targets and env variables
+/// to it, for organizing a large Yakefile in sections. A target must only be defined
+/// in one of the documents.
+fn parse_yml_documents(contents: &str, base: Option<Value>) -> Result<Yake, String> {
+    let error = |e: serde_yaml::Error| {
+        // syntax errors are shared between the documents and have no location, parsing
+        // the contents again gets it
+        let location = e
+            .location()
+            .or_else(|| {
+                serde_yaml::from_str::<Value>(contents)
+                    .err()
+                    .and_then(|e| e.location())
+            })
+            .map(|location| (location.line(), location.column()));
+        with_snippet(e.to_string(), contents, location)
+    };
+    let mut documents = serde_yaml::Deserializer::from_str(contents);
+    let mut yake = match (documents.next(), base) {
+        (Some(document), None) => Yake::deserialize(document).map_err(error)?,
+        (Some(document), Some(base)) => {
+            let value = Value::deserialize(document).map_err(error)?;
+            let value = resolve_target_extends(merge_values(base, value))?;
+            Yake::deserialize(value).map_err(|e| e.to_string())?
+        }
+        (None, _) => return serde_yaml::from_str(contents).map_err(error),
+    };
+    for (i, document) in documents.enumerate() {
+        // an empty document, e.g. after a trailing `---`, adds nothing
+        let section = match Option::<YakefileSection>::deserialize(document) {
+            Ok(Some(section)) => section,
+            Ok(None) => continue,
+            Err(e) => return Err(format!("Document {}: {}", i + 2, error(e))),
+        };
+        if !section.env.is_empty() {
+            yake.env
+                .get_or_insert_with(HashMap::new)
+                .extend(section.env);
+        }
+        // the targets of a section follow the ones of the documents before
+        let offset = yake
+            .targets
+            .values()
+            .map(|target| target.position + 1)
+            .max()
+            .unwrap_or(0);
+        for (name, mut target) in section.targets {
+            target.position += offset;
+            if extends_target(&target) {
+                return Err(format!(
+                    "Document {}: target {} must not extend another target, only targets of the first document can",
+                    i + 2,
+                    name
+                ));
+            }
+            if yake.targets.contains_key(&name) {
+                return Err(format!(
+                    "Document {}: target {} is already defined by a document before",
+                    i + 2,
+                    name
+                ));
+            }
+            yake.targets.insert(name, target);
+        }
+    }
+    Ok(yake)
+}
+
+/// Appends the line of a parse error to it's message, with a caret pointing at the
+/// column. Lines and columns start at 1, errors without a location are kept.
+fn with_snippet(message: String, contents: &str, location: Option<(usize, usize)>) -> String {
+    let (line, column) = match location {
+        Some((line, column)) if line > 0 => (line, column),
+        _ => return message,
+    };
+    let text = match contents.lines().nth(line - 1) {
+        Some(text) => text.trim_end(),
+        None => return message,
+    };
+    let gutter = " ".repeat(line.to_string().len());
+    let indent: String = text
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!(
+        "{}\n {} | {}\n {} | {}^",
+        message, line, text, gutter, indent
+    )
+}
+
+/// Collects the events of a yaml parser together with their location.
+struct MarkedEvents(Vec<(Event, Marker)>);
+
+impl MarkedEventReceiver for MarkedEvents {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        self.0.push((ev, mark));
+    }
+}
+
+/// Finds the line number of a target's key within a Yakefile, e.g. `docker.postgres`
+/// is looked up as `targets -> docker -> targets -> postgres`. All documents of the
+/// Yakefile are searched.
+pub fn find_target_line(path: &Path, target_name: &str) -> Option<usize> {
+    let events = read_yml_events(path)?;
+
+    let mut keys = Vec::new();
+    for name in target_name.split('.') {
+        keys.push("targets");
+        keys.push(name);
+    }
+
+    let line = document_starts(&events).find_map(|pos| find_key_line(&events, pos, &keys));
+    line
+}
+
+/// Reads the events of the YAML parser for a Yakefile, along with their location.
+fn read_yml_events(path: &Path) -> Option<Vec<(Event, Marker)>> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .ok()?;
+
+    let mut events = MarkedEvents(Vec::new());
+    Parser::new(contents.chars()).load(&mut events, true).ok()?;
+    Some(events.0)
+}
+
+/// Positions of the root nodes of the documents among the events.
+fn document_starts(events: &[(Event, Marker)]) -> impl Iterator<Item = usize> + '_ {
+    events
+        .iter()
+        .enumerate()
+        .filter(|(_, (ev, _))| *ev == Event::DocumentStart)
+        .map(|(pos, _)| pos + 1)
+}
+
+/// Looks up a path of keys within the mapping starting at `pos`. Entries of sequences
+/// are given by their index.
+fn find_key_line(events: &[(Event, Marker)], mut pos: usize, keys: &[&str]) -> Option<usize> {
+    match events.get(pos)?.0 {
+        Event::MappingStart(_) => pos += 1,
+        Event::SequenceStart(_) => {
+            pos += 1;
+            for _ in 0..keys[0].parse::<usize>().ok()? {
+                pos = skip_node(events, pos)?;
+            }
+            return find_key_line(events, pos, &keys[1..]);
+        }
+        _ => return None,
+    }
+
+    loop {
+        let (key, mark) = match *events.get(pos)? {
+            (Event::Scalar(ref key, ..), mark) => (key, mark),
+            _ => return None,
+        };
+        if key == keys[0] {
+            return if keys.len() == 1 {
+                Some(mark.line())
+            } else {
+                find_key_line(events, pos + 1, &keys[1..])
+            };
+        }
+        pos = skip_node(events, pos + 1)?;
+    }
+}
+
+/// A field of a Yakefile which is not known to yake and therefore ignored, e.g. a typo
+/// like `exce`.
+#[derive(Debug, PartialEq)]
+struct UnknownField {
+    /// Index of the document of the Yakefile the field is in
+    document: usize,
+    /// Keys leading to the field, starting at the root of the document. Entries of
+    /// sequences are given by their index.
+    keys: Vec<String>,
+}
+
+impl UnknownField {
+    /// Describes the field, relative to the target it belongs to.
+    fn describe(&self) -> String {
+        let mut target = Vec::new();
+        let mut keys = &self.keys[..];
+        while keys.len() > 2 && keys[0] == "targets" {
+            target.push(keys[1].as_str());
+            keys = &keys[2..];
+        }
+        let parents: Vec<&str> = keys[..keys.len() - 1]
+            .iter()
+            .filter(|key| key.parse::<usize>().is_err())
+            .map(|key| key.as_str())
+            .collect();
+
+        let mut description = String::new();
+        if !target.is_empty() {
+            description.push_str(&format!("target {}: ", target.join(".")));
+        }
+        description.push_str(&format!("Unknown field {}", keys[keys.len() - 1]));
+        if !parents.is_empty() {
+            description.push_str(&format!(" in {}", parents.join(".")));
+        }
+        description
+    }
+}
+
+/// Finds the fields of a Yakefile yake does not know and ignores, which are usually
+/// typos. Each one is described along with the file and, except for TOML, the line.
+pub fn find_unknown_fields(path: &Path) -> Result<Vec<String>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("File not found: {}", e))?;
+    let documents: Vec<Value> = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => vec![toml::from_str(&contents).map_err(|e| e.to_string())?],
+        Some("json") => vec![serde_json::from_str(&contents).map_err(|e| e.to_string())?],
+        _ => serde_yaml::Deserializer::from_str(&contents)
+            .map(Value::deserialize)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?,
+    };
+
+    let mut fields = Vec::new();
+    for (document, value) in documents.iter().enumerate() {
+        let mut keys = Vec::new();
+        check_yakefile_fields(value, document, &mut keys, &mut fields);
+    }
+
+    let events = read_yml_events(path).unwrap_or_default();
+    let starts: Vec<usize> = document_starts(&events).collect();
+    Ok(fields
+        .iter()
+        .map(|field| {
+            let keys: Vec<&str> = field.keys.iter().map(|key| key.as_str()).collect();
+            let line = starts
+                .get(field.document)
+                .and_then(|&pos| find_key_line(&events, pos, &keys));
+            match line {
+                Some(line) => format!("{}:{}: {}", path.display(), line, field.describe()),
+                None => format!("{}: {}", path.display(), field.describe()),
+            }
+        })
+        .collect())
+}
+
+/// Fails if a Yakefile has fields yake does not know, listing all of them.
+pub fn deny_unknown_fields(path: &Path) -> Result<(), YakeError> {
+    let error = |message: String| YakeError::Load {
+        path: path.to_path_buf(),
+        message,
+    };
+    let fields = find_unknown_fields(path).map_err(error)?;
+    if fields.is_empty() {
+        return Ok(());
+    }
+    Err(error(format!("Unknown fields:\n{}", fields.join("\n"))))
+}
+
+/// Names of the fields of a type, as serialized from a value of it.
+fn field_names<T: Serialize>(value: &T) -> Vec<String> {
+    match serde_yaml::to_value(value) {
+        Ok(Value::Mapping(mapping)) => mapping
+            .iter()
+            .filter_map(|(key, _)| key.as_str().map(|key| key.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
-fn find_yakefiles(directory: &str) -> Result<Vec<DirEntry>, String> {
+/// Collects the keys of a mapping which are not among the known fields.
+fn check_fields(
+    value: &Value,
+    known: &[String],
+    document: usize,
+    keys: &mut Vec<String>,
+    unknown: &mut Vec<UnknownField>,
+) {
+    for (key, _) in value
+        .as_mapping()
+        .into_iter()
+        .flat_map(|mapping| mapping.iter())
+    {
+        let key = key.as_str().unwrap_or_default();
+        if !known.iter().any(|field| field == key) {
+            keys.push(key.to_string());
+            unknown.push(UnknownField {
+                document,
+                keys: keys.clone(),
+            });
+            keys.pop();
+        }
+    }
+}
+
+/// Calls `check` for a field of a mapping, if it is given.
+fn check_field<F>(value: &Value, name: &str, keys: &mut Vec<String>, check: F)
+where
+    F: FnOnce(&Value, &mut Vec<String>),
+{
+    if let Some(field) = value.get(name) {
+        keys.push(name.to_string());
+        check(field, keys);
+        keys.pop();
+    }
+}
+
+/// Calls `check` for each entry of a sequence or the values of a mapping.
+fn check_entries<F>(value: &Value, keys: &mut Vec<String>, mut check: F)
+where
+    F: FnMut(&Value, &mut Vec<String>),
+{
+    match *value {
+        Value::Sequence(ref entries) => {
+            for (i, entry) in entries.iter().enumerate() {
+                keys.push(i.to_string());
+                check(entry, keys);
+                keys.pop();
+            }
+        }
+        Value::Mapping(ref entries) => {
+            for (key, entry) in entries {
+                keys.push(key.as_str().unwrap_or_default().to_string());
+                check(entry, keys);
+                keys.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects the unknown fields of a document of a Yakefile. Documents following the
+/// first one only have env variables and targets, which is checked when they are
+/// parsed.
+fn check_yakefile_fields(
+    value: &Value,
+    document: usize,
+    keys: &mut Vec<String>,
+    unknown: &mut Vec<UnknownField>,
+) {
+    let minimal: Yake = serde_yaml::from_str("{meta: {doc: '', version: ''}, targets: {}}")
+        .expect("Unable to parse the minimal Yakefile");
+    if document == 0 {
+        check_fields(value, &field_names(&minimal), document, keys, unknown);
+        check_field(value, "meta", keys, |meta, keys| {
+            check_fields(meta, &field_names(&minimal.meta), document, keys, unknown)
+        });
+        for hook in ["before_all", "after_all"] {
+            check_field(value, hook, keys, |commands, keys| {
+                check_command_fields(commands, document, keys, unknown)
+            });
+        }
+        check_field(value, "snippets", keys, |snippets, keys| {
+            check_entries(snippets, keys, |commands, keys| {
+                check_command_fields(commands, document, keys, unknown)
+            })
+        });
+    }
+    check_field(value, "targets", keys, |targets, keys| {
+        check_entries(targets, keys, |target, keys| {
+            check_target_fields(target, document, keys, unknown)
+        })
+    });
+}
+
+/// Collects the unknown fields of a target and it's sub targets.
+fn check_target_fields(
+    value: &Value,
+    document: usize,
+    keys: &mut Vec<String>,
+    unknown: &mut Vec<UnknownField>,
+) {
+    let target = YakeTarget::default();
+    check_fields(value, &field_names(&target), document, keys, unknown);
+    check_field(value, "meta", keys, |meta, keys| {
+        check_fields(meta, &field_names(&target.meta), document, keys, unknown)
+    });
+    for hook in ["exec", "before", "after"] {
+        check_field(value, hook, keys, |commands, keys| {
+            check_command_fields(commands, document, keys, unknown)
+        });
+    }
+    check_field(value, "container", keys, |container, keys| {
+        let known = field_names(&YakeContainer::default());
+        check_fields(container, &known, document, keys, unknown)
+    });
+    check_field(value, "remote", keys, |remote, keys| {
+        let known = field_names(&YakeRemote::default());
+        check_fields(remote, &known, document, keys, unknown)
+    });
+    check_field(value, "params", keys, |params, keys| {
+        let known = field_names(&YakeParamDetails::default());
+        check_entries(params, keys, |param, keys| {
+            check_fields(param, &known, document, keys, unknown)
+        })
+    });
+    check_field(value, "requires_env", keys, |variables, keys| {
+        let known = field_names(&YakeRequiredEnvDetails::default());
+        check_entries(variables, keys, |variable, keys| {
+            check_fields(variable, &known, document, keys, unknown)
+        })
+    });
+    check_field(value, "targets", keys, |targets, keys| {
+        check_entries(targets, keys, |target, keys| {
+            check_target_fields(target, document, keys, unknown)
+        })
+    });
+}
+
+/// Collects the unknown fields of commands given in their object form with `cmd`.
+/// Commands with `set`, `use` or variants for operating systems fail to parse instead.
+fn check_command_fields(
+    value: &Value,
+    document: usize,
+    keys: &mut Vec<String>,
+    unknown: &mut Vec<UnknownField>,
+) {
+    let known = field_names(&YakeCommandDetails::default());
+    check_entries(value, keys, |command, keys| {
+        if command.get("cmd").is_some() {
+            check_fields(command, &known, document, keys, unknown)
+        }
+    });
+}
+
+/// Gets the position of the event following the node starting at `pos`.
+fn skip_node(events: &[(Event, Marker)], mut pos: usize) -> Option<usize> {
+    let mut depth = 0;
+    loop {
+        match events.get(pos)?.0 {
+            Event::MappingStart(_) | Event::SequenceStart(_) => depth += 1,
+            Event::MappingEnd | Event::SequenceEnd => depth -= 1,
+            _ => (),
+        }
+        pos += 1;
+        if depth == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// Finds the nearest Yakefile in a directory or it's parent directories. The path is
+/// returned relative to the directory, e.g. `../../Yakefile`.
+pub fn find_yakefile(directory: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for dir in fs::canonicalize(directory).ok()?.ancestors() {
+        if let Some(name) = find_yakefile_name(dir) {
+            return Some(relative.join(name));
+        }
+        relative.push("..");
+    }
+    None
+}
+
+/// Name of the Yakefile within a directory, the first of `YAKEFILE_NAMES` found.
+fn find_yakefile_name(directory: &Path) -> Option<&'static str> {
+    YAKEFILE_NAMES
+        .iter()
+        .find(|name| directory.join(name).is_file())
+        .cloned()
+}
+
+/// Finds the Yakefiles of the subdirectories of a directory, up to `depth` levels
+/// below it. Directories ignored by git are skipped, see `GitIgnore`, as well as
+/// directories and Yakefiles whose path relative to the directory matches one of the
+/// `exclude` glob patterns.
+fn find_yakefiles(
+    directory: &Path,
+    depth: usize,
+    exclude: &[String],
+) -> Result<Vec<DirEntry>, String> {
     let mut files = Vec::new();
+    let exclude = exclude
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).map_err(|e| format!("Invalid pattern \"{}\": {}", pattern, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let is_excluded = |path: &Path| {
+        let relative: Vec<String> = path
+            .strip_prefix(directory)
+            .unwrap_or(path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let relative = relative.join("/");
+        exclude
+            .iter()
+            .any(|pattern| pattern.matches_with(&relative, options))
+    };
 
-    fn is_yakefile_or_dir(entry: &DirEntry) -> bool {
+    fn is_yakefile(entry: &DirEntry) -> bool {
         entry
-            .file_name()
-            .to_str()
-            .map(|s| s == "Yakefile" || entry.path().is_dir())
-            .unwrap_or(false)
+            .path()
+            .parent()
+            .and_then(find_yakefile_name)
+            .is_some_and(|name| entry.file_name() == name)
     }
 
+    let mut ignore = GitIgnore::new(directory);
+    let mut is_yakefile_or_dir = |entry: &DirEntry| {
+        let is_dir = entry.path().is_dir();
+        if ignore.is_ignored(entry.path(), is_dir) || is_excluded(entry.path()) {
+            return false;
+        }
+        if is_dir {
+            ignore.add_dir(entry.path());
+        }
+        // the Yakefile of the directory itself is no sub yake
+        is_dir || (entry.depth() > 1 && is_yakefile(entry))
+    };
+
     WalkDir::new(directory)
-        .min_depth(2)
-        .max_depth(2)
+        .min_depth(1)
+        .max_depth(depth + 1)
         .into_iter()
-        .filter_entry(|e| is_yakefile_or_dir(e))
+        .filter_entry(&mut is_yakefile_or_dir)
         .filter_map(|v| v.ok())
         .for_each(|v| {
             if v.path().is_file() {
@@ -42,12 +834,94 @@ fn find_yakefiles(directory: &str) -> Result<Vec<DirEntry>, String> {
     Ok(files)
 }
 
-pub fn load_yml_from_subdirs(directory: &str) -> Result<Vec<Yake>, String> {
-    let files = find_yakefiles(directory);
+/// Paths of the sub yakes of a yake: the Yakefiles listed in `meta.includes`, in the
+/// order they are listed, then those in subdirectories if `meta.include_recursively`
+/// is set. Includes may be glob patterns like `services/*/Yakefile`, the files and
+/// directories matching them are included in sorted order, or HTTPS URLs, see
+/// `fetch_remote_include`. Remote includes which are not pinned to a digest are
+/// warned about. Yakefiles which are listed and found in a subdirectory are returned
+/// once.
+pub fn find_sub_yakefiles(yake: &Yake) -> Result<Vec<PathBuf>, YakeError> {
+    // sub yakes are searched next to the Yakefile, which is not necessarily the
+    // current directory
+    let base_dir = yake.get_base_dir();
+    let mut paths = Vec::new();
+    for include in yake.meta.includes.as_deref().unwrap_or_default() {
+        let matches = if is_remote_include(include) {
+            let (path, digest) = fetch_remote_include(&base_dir, include, yake.update_includes)
+                .map_err(|message| YakeError::Load {
+                    path: yake
+                        .source
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from("Yakefile")),
+                    message,
+                })?;
+            if split_pin(include).1.is_none() {
+                Logger::default().warn(&format!(
+                    "Remote include {} is not pinned, pin it with #sha256={}",
+                    include, digest
+                ));
+            }
+            vec![path]
+        } else if include.contains(['*', '?', '[']) {
+            expand_include(&base_dir, include)?
+        } else {
+            vec![base_dir.join(include)]
+        };
+        for mut path in matches {
+            if path.is_dir() {
+                path.push(find_yakefile_name(&path).unwrap_or("Yakefile"));
+            }
+            paths.push(path);
+        }
+    }
+    if let Some(true) = yake.meta.include_recursively {
+        let depth = yake.meta.include_depth.unwrap_or(1);
+        let exclude = yake.meta.include_exclude.as_deref().unwrap_or_default();
+        let files =
+            find_yakefiles(&base_dir, depth, exclude).map_err(|message| YakeError::Load {
+                path: base_dir.clone(),
+                message,
+            })?;
+        paths.extend(files.into_iter().map(DirEntry::into_path));
+    }
+
+    let mut canonical_paths = HashSet::new();
+    paths.retain(|path| canonical_paths.insert(fs::canonicalize(path).unwrap_or(path.clone())));
+    Ok(paths)
+}
+
+/// Files and directories matching an include pattern, relative to the directory of
+/// the Yakefile.
+fn expand_include(base_dir: &Path, include: &str) -> Result<Vec<PathBuf>, YakeError> {
+    let error = |message: String| YakeError::Load {
+        path: base_dir.join(include),
+        message,
+    };
+    let pattern = format!(
+        "{}/{}",
+        Pattern::escape(&base_dir.to_string_lossy()),
+        include
+    );
+    let mut paths = glob(&pattern)
+        .map_err(|e| error(format!("Invalid pattern: {}", e)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| error(format!("Unable to read {}", e)))?;
+    paths.sort();
+    Ok(paths)
+}
+
+pub fn load_yml_from_subdirs(directory: &Path) -> Result<Vec<Yake>, YakeError> {
+    let files = find_yakefiles(directory, 1, &[]).map_err(|message| YakeError::Load {
+        path: directory.to_path_buf(),
+        message,
+    })?;
     let mut yakes = Vec::new();
 
-    for entry in files.unwrap() {
-        yakes.push(load_yml_from_file(entry.path().to_str().unwrap()));
+    // paths are passed on as they are, they are not necessarily valid unicode on
+    // every platform
+    for entry in files {
+        yakes.push(load_yml_from_file(entry.path())?);
     }
 
     Ok(yakes)
@@ -56,20 +930,325 @@ pub fn load_yml_from_subdirs(directory: &str) -> Result<Vec<Yake>, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use yake::YakeTargetType;
 
     #[test]
     fn test_find_yakefiles() {
-        let dir = ".";
+        let dir = Path::new(".");
 
-        let files = find_yakefiles(dir);
+        let files = find_yakefiles(dir, 1, &[]);
         assert_eq!(files.unwrap().len(), 1);
+
+        let dir = Path::new("tests/fixtures/include_depth");
+        let files: Vec<PathBuf> = find_yakefiles(dir, 2, &[])
+            .unwrap()
+            .into_iter()
+            .map(DirEntry::into_path)
+            .collect();
+        assert_eq!(
+            files,
+            vec![PathBuf::from(
+                "tests/fixtures/include_depth/packages/foo/Yakefile"
+            )]
+        );
+        assert_eq!(find_yakefiles(dir, 3, &[]).unwrap().len(), 2);
+        let exclude = ["packages/*/bar".to_string()];
+        assert_eq!(find_yakefiles(dir, 3, &exclude).unwrap().len(), 1);
+        let exclude = ["packages/**".to_string()];
+        assert!(find_yakefiles(dir, 3, &exclude).unwrap().is_empty());
+        assert!(find_yakefiles(dir, 3, &["[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_find_yakefile() {
+        assert_eq!(
+            find_yakefile(Path::new("tests/fixtures/parent/src/nested")),
+            Some(PathBuf::from("../../Yakefile"))
+        );
+        assert_eq!(
+            find_yakefile(Path::new("tests/fixtures/parent")),
+            Some(PathBuf::from("Yakefile"))
+        );
+        assert_eq!(find_yakefile(Path::new("does/not/exist")), None);
     }
 
     #[test]
     fn test_load_yml_from_subdirs() {
-        let dir = ".";
+        let dir = Path::new(".");
 
         let sub_yakes = load_yml_from_subdirs(dir);
         assert_eq!(sub_yakes.unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_find_target_line() {
+        let path = Path::new("tests/fixtures/which/Yakefile");
+
+        assert_eq!(find_target_line(path, "build"), Some(6));
+        assert_eq!(find_target_line(path, "services"), Some(12));
+        assert_eq!(find_target_line(path, "services.lint"), Some(17));
+        assert_eq!(find_target_line(path, "services.build"), None);
+        assert_eq!(find_target_line(path, "lint"), None);
+        assert_eq!(find_target_line(Path::new("does/not/exist"), "build"), None);
+    }
+
+    #[test]
+    fn test_load_yml_from_file_missing() {
+        let path = Path::new("tests/fixtures/does-not-exist/Yakefile");
+
+        match load_yml_from_file(path) {
+            Err(YakeError::Load {
+                path: error_path, ..
+            }) => assert_eq!(error_path, path),
+            result => panic!("expected a load error, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_find_sub_yakefiles() {
+        let yake = load_yml_from_file(Path::new("tests/fixtures/includes/Yakefile")).unwrap();
+
+        assert_eq!(
+            find_sub_yakefiles(&yake).unwrap(),
+            vec![
+                PathBuf::from("tests/fixtures/includes/services/api/Yakefile"),
+                PathBuf::from("tests/fixtures/includes/infra/Yakefile")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_sub_yakefiles_patterns() {
+        let mut yake = load_yml_from_file(Path::new("tests/fixtures/includes/Yakefile")).unwrap();
+        yake.meta.includes = Some(vec![
+            "services/*/Yakefile".to_string(),
+            "infra*".to_string(),
+            "services/api/Yakefile".to_string(),
+            "docs/*".to_string(),
+        ]);
+
+        assert_eq!(
+            find_sub_yakefiles(&yake).unwrap(),
+            vec![
+                PathBuf::from("tests/fixtures/includes/services/api/Yakefile"),
+                PathBuf::from("tests/fixtures/includes/services/web/Yakefile"),
+                PathBuf::from("tests/fixtures/includes/infra/Yakefile")
+            ]
+        );
+
+        yake.meta.includes = Some(vec!["services/[".to_string()]);
+        assert!(find_sub_yakefiles(&yake).is_err());
+    }
+
+    #[test]
+    fn test_load_toml_and_json() {
+        let toml = load_yml_from_file(Path::new("tests/fixtures/formats/toml/Yakefile.toml"));
+        let toml = toml.unwrap();
+        assert_eq!(toml.meta.default_target, Some("greet".to_string()));
+        assert_eq!(
+            toml.get_target_by_name("greet").unwrap().meta.depends,
+            Some(vec!["prepare".to_string()])
+        );
+
+        let json = load_yml_from_file(Path::new("tests/fixtures/formats/json/Yakefile.json"));
+        assert_eq!(json.unwrap().get_target_names(), vec!["greet"]);
+
+        assert_eq!(
+            find_yakefile(Path::new("tests/fixtures/formats/toml")),
+            Some(PathBuf::from("Yakefile.toml"))
+        );
+        assert_eq!(
+            find_sub_yakefiles(
+                &load_yml_from_file(Path::new("tests/fixtures/formats/Yakefile")).unwrap()
+            )
+            .unwrap()
+            .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_parse_yml_documents() {
+        let yake = load_yml_from_file(Path::new("tests/fixtures/documents/Yakefile")).unwrap();
+        let mut names = yake.get_target_names();
+        names.sort();
+        assert_eq!(names, vec!["build", "deploy", "test"]);
+        assert_eq!(yake.env.unwrap().len(), 2);
+        assert_eq!(
+            find_target_line(Path::new("tests/fixtures/documents/Yakefile"), "deploy"),
+            Some(29)
+        );
+
+        let yml = |section: &str| {
+            format!(
+                "meta:\n  doc: docs\n  version: 0.1.0\ntargets:\n  build:\n    meta:\n      doc: build\n      type: callable\n---\n{}",
+                section
+            )
+        };
+        assert!(parse_yml_documents(&yml(""), None).is_ok());
+        assert_eq!(
+            parse_yml_documents(
+                &yml("targets:\n  build:\n    meta:\n      doc: again\n      type: group\n"),
+                None
+            )
+            .unwrap_err(),
+            "Document 2: target build is already defined by a document before"
+        );
+        assert!(parse_yml_documents(&yml("meta:\n  doc: more\n"), None)
+            .unwrap_err()
+            .starts_with("Document 2: unknown field `meta`"));
+    }
+
+    #[test]
+    fn test_find_unknown_fields() {
+        let path = Path::new("tests/fixtures/unknown_fields/Yakefile");
+        assert_eq!(
+            find_unknown_fields(path).unwrap(),
+            vec![
+                "tests/fixtures/unknown_fields/Yakefile:5: Unknown field shel",
+                "tests/fixtures/unknown_fields/Yakefile:4: Unknown field defualt_target in meta",
+                "tests/fixtures/unknown_fields/Yakefile:13: target build: Unknown field defualt in params",
+                "tests/fixtures/unknown_fields/Yakefile:22: target test: Unknown field dependss in meta",
+            ]
+        );
+        assert!(deny_unknown_fields(path).is_err());
+
+        let path = Path::new("tests/fixtures/formats/toml/Yakefile.toml");
+        assert_eq!(find_unknown_fields(path).unwrap(), Vec::<String>::new());
+        assert!(deny_unknown_fields(Path::new("tests/fixtures/groups/Yakefile")).is_ok());
+    }
+
+    #[test]
+    fn test_parse_error_snippet() {
+        let error =
+            parse_yml_documents("meta:\n  doc: \"Docs\n  version: 0.1.0\n", None).unwrap_err();
+        assert!(
+            error.ends_with("at line 2 column 8\n 2 |   doc: \"Docs\n   |        ^"),
+            "{}",
+            error
+        );
+
+        let contents =
+            "meta:\n  doc: Docs\n  version: 0.1.0\ntargets: {}\n---\ntargets:\n  - build\n";
+        assert_eq!(
+            parse_yml_documents(contents, None).unwrap_err(),
+            "Document 2: targets: invalid type: sequence, expected a map at line 7 column 3\n 7 |   - build\n   |   ^"
+        );
+
+        assert_eq!(
+            with_snippet("Failed".to_string(), "a\nb", Some((3, 1))),
+            "Failed"
+        );
+        assert_eq!(with_snippet("Failed".to_string(), "a\nb", None), "Failed");
+    }
+
+    #[test]
+    fn test_find_yakefiles_gitignore() {
+        let dir = std::env::temp_dir().join(format!("yake-find-yakefiles-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for sub_dir in ["api", "node_modules/lib", "web/dist"] {
+            fs::create_dir_all(dir.join(sub_dir)).unwrap();
+            fs::write(dir.join(sub_dir).join("Yakefile"), "").unwrap();
+        }
+        fs::write(dir.join(".gitignore"), "node_modules/\n").unwrap();
+        fs::write(dir.join("web/.gitignore"), "dist\n").unwrap();
+
+        let files: Vec<PathBuf> = find_yakefiles(&dir, 2, &[])
+            .unwrap()
+            .into_iter()
+            .map(DirEntry::into_path)
+            .collect();
+        assert_eq!(files, vec![dir.join("api/Yakefile")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_extends() {
+        let yake = parse_yml_from_file(Path::new("tests/fixtures/extends/Yakefile")).unwrap();
+        assert_eq!(yake.meta.doc, "Yakefile of the repo");
+        assert_eq!(yake.meta.version, "0.1.0");
+        let env = yake.env.clone().unwrap();
+        assert_eq!(env.get("STAGE"), Some(&"test".to_string()));
+        assert_eq!(
+            env.get("REGISTRY"),
+            Some(&"registry.example.com".to_string())
+        );
+        let build = yake.targets.get("build").unwrap();
+        assert_eq!(build.meta.doc, "Builds the app");
+        assert_eq!(build.exec.as_ref().unwrap().len(), 1);
+        assert!(yake.targets.contains_key("lint"));
+        assert!(yake.targets.contains_key("deploy"));
+
+        let dir = std::env::temp_dir().join(format!("yake-extends-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.yml"), "extends: b.yml\n").unwrap();
+        fs::write(dir.join("b.yml"), "extends: a.yml\n").unwrap();
+        let error = parse_yml_from_file(&dir.join("a.yml")).unwrap_err();
+        assert!(error.starts_with("Cyclic extends: "), "{}", error);
+        assert!(error.ends_with("a.yml"), "{}", error);
+
+        fs::write(dir.join("b.yml"), "meta: [").unwrap();
+        let error = parse_yml_from_file(&dir.join("a.yml")).unwrap_err();
+        assert!(
+            error.starts_with(&format!(
+                "Unable to load {}: Unable to parse: ",
+                dir.join("b.yml").display()
+            )),
+            "{}",
+            error
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_target_extends() {
+        let yake =
+            parse_yml_from_file(Path::new("tests/fixtures/target_extends/Yakefile")).unwrap();
+        let web = yake.get_target_by_name("images.web").unwrap();
+        assert_eq!(web.meta.doc, "Builds the web image");
+        assert_eq!(web.meta.target_type, YakeTargetType::Callable);
+        assert_eq!(web.meta.internal, None);
+        assert_eq!(web.extends, None);
+        let env = web.env.unwrap();
+        assert_eq!(env.get("IMAGE"), Some(&"web".to_string()));
+        assert_eq!(env.get("TAG"), Some(&"stable".to_string()));
+        assert_eq!(
+            env.get("REGISTRY"),
+            Some(&"registry.example.com".to_string())
+        );
+        assert_eq!(web.exec.unwrap().len(), 1);
+        let base = yake.get_target_by_name("_docker_base").unwrap();
+        assert_eq!(base.meta.internal, Some(true));
+
+        let parse = |contents: &str| {
+            let value = serde_yaml::from_str(contents).unwrap();
+            resolve_target_extends(value).map(|_| ())
+        };
+        let error = parse("targets:\n  a:\n    extends: b\n  b:\n    extends: a\n").unwrap_err();
+        assert!(
+            error == "Cyclic extends of targets: a -> b -> a"
+                || error == "Cyclic extends of targets: b -> a -> b",
+            "{}",
+            error
+        );
+        assert_eq!(
+            parse("targets:\n  a:\n    extends: c\n"),
+            Err("Target a extends unknown target c".to_string())
+        );
+        assert_eq!(
+            parse("targets:\n  a:\n    extends: [b]\n"),
+            Err("Target a: extends must be the name of a target".to_string())
+        );
+
+        let error = parse_yml_documents(
+            "meta:\n  doc: Y\n  version: 0.1.0\ntargets:\n  a:\n    meta:\n      doc: A\n      type: callable\n---\ntargets:\n  b:\n    extends: a\n    meta:\n      doc: B\n      type: callable\n",
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "Document 2: target b must not extend another target, only targets of the first document can"
+        );
+    }
 }
@@ -0,0 +1,1634 @@
+#[cfg(unix)]
+extern crate libc;
+extern crate serde_json;
+extern crate sha2;
+
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+/// Runs the yake binary within a fixture directory.
+fn run_yake(fixture: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_yake"))
+        .args(args)
+        .current_dir(format!("tests/fixtures/{}", fixture))
+        .output()
+        .expect("Unable to run yake")
+}
+
+#[test]
+fn test_interactive_output_is_not_prefixed() {
+    let output = run_yake("interactive", &["repl"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("\ninside the repl\n"));
+    assert!(!stdout.contains("┆"));
+    assert!(!stdout.contains("↪ Done"));
+}
+
+#[test]
+fn test_interactive_exit_code_propagates() {
+    let output = run_yake("interactive", &["failing"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr.contains("exited with code 3"));
+    assert!(!stdout.contains("never reached"));
+}
+
+#[test]
+fn test_which_json() {
+    let output = run_yake("which", &["which", "api.build", "--format", "json"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("\"target\": \"api.build\""));
+    assert!(stdout.contains("api/Yakefile\",\n    \"line\": 5\n"));
+    assert!(stdout.contains("\"file\": \"Yakefile\",\n      \"line\": 28\n"));
+}
+
+#[test]
+fn test_which_unknown_target() {
+    let output = run_yake("which", &["which", "lint"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown target: 'lint'"));
+}
+
+#[test]
+fn test_silent_success() {
+    let output = run_yake("silent", &["quiet"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("↪ quiet ✓ ("));
+    assert!(!stdout.contains("noisy output"));
+}
+
+#[test]
+fn test_silent_failure() {
+    let output = run_yake("silent", &["failing"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stdout.contains("┆  context for debugging\n"));
+    assert!(stdout.contains("↪ failing ✗ ("));
+    assert!(stderr.contains("┆  error details\n"));
+}
+
+#[test]
+fn test_no_silent() {
+    let output = run_yake("silent", &["--no-silent", "quiet"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  noisy output\n"));
+    assert!(stdout.contains("↪ Done"));
+}
+
+/// Replaces durations like `0.1s` with `N.Ns`, so that output can be compared.
+fn normalize_durations(output: &str) -> String {
+    let mut normalized = String::new();
+    let mut chars = output.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut number = c.to_string();
+            while let Some(&next) = chars.peek() {
+                if next.is_ascii_digit() || next == '.' {
+                    number.push(next);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            if chars.peek() == Some(&'s') && number.contains('.') {
+                normalized.push_str("N.N");
+            } else {
+                normalized.push_str(&number);
+            }
+        } else {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+#[test]
+fn test_ci_folding_github() {
+    let output = run_yake("ci_folding", &["--ci-folding", "github", "build"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        normalize_durations(&String::from_utf8_lossy(&output.stdout)),
+        "::group::install\n\
+         ↪ Executing echo \"installing\":\n\
+         ┆  installing\n\
+         ↪ Done\n\
+         install finished after N.Ns\n\
+         ::endgroup::\n\
+         ::group::build\n\
+         ↪ Executing echo \"building\":\n\
+         ┆  building\n\
+         ↪ Done\n\
+         build finished after N.Ns\n\
+         ::endgroup::\n\
+         Summary:\n  \
+         ✓ install  ok  N.Ns\n  \
+         ✓ build    ok  N.Ns\n\
+         ✓ Passed: 2 ok in N.Ns\n"
+    );
+}
+
+#[test]
+fn test_ci_folding_closed_on_failure() {
+    let output = run_yake("ci_folding", &["--ci-folding", "github", "failing"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        normalize_durations(&String::from_utf8_lossy(&output.stdout)),
+        "::group::failing\n\
+         ↪ Executing exit 1:\n\
+         failing finished after N.Ns\n\
+         ::endgroup::\n"
+    );
+}
+
+#[test]
+fn test_deps_only() {
+    let output = run_yake("deps_only", &["--deps-only", "package"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  compiling\n"));
+    assert!(!stdout.contains("packaging"));
+    assert!(stdout.contains("↪ package skipped (deps-only)\n"));
+    assert!(stdout.contains("  ↷ package  skipped (deps-only)\n"));
+}
+
+#[test]
+fn test_skip_deps() {
+    let output = run_yake("multiple_targets", &["--skip-deps", "build", "test"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆"))
+        .collect();
+    assert_eq!(lines, vec!["┆  building", "┆  testing unit"]);
+}
+
+#[test]
+fn test_dry_run() {
+    let output = run_yake("dry_run", &["--dry-run", "deploy"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "↪ Would execute build:\n\
+         ┆  export STAGE=prod\n\
+         ┆  echo \"building\"\n\
+         ↪ Would execute deploy:\n\
+         ┆  export MESSAGE='deploying to prod'\n\
+         ┆  export STAGE=prod\n\
+         ┆  echo \"$MESSAGE\"; exit 1\n"
+    );
+
+    let output = run_yake("dry_run", &["--dry-run", "migrate"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with(
+        "┆  cat <<'EOF'\n\
+         ┆  -- migrating prod to 0.1.0\n\
+         ┆  select 1;\n\
+         ┆  EOF\n\
+         ┆  cat < statements.sql\n"
+    ));
+}
+
+#[test]
+fn test_jobs_run_dependencies_concurrently() {
+    let rendezvous = std::env::temp_dir().join(format!("yake-jobs-{}", std::process::id()));
+    std::fs::create_dir_all(&rendezvous).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .args(["-j", "2", "both"])
+        .env("RENDEZVOUS", &rendezvous)
+        .current_dir("tests/fixtures/jobs")
+        .output()
+        .expect("Unable to run yake");
+    std::fs::remove_dir_all(&rendezvous).unwrap();
+
+    // the output of the targets is tagged with their names as they run concurrently
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("[left]  ↪ Done\n"));
+    assert!(stdout.contains("[right] ↪ Done\n"));
+    assert!(stdout.contains("[both]  ┆  both finished\n[both]  ↪ Done\n"));
+}
+
+#[test]
+fn test_jobs_invalid() {
+    let output = run_yake("jobs", &["-j", "0", "both"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("must be a positive number"));
+}
+
+#[test]
+fn test_fail_fast() {
+    let output = run_yake("fail_fast", &["deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stdout.contains("┆  compiling\n"));
+    assert!(!stdout.contains("never reached"));
+    assert!(!stdout.contains("deploying"));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Command \"exit 4\" exited with code 4, expected one of [0]"));
+}
+
+#[test]
+fn test_placeholders() {
+    let output = run_yake("placeholders", &["build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("↪ Executing echo \"app:0.1.0\" '{{.Names}}':\n"));
+    assert!(stdout.contains("┆  app:0.1.0 {{.Names}}\n"));
+}
+
+#[test]
+fn test_placeholders_unknown() {
+    let output = run_yake("placeholders", &["typo"]);
+
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("never reached"));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Unknown variable {{IMAEG}} in command \"echo \"{{IMAEG}}\"\""));
+}
+
+#[test]
+fn test_args_after_double_dash() {
+    let output = run_yake("args", &["test", "--", "--nocapture", "it's a test"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  [--nocapture][it's a test]\n"));
+    assert!(stdout.contains("┆  --nocapture it's a test\n"));
+}
+
+#[test]
+fn test_params() {
+    let output = run_yake("params", &["deploy", "stage=prod"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  deploying to prod in eu\n"));
+
+    let output = run_yake("params", &["deploy", "stage=prod", "region=us=1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  deploying to prod in us=1\n"));
+}
+
+#[test]
+fn test_params_invalid() {
+    let output = run_yake("params", &["deploy"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Missing parameter stage of target deploy, give it as stage=<value>"));
+
+    let output = run_yake("params", &["deploy", "stage=prod", "zone=a"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown parameter zone"));
+
+    // arguments without = are targets
+    let output = run_yake("params", &["deploy", "prod"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown target: 'prod'"));
+
+    let output = run_yake("params", &["deploy", "=prod"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected name=value, got =prod"));
+}
+
+#[test]
+fn test_file() {
+    let output = run_yake("file", &["-f", "configs/ci.yml", "greet"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  hello from configs\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("greet")
+        .env("YAKEFILE", "configs/ci.yml")
+        .current_dir("tests/fixtures/file")
+        .output()
+        .expect("Unable to run yake");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  hello from configs\n"));
+}
+
+#[test]
+fn test_yakefile_of_parent_directory() {
+    let output = run_yake("parent/src/nested", &["build"]);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  building in parent\n"));
+}
+
+#[test]
+fn test_profiles() {
+    let output = run_yake("profiles", &["deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  deploying 1 replicas of 1.2 to dev\n"));
+
+    let output = run_yake("profiles", &["--profile", "prod", "deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  deploying 3 replicas of 1.2 to prod, carefully\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("deploy")
+        .env("YAKE_PROFILE", "staging")
+        .current_dir("tests/fixtures/profiles")
+        .output()
+        .expect("Unable to run yake");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Unknown profile: staging, the Yakefile defines prod"));
+}
+
+#[test]
+fn test_requires_env() {
+    let deploy = |envs: &[(&str, &str)]| {
+        Command::new(env!("CARGO_BIN_EXE_yake"))
+            .args(["--no-input", "deploy"])
+            .env_remove("AWS_PROFILE")
+            .env_remove("DATABASE_URL")
+            .envs(envs.iter().copied())
+            .current_dir("tests/fixtures/requires_env")
+            .output()
+            .expect("Unable to run yake")
+    };
+
+    let output = deploy(&[("AWS_PROFILE", "")]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(!stdout.contains("migrating"));
+    assert!(stderr.contains(
+        "Missing env variables: AWS_PROFILE (required by deploy), DATABASE_URL (required by migrate, deploy)"
+    ));
+
+    let output = deploy(&[("AWS_PROFILE", "ops"), ("DATABASE_URL", "postgres://db")]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  migrating ***\n"));
+    assert!(stdout.contains("┆  deploying with ops to eu-west-1\n"));
+}
+
+#[test]
+fn test_env_files() {
+    let output = run_yake("env_file", &["connect"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  local:s3cret@db.example.com\n"));
+
+    let output = run_yake("env_file", &["test"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  local:test@localhost\n"));
+}
+
+#[test]
+fn test_env_overrides() {
+    let output = run_yake(
+        "env_file",
+        &["-e", "DB_HOST=db.test", "--env", "DB_USER=ci", "test"],
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  ci:test@db.test\n"));
+
+    let output = run_yake("env_file", &["-e", "DB_HOST", "test"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected name=value, got DB_HOST"));
+}
+
+#[test]
+fn test_missing_yakefile() {
+    let output = run_yake("file", &["-f", "configs/missing.yml", "greet"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.starts_with("Unable to load configs/missing.yml: File not found"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_default_target() {
+    let output = run_yake("default_target", &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  building\n"));
+    assert!(!stdout.contains("testing"));
+
+    let output = run_yake("default_target", &["test"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("┆  testing\n"));
+    assert!(!stdout.contains("building"));
+}
+
+#[test]
+fn test_target_list_without_default_target() {
+    let output = run_yake("target_list", &[]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Available targets:\n  build            Builds the project\n\ndocker             Docker targets\n  docker.postgres  Starts postgres\n"
+    );
+}
+
+#[test]
+fn test_target_list_nested_groups() {
+    let output = run_yake("groups", &["--list"]);
+
+    // groups head sections nested by their depth, including groups which only
+    // contain groups
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.lines().collect::<Vec<&str>>(),
+        vec![
+            "Available targets:",
+            "  lint                   Lints the project",
+            "",
+            "release                  Releases the project",
+            "",
+            "  release.docker         Docker images",
+            "    release.docker.push  Pushes the images",
+            "",
+            "test                     All tests",
+            "  test.integration       Integration tests",
+            "  test.unit              Unit tests",
+            "",
+            "  test.e2e               End to end tests",
+            "    test.e2e.browser     Browser tests",
+        ]
+    );
+}
+
+#[test]
+fn test_internal_targets() {
+    let output = run_yake("internal", &["deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.find("┆  setting up").unwrap() < stdout.find("┆  deploying").unwrap());
+
+    let output = run_yake("internal", &["setup"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Target setup is internal, it only runs as a dependency of deploy"));
+
+    let output = run_yake("internal", &["--list"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Available targets:\n  deploy  Deploys the app\n"
+    );
+}
+
+#[test]
+fn test_group_runs_children() {
+    let output = run_yake("groups", &["test"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let positions: Vec<usize> = [
+        "┆  linting\n",
+        "┆  unit tests\n",
+        "┆  integration tests\n",
+        "┆  browser tests\n",
+    ]
+    .iter()
+    .map(|line| stdout.find(line).expect(line))
+    .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", stdout);
+
+    let output = run_yake("groups", &["test.e2e"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  browser tests\n"));
+    assert!(!stdout.contains("unit tests"));
+}
+
+#[test]
+fn test_when_conditions() {
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("release")
+        .env("CI", "true")
+        .current_dir("tests/fixtures/when")
+        .output()
+        .expect("Unable to run yake");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  publishing\n"));
+    assert!(stdout.contains("↪ notify skipped (when: env.DEPLOY_ENV == 'production')\n"));
+    assert!(!stdout.contains("┆  notifying\n"));
+    assert!(stdout.contains("┆  released\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("publish")
+        .env_remove("CI")
+        .current_dir("tests/fixtures/when")
+        .output()
+        .expect("Unable to run yake");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("↪ publish skipped (when: "));
+    assert!(!stdout.contains("┆  publishing\n"));
+}
+
+#[test]
+fn test_outputs_up_to_date() {
+    let dir = std::env::temp_dir().join(format!("yake-incremental-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::copy("tests/fixtures/incremental/Yakefile", dir.join("Yakefile")).unwrap();
+    std::fs::write(dir.join("src/a.txt"), "a\n").unwrap();
+    let build = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+            .arg("build")
+            .current_dir(&dir)
+            .output()
+            .expect("Unable to run yake");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    assert!(build().contains("┆  built\n"));
+    assert!(build().contains("↪ build skipped (up to date)\n"));
+
+    let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+    let input = std::fs::File::create(dir.join("src/b.txt")).unwrap();
+    input.set_modified(modified).unwrap();
+    assert!(build().contains("┆  built\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unchanged_inputs() {
+    let dir = std::env::temp_dir().join(format!("yake-fingerprints-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::copy("tests/fixtures/incremental/Yakefile", dir.join("Yakefile")).unwrap();
+    std::fs::write(dir.join("src/a.txt"), "a\n").unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Unable to run yake");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    assert!(run(&["verify"]).contains("┆  verified\n"));
+    assert!(run(&["verify"]).contains("↪ verify skipped (unchanged)\n"));
+    assert!(run(&["--force", "verify"]).contains("┆  verified\n"));
+
+    // rewriting the same content keeps the fingerprint
+    std::fs::write(dir.join("src/a.txt"), "a\n").unwrap();
+    assert!(run(&["verify"]).contains("↪ verify skipped (unchanged)\n"));
+
+    std::fs::write(dir.join("src/a.txt"), "b\n").unwrap();
+    assert!(run(&["verify"]).contains("┆  verified\n"));
+
+    assert!(run(&["stamp"]).contains("┆  stamped\n"));
+    assert!(run(&["stamp"]).contains("┆  stamped\n"));
+    assert!(dir.join(".yake/fingerprints.json").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_watch_runs_target_again_on_changes() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join(format!("yake-watch-cli-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::copy("tests/fixtures/watch/Yakefile", dir.join("Yakefile")).unwrap();
+    std::fs::write(dir.join("src/a.txt"), "first version\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .args(["--watch", "build"])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Unable to run yake");
+    let (lines, received) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let _ = lines.send(line.unwrap());
+        }
+    });
+    let wait_for = |expected: &str| loop {
+        let line = received
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap_or_else(|_| panic!("timed out waiting for {}", expected));
+        if line.contains(expected) {
+            break;
+        }
+    };
+
+    wait_for("┆  first version");
+    wait_for("↪ Watching 1 files for changes");
+    std::fs::write(dir.join("src/a.txt"), "second version\n").unwrap();
+    wait_for("┆  second version");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_timeout() {
+    let started = Instant::now();
+    let output = run_yake("timeout", &["integration"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(output.status.code(), Some(124));
+    assert!(stdout.contains("┆  waiting for server\n"));
+    assert!(!stdout.contains("never reached"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Command \"sleep 30 & echo \"waiting for server\"; wait\" timed out after 500ms"
+    ));
+}
+
+#[test]
+fn test_retries() {
+    let output = run_yake("retries", &["push"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout.matches("┆  pushing\n").count(), 3);
+    assert!(stdout.contains("↪ Retrying echo \"pushing\"; exit 3 in 10ms (1 of 2)\n"));
+    assert!(stdout.contains("↪ Retrying echo \"pushing\"; exit 3 in 20ms (2 of 2)\n"));
+}
+
+#[test]
+fn test_ignore_errors() {
+    let output = run_yake("ignore_errors", &["clean"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains(
+        "↪ Ignoring failure of echo \"no such container\" >&2; exit 1: Command \"echo \"no such container\" >&2; exit 1\" exited with code 1, expected one of [0]\n"
+    ));
+    assert!(stdout.contains("┆  cleaned\n"));
+}
+
+#[test]
+fn test_multiple_targets() {
+    let output = run_yake(
+        "multiple_targets",
+        &["clean", "build", "test", "suite=integration", "-j", "4"],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout.lines().filter(|line| line.contains("┆")).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "[clean]   ┆  cleaning",
+            "[compile] ┆  compiling",
+            "[build]   ┆  building",
+            "[test]    ┆  testing integration"
+        ]
+    );
+}
+
+#[test]
+fn test_graph() {
+    let output = run_yake("groups", &["graph", "--format", "dot"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("digraph yake {\n"));
+    assert!(stdout.contains("    subgraph \"cluster_test\" {\n"));
+    assert!(stdout.contains("        subgraph \"cluster_test.e2e\" {\n"));
+    assert!(stdout.contains("            \"test.e2e.browser\" [tooltip=\"Browser tests\"];\n"));
+    assert!(stdout.contains("    \"test\" -> \"lint\";\n"));
+}
+
+#[test]
+fn test_json_output() {
+    let output = run_yake("fail_fast", &["--output", "json", "deploy"]);
+    assert_eq!(output.status.code(), Some(4));
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout is no JSON document");
+    assert_eq!(report["success"], false);
+    assert_eq!(report["exit_code"], 4);
+    let targets = report["targets"].as_array().unwrap();
+    assert_eq!(targets.len(), 2);
+    assert_eq!(targets[0]["target"], "build");
+    assert_eq!(targets[0]["status"], "failed");
+    assert_eq!(targets[0]["exit_code"], 4);
+    assert_eq!(targets[0]["output"], "compiling\n");
+    assert_eq!(
+        targets[0]["error"],
+        "Command \"exit 4\" exited with code 4, expected one of [0]"
+    );
+    assert_eq!(targets[1]["target"], "deploy");
+    assert_eq!(targets[1]["status"], "not_run");
+}
+
+#[test]
+fn test_summary() {
+    let output = run_yake("fail_fast", &["deploy"]);
+    let stdout = normalize_durations(&String::from_utf8_lossy(&output.stdout));
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stdout.ends_with(
+        "Summary:\n  \
+         ✗ build   failed (exit 4)  N.Ns\n  \
+         - deploy  not run\n\
+         ✗ Failed: 1 failed, 1 not run in N.Ns\n"
+    ));
+
+    // a single target needs no summary
+    let output = run_yake("fail_fast", &["build"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Summary:"));
+}
+
+#[test]
+fn test_cross_file_loop_back() {
+    // the target of the root yakefile referenced back by the sub yakefile runs once
+    let output = run_yake("cross_file_loop", &["x"]);
+    let stdout = normalize_durations(&String::from_utf8_lossy(&output.stdout));
+
+    assert!(output.status.success());
+    assert_eq!(stdout.matches("┆  RUN-Z\n").count(), 1);
+    assert!(stdout.ends_with(
+        "Summary:\n  \
+         ✓ z               ok  N.Ns\n  \
+         ✓ sub/Yakefile:y  ok  N.Ns\n  \
+         ✓ x               ok  N.Ns\n\
+         ✓ Passed: 3 ok in N.Ns\n"
+    ));
+}
+
+#[test]
+fn test_reserved_exit_codes_of_commands() {
+    // commands exiting like a timeout or an interrupt are not mistaken for one
+    for target in ["verify", "abort"] {
+        let output = run_yake("exit_codes", &[target]);
+        assert_eq!(output.status.code(), Some(1));
+    }
+    let output = run_yake("exit_codes", &["slow"]);
+    assert_eq!(output.status.code(), Some(124));
+}
+
+#[test]
+fn test_keep_going() {
+    let output = run_yake("keep_going", &["lint", "package", "docs"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("documenting"));
+
+    // the failed targets exit with 1, the target depending on one of them is not run
+    let output = run_yake("keep_going", &["--keep-going", "lint", "package", "docs"]);
+    let stdout = normalize_durations(&String::from_utf8_lossy(&output.stdout));
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("┆  documenting\n"));
+    assert!(!stdout.contains("packaging"));
+    assert!(stdout.ends_with(
+        "Summary:\n  \
+         ✗ lint     failed (exit 3)  N.Ns\n  \
+         ✗ test     failed (exit 5)  N.Ns\n  \
+         - package  not run\n  \
+         ✓ docs     ok               N.Ns\n\
+         ✗ Failed: 2 failed, 1 not run, 1 ok in N.Ns\n"
+    ));
+}
+
+#[test]
+fn test_timings() {
+    let output = run_yake("multiple_targets", &["--timings", "build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let timings = &stdout[stdout.find("Timings:\n").expect("no timings") + 9..];
+    let timings = &timings[..timings.find("Summary:\n").expect("no summary")];
+    let rows: Vec<&str> = timings
+        .lines()
+        .map(|line| line.trim_end_matches(|c: char| c == 's' || c == '.' || c.is_ascii_digit()))
+        .map(str::trim_end)
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            "  compile",
+            "    echo \"compiling\"",
+            "  build",
+            "    echo \"building\"",
+            "  Total"
+        ]
+    );
+}
+
+#[test]
+fn test_verbosity() {
+    let output = run_yake("multiple_targets", &["-q", "build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert_eq!(stdout, "┆  compiling\n┆  building\n");
+
+    let output = run_yake("multiple_targets", &["-v", "build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("↪ Scheduled build after compile\n"));
+    assert!(stdout.contains("  shell: bash -c 'echo \"building\"'\n"));
+}
+
+#[test]
+fn test_color() {
+    let output = run_yake("multiple_targets", &["build"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains('\x1b'));
+
+    let output = run_yake("multiple_targets", &["--color", "always", "build"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\x1b[1;34m↪ Executing"));
+}
+
+#[test]
+fn test_cwd() {
+    let output = run_yake("cwd", &["root", "web.lint", "web.build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆"))
+        .collect();
+    assert_eq!(lines, vec!["┆  cwd", "┆  web", "┆  app"]);
+
+    let output = run_yake("cwd", &["web.missing"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Working directory \"web/missing\" of target web.missing does not exist"));
+}
+
+#[test]
+fn test_includes() {
+    let output = run_yake("includes", &["api.test", "infra.deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆"))
+        .collect();
+    assert_eq!(lines, vec!["┆  api test in api", "┆  deploying"]);
+
+    let output = run_yake("includes", &["web"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_check() {
+    let output = run_yake("check", &["check"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout.contains("Yakefile:8: target build: Unknown dependency: compile\n"));
+    assert!(stdout.contains("Yakefile:41: target deploy: Unknown field exce\n"));
+    assert!(stdout.contains("broken/Yakefile: Unable to parse: targets: invalid type: sequence, expected a map at line 5 column 3\n 5 |   - build\n   |   ^\n"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Found 11 problem(s) in Yakefile"));
+
+    let output = run_yake("groups", &["check"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "No problems found in Yakefile\n"
+    );
+}
+
+#[test]
+fn test_strict_unknown_fields() {
+    let output = run_yake("unknown_fields", &["test"]);
+    assert!(output.status.success());
+
+    let output = run_yake("unknown_fields", &["--strict", "test"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains("Unable to load Yakefile: Unknown fields:\n"));
+    assert!(stderr.contains("Yakefile:22: target test: Unknown field dependss in meta\n"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("testing"));
+}
+
+#[test]
+fn test_sub_yake_conflict() {
+    let output = run_yake("conflicts", &["api.build"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains(
+        "api/Yakefile: Target api.build is defined by Yakefile and ./api/Yakefile, mark it with `override: true` to replace it\n"
+    ));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("api build"));
+}
+
+#[test]
+fn test_include_strategy_namespace() {
+    let output = run_yake("include_strategy", &["build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  services api build for test\n"));
+    assert!(stdout.contains("┆  libs api build for test\n"));
+}
+
+#[test]
+fn test_include_depth() {
+    let output = run_yake("include_depth", &["build"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  foo build\n"));
+
+    let output = run_yake("include_depth", &["bar.build"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_extends() {
+    let output = run_yake("extends", &["deploy", "lint"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  repo build for test at registry.example.com\n"));
+    assert!(stdout.contains("┆  base lint\n"));
+}
+
+#[test]
+fn test_target_extends() {
+    let output = run_yake("target_extends", &["images"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("┆  building registry.example.com/api:latest\n"));
+    assert!(stdout.contains("┆  building registry.example.com/web:stable\n"));
+
+    let output = run_yake("target_extends", &["--list"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("_docker_base"));
+}
+
+#[test]
+fn test_snippets() {
+    let output = run_yake("snippets", &["api"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout.lines().filter(|l| l.starts_with("┆  ")).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "┆  building api",
+            "┆  logging in to registry.example.com",
+            "┆  pushing api"
+        ]
+    );
+}
+
+#[test]
+fn test_required_version() {
+    let output = run_yake("version", &["build"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains(&format!(
+        "Unable to load Yakefile: Yakefile requires yake 99.0.0 or newer, this is yake {}",
+        env!("CARGO_PKG_VERSION")
+    )));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("never reached"));
+}
+
+#[test]
+fn test_deprecated() {
+    let output = run_yake("deprecated", &["package"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("⚠ Target package is deprecated: use build instead\n"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  packaging\n"));
+
+    let output = run_yake("deprecated", &["--strict", "package"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Target package is deprecated: use build instead"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("packaging"));
+
+    let output = run_yake("deprecated", &["--list"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Available targets:\n  build    Builds the app\n  package  Builds the app, the old way (deprecated: use build instead)\n"
+    );
+}
+
+#[test]
+fn test_after_hooks_run_on_failure() {
+    let output = run_yake("hooks", &["deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(4));
+    let lines: Vec<&str> = stdout.lines().filter(|l| l.starts_with("┆  ")).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "┆  starting services",
+            "┆  deploying",
+            "┆  stopping services"
+        ]
+    );
+    assert!(!stdout.contains("not reached"));
+}
+
+#[test]
+fn test_failing_before_hook_skips_exec() {
+    let output = run_yake("hooks", &["unprepared"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(1));
+    let lines: Vec<&str> = stdout.lines().filter(|l| l.starts_with("┆  ")).collect();
+    assert_eq!(lines, vec!["┆  preparing", "┆  cleaning up"]);
+    assert!(!stdout.contains("deploying"));
+}
+
+#[test]
+fn test_dry_run_prints_hooks() {
+    let output = run_yake("hooks", &["--dry-run", "deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains(
+        "┆  before: echo \"starting services\"\n┆  echo \"deploying\"; exit 4\n┆  echo \"not reached\"\n┆  after: echo \"stopping services\"\n"
+    ));
+}
+
+#[test]
+fn test_global_hooks_run_around_targets() {
+    let output = run_yake("global_hooks", &["test"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout.lines().filter(|l| l.starts_with("┆  ")).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "┆  checking staging",
+            "┆  building",
+            "┆  testing",
+            "┆  notifying"
+        ]
+    );
+}
+
+#[test]
+fn test_after_all_runs_on_failure() {
+    let output = run_yake("global_hooks", &["fail"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(stdout.contains("┆  notifying\n"));
+}
+
+#[test]
+fn test_failing_before_all_skips_targets() {
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("build")
+        .env("BREAK_CHECK", "1")
+        .current_dir("tests/fixtures/global_hooks")
+        .output()
+        .expect("Unable to run yake");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!stdout.contains("building"));
+    assert!(stdout.contains("┆  notifying\n"));
+}
+
+#[test]
+fn test_secrets_are_masked() {
+    let output = run_yake("secrets", &["-vv", "deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stdout.contains("┆  token *** in eu\n"));
+    assert!(stdout.contains("  env: DB_PASSWORD=***\n"));
+    assert!(stdout.contains("↪ Executing echo \"***\" >&2; exit 3:\n"));
+    for text in [&stdout, &stderr] {
+        assert!(!text.contains("s3cr3t-token"));
+        assert!(!text.contains("hunter2"));
+    }
+}
+
+#[test]
+fn test_secrets_are_masked_in_dry_runs() {
+    let output = run_yake("secrets", &["--dry-run", "deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout
+        .contains("┆  export API_TOKEN=***\n┆  export DB_PASSWORD=***\n┆  export REGION=eu\n"));
+    assert!(!stdout.contains("hunter2"));
+}
+
+#[test]
+fn test_import_makefile() {
+    let output = run_yake("import", &["import"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("  default_target: greet\n"));
+    assert!(stdout.contains("      - \"echo \\\"${GREETING} $(cat name.txt)\\\"\"\n"));
+    assert!(stdout.contains("    outputs:\n      - \"name.txt\"\n"));
+}
+
+#[test]
+fn test_import_missing_makefile() {
+    let output = run_yake("import", &["import", "GNUmakefile"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.starts_with("Unable to load GNUmakefile: "));
+}
+
+#[test]
+fn test_export_justfile() {
+    let output = run_yake("hooks", &["export", "--format", "just"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("# Exported from a Yakefile by yake export\n"));
+    assert!(stdout.contains("\ndeploy:\n    #!/usr/bin/env bash\n    set -e\n"));
+    assert!(stdout.contains("    trap after EXIT\n    echo \"starting services\"\n"));
+}
+
+#[test]
+fn test_toml_and_json_yakefiles() {
+    let output = run_yake("formats", &["all"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines,
+        vec![
+            "┆  hello from json",
+            "┆  preparing",
+            "┆  hello from toml",
+            "┆  all greeted"
+        ]
+    );
+}
+
+#[test]
+fn test_multi_document_yakefile() {
+    let output = run_yake("documents", &["deploy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines,
+        vec![
+            "┆  building demo",
+            "┆  testing demo with unit",
+            "┆  deploying demo"
+        ]
+    );
+}
+
+#[test]
+fn test_matrix_targets() {
+    let output = run_yake("matrix", &["build"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines,
+        vec![
+            "┆  building demo for linux/amd64",
+            "┆  building demo for linux/arm64",
+            "┆  building demo for darwin/amd64",
+            "┆  building demo for darwin/arm64"
+        ]
+    );
+
+    let output = run_yake("matrix", &["release"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("┆  building demo for linux/amd64\n"));
+    assert!(!stdout.contains("darwin"));
+}
+
+#[test]
+fn test_builtin_placeholders() {
+    let output = run_yake("builtins", &["info"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let sha = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .expect("Unable to run git");
+    let sha = String::from_utf8_lossy(&sha.stdout).trim().to_string();
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(lines[0], format!("┆  sha {}", sha));
+    let build = lines[1].strip_prefix("┆  build ").unwrap();
+    assert!(build.starts_with(&sha[..7]));
+    assert_eq!(build.len(), sha[..7].len() + 37);
+    assert!(lines[2].starts_with("┆  at 20"));
+    assert!(!lines[2].contains("{{"));
+
+    // the values are determined once per execution
+    assert_eq!(lines[3], format!("{} again", lines[1]));
+    assert_eq!(lines[4], format!("┆  uuid {}", &build[8..]));
+}
+
+#[test]
+fn test_set_command_output() {
+    let output = run_yake("set", &["release"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines,
+        vec!["┆  version 1.2.3", "┆  releasing 1.2.3 as v1.2.3"]
+    );
+
+    let output = run_yake("set", &["unrelated"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("┆  version is unset\n"));
+}
+
+#[test]
+fn test_os_commands() {
+    let output = run_yake("os_commands", &["open"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+    let expected = match std::env::consts::OS {
+        "linux" => "┆  xdg-open docs",
+        "macos" => "┆  open docs",
+        _ => "┆  start docs",
+    };
+
+    assert!(output.status.success());
+    assert_eq!(lines, vec![expected, "┆  opened"]);
+}
+
+#[test]
+fn test_script_mode() {
+    let output = run_yake("script_mode", &["script", "lines"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(lines, vec!["┆  hello from /tmp", "┆  greeting is unset"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_is_forwarded() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::Stdio;
+
+    let started = Instant::now();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("summarize")
+        .current_dir("tests/fixtures/interrupt")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Unable to run yake");
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut output = String::new();
+    while !output.contains("┆  serving\n") {
+        assert!(stdout.read_line(&mut output).unwrap() > 0);
+    }
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+    stdout.read_to_string(&mut output).unwrap();
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    let status = child.wait().unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(status.code(), Some(130));
+    assert!(output.contains("┆  cleaning up\n"));
+    assert!(output.contains("┆  after all\n"));
+    assert!(!output.contains("┆  never"));
+    assert!(!output.contains("never started"));
+    assert!(stderr.contains("was interrupted"));
+}
+
+#[test]
+fn test_services() {
+    let dir = std::env::temp_dir().join(format!("yake-services-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy("tests/fixtures/services/Yakefile", dir.join("Yakefile")).unwrap();
+    let yake = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Unable to run yake");
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    assert_eq!(yake(&["status"]), "db  stopped\n");
+    let started = Instant::now();
+    let stdout = yake(&["app"]);
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(stdout.contains("┆  preparing db\n"));
+    assert!(stdout.contains("↪ Started db (pid "));
+    assert!(stdout.contains("logging to .yake/services/db.log"));
+    assert!(stdout.contains("┆  using db\n"));
+    assert!(yake(&["status", "db"]).starts_with("db  running, pid "));
+    assert!(yake(&["start", "db"]).contains("↪ db skipped (running, pid "));
+
+    assert!(yake(&["stop"]).starts_with("↪ Stopped db (pid "));
+    assert_eq!(yake(&["status"]), "db  stopped\n");
+    assert_eq!(yake(&["stop", "db"]), "db is not running\n");
+    let log = std::fs::read_to_string(dir.join(".yake/services/db.log")).unwrap();
+    assert_eq!(log, "db started\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_start_only_starts_services() {
+    let output = run_yake("services", &["start", "app"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Target app is not a service, services are [\"db\"]"));
+}
+
+#[test]
+fn test_parallel_grouped_output() {
+    let output = run_yake("parallel", &["lint"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("┆  "))
+        .collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines,
+        vec![
+            "┆  [1] api clean",
+            "┆  [1] api done",
+            "┆  [2] web clean",
+            "┆  [2] web done"
+        ]
+    );
+}
+
+#[test]
+fn test_parallel_bounded_by_jobs() {
+    // position of the start of the third command and of the first end
+    let waves = |args: &[&str]| {
+        let output = run_yake("parallel", args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let lines: Vec<String> = stdout.lines().map(str::to_string).collect();
+        let position = |line: &str| lines.iter().position(|l| l == line).unwrap();
+        let first_end = lines.iter().position(|l| l.ends_with("] end")).unwrap();
+        (position("┆  [3] start"), first_end)
+    };
+
+    // all commands start at once by default, with -j 2 the third one waits for a
+    // free slot
+    let (third, first_end) = waves(&["generate"]);
+    assert!(third < first_end);
+    let (third, first_end) = waves(&["-j", "2", "generate"]);
+    assert!(third > first_end);
+}
+
+#[test]
+fn test_init() {
+    let dir = std::env::temp_dir().join(format!("yake-init-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let yake = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_yake"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Unable to run yake")
+    };
+
+    let output = yake(&["init", "--template", "rust"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Created ./Yakefile"));
+    let output = yake(&["--list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("release"));
+
+    let output = yake(&["init"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Unable to create ./Yakefile: it exists already"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_remote_include() {
+    let dir = std::env::temp_dir().join(format!("yake-remote-cli-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let url = "https://yake.invalid/shared/ci/Yakefile";
+    let hex = |content: &[u8]| -> String {
+        Sha256::digest(content)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    };
+    let remote = "meta:\n  doc: \"Shared targets\"\n  version: 0.1.0\ntargets:\n  lint:\n    meta:\n      doc: \"Lints\"\n      type: callable\n    exec:\n      - pwd\n";
+    let digest = hex(remote.as_bytes());
+    let cached = dir.join(".yake/includes").join(&digest).join("ci/Yakefile");
+    std::fs::create_dir_all(cached.parent().unwrap()).unwrap();
+    std::fs::write(&cached, remote).unwrap();
+    let write_yakefile = |include: &str| {
+        let yakefile = format!(
+            "meta:\n  doc: \"Includes shared targets\"\n  version: 0.1.0\n  includes:\n    - {}\ntargets: {{}}\n",
+            include
+        );
+        std::fs::write(dir.join("Yakefile"), yakefile).unwrap();
+    };
+    let yake = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_yake"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Unable to run yake")
+    };
+
+    let pinned = format!("{}#sha256={}", url, digest);
+    write_yakefile(&pinned);
+    let output = yake(&["ci.lint"]);
+    assert!(output.status.success());
+    let pwd = std::fs::canonicalize(&dir).unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("┆  {}\n", pwd.display())));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    // unpinned includes use the content downloaded last and are warned about
+    let index = dir
+        .join(".yake/includes/urls")
+        .join(&hex(url.as_bytes())[..16]);
+    std::fs::create_dir_all(index.parent().unwrap()).unwrap();
+    std::fs::write(&index, &digest).unwrap();
+    write_yakefile(url);
+    let output = yake(&["ci.lint"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "⚠ Remote include {} is not pinned, pin it with #sha256={}\n",
+            url, digest
+        )
+    );
+    let output = yake(&["includes"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("- {}\n", pinned)
+    );
+    let output = yake(&["includes", "--update-hashes"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    // pinned includes are printed as they are, local ones not at all
+    std::fs::create_dir_all(dir.join("local")).unwrap();
+    std::fs::write(dir.join("local/Yakefile"), "targets: {}\n").unwrap();
+    write_yakefile(&format!("{}\n    - local/Yakefile", pinned));
+    let output = yake(&["includes"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("- {}\n", pinned)
+    );
+
+    // with --update-hashes outdated pins are replaced by the digest of the content
+    // downloaded again, with curl replaced by a script printing the new content
+    let updated = remote.replace("Lints", "Lints all");
+    let updated_digest = hex(updated.as_bytes());
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    std::fs::write(dir.join("remote.yml"), &updated).unwrap();
+    std::fs::write(bin.join("curl"), "#!/bin/sh\ncat \"$PWD/remote.yml\"\n").unwrap();
+    std::fs::set_permissions(
+        bin.join("curl"),
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )
+    .unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .args(["includes", "--update-hashes"])
+        .env("PATH", path)
+        .current_dir(&dir)
+        .output()
+        .expect("Unable to run yake");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("- {}#sha256={}\n", url, updated_digest)
+    );
+    let cached = dir
+        .join(".yake/includes")
+        .join(&updated_digest)
+        .join("ci/Yakefile");
+    assert_eq!(std::fs::read_to_string(cached).unwrap(), updated);
+
+    write_yakefile(&format!("{}#sha256={}", url, hex(b"other")));
+    let output = yake(&["ci.lint"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Unable to load Yakefile: Unable to download https://yake.invalid/shared/ci/Yakefile"
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_remote_env_on_stdin() {
+    // ssh is replaced by a script running the remote command locally
+    let fixture = std::fs::canonicalize("tests/fixtures/remote").unwrap();
+    let args = std::env::temp_dir().join(format!("yake-ssh-{}", std::process::id()));
+    let path = format!("{}:{}", fixture.display(), std::env::var("PATH").unwrap());
+    let output = Command::new(env!("CARGO_BIN_EXE_yake"))
+        .arg("deploy")
+        .env("PATH", path)
+        .env("SSH_ARGS", &args)
+        .current_dir(&fixture)
+        .output()
+        .expect("Unable to run yake");
+
+    // the env arrives ahead of the input of the command, without being an argument
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains(
+        "┆  deploying prod\n\
+         ┆  token given\n\
+         ┆  input of the command\n"
+    ));
+    let args = std::fs::read_to_string(&args).and_then(|content| {
+        std::fs::remove_file(&args)?;
+        Ok(content)
+    });
+    let args = args.unwrap();
+    assert!(args.starts_with("-T -o BatchMode=yes example.com eval"));
+    assert!(!args.contains("s3cret"));
+    assert!(!args.contains("STAGE=prod"));
+}
+
+#[test]
+fn test_env_references() {
+    let output = run_yake("env_references", &["health"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("┆  https://api.example.com/health\n"));
+
+    // invalid env fails like an invalid Yakefile, before anything runs
+    let output = run_yake("env_references", &["health", "cyclic"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("api.example.com"));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Invalid env of target cyclic: Cyclic reference in env variables: A -> B -> A\n"
+    );
+}